@@ -4,6 +4,15 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SchemaContext {
     pub tables: Vec<TableContext>,
+    #[serde(default)]
+    pub enums: Vec<EnumContext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnumContext {
+    pub schema: String,
+    pub name: String,
+    pub labels: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +34,22 @@ pub struct ColumnContext {
 impl SchemaContext {
     pub fn to_ddl_summary(&self) -> String {
         let mut out = String::new();
+        for e in &self.enums {
+            let labels: Vec<String> = e
+                .labels
+                .iter()
+                .map(|l| format!("'{}'", l.replace('\'', "''")))
+                .collect();
+            out.push_str(&format!(
+                "CREATE TYPE {}.{} AS ENUM ({});\n",
+                e.schema,
+                e.name,
+                labels.join(", ")
+            ));
+        }
+        if !self.enums.is_empty() {
+            out.push('\n');
+        }
         for table in &self.tables {
             out.push_str(&format!("-- {}.{}\n", table.schema, table.name));
             out.push_str(&format!(
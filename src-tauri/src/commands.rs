@@ -1,7 +1,10 @@
-use crate::ai::{AIConfig, AIProvider, AIService, SchemaContext, TableContext, ColumnContext};
+use crate::ai::{AIConfig, AIProvider, AIService, SchemaContext, TableContext, ColumnContext, EnumContext};
 use crate::db::{self, ConnectionConfig, ConnectionManager};
-use crate::storage::{ConnectionRecord, LocalDb, QueryHistoryEntry, SavedQuery};
+use crate::storage::{
+    ConnectionRecord, LocalDb, QueryHistoryEntry, QueryParameter, SavedQuery, SchemaSnapshotRecord,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tauri::State;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,6 +18,10 @@ pub struct ConnectionInput {
     pub password: String,
     pub ssl_mode: Option<String>,
     pub color: Option<String>,
+    pub protected: Option<bool>,
+    pub row_limit: Option<u32>,
+    pub cell_byte_budget: Option<u32>,
+    pub execution_mode: Option<db::ExecutionMode>,
 }
 
 impl From<&ConnectionInput> for ConnectionConfig {
@@ -29,6 +36,10 @@ impl From<&ConnectionInput> for ConnectionConfig {
             password: input.password.clone(),
             ssl_mode: Default::default(),
             color: input.color.clone(),
+            protected: input.protected.unwrap_or(false),
+            row_limit: input.row_limit.unwrap_or(db::DEFAULT_ROW_LIMIT),
+            cell_byte_budget: input.cell_byte_budget.unwrap_or(db::DEFAULT_CELL_BYTE_BUDGET),
+            execution_mode: input.execution_mode.unwrap_or_default(),
         }
     }
 }
@@ -44,6 +55,7 @@ pub async fn test_connection(input: ConnectionInput) -> Result<String, String> {
 #[tauri::command]
 pub async fn connect(
     input: ConnectionInput,
+    app: tauri::AppHandle,
     manager: State<'_, ConnectionManager>,
     local_db: State<'_, LocalDb>,
 ) -> Result<(), String> {
@@ -54,7 +66,36 @@ pub async fn connect(
             config.password = pw;
         }
     }
-    manager.connect(&config).await.map_err(|e| e.to_string())
+    // Settings not sent by the client come from the saved connection
+    if let Ok(conns) = local_db.list_connections().await {
+        if let Some(record) = conns.iter().find(|c| c.id == config.id) {
+            if input.protected.is_none() {
+                config.protected = record.protected;
+            }
+            if input.row_limit.is_none() {
+                config.row_limit = record.row_limit;
+            }
+            if input.cell_byte_budget.is_none() {
+                config.cell_byte_budget = record.cell_byte_budget;
+            }
+            if input.execution_mode.is_none() {
+                config.execution_mode = db::ExecutionMode::from_name(&record.execution_mode);
+            }
+        }
+    }
+    manager.connect(&config).await.map_err(|e| e.to_string())?;
+
+    // Record the catalog in the background; a new version is only stored
+    // when it changed since the last connect
+    tauri::async_runtime::spawn(async move {
+        use tauri::Manager;
+        let manager = app.state::<ConnectionManager>();
+        let local_db = app.state::<LocalDb>();
+        if let Err(e) = record_schema_snapshot(&config.id, "connect", &manager, &local_db).await {
+            eprintln!("Failed to snapshot schema of {}: {}", config.id, e);
+        }
+    });
+    Ok(())
 }
 
 #[tauri::command]
@@ -68,19 +109,84 @@ pub async fn disconnect(
         .map_err(|e| e.to_string())
 }
 
+/// Either the query result, or a request to confirm destructive statements
+/// on a protected connection by calling again with the returned token.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExecuteResponse {
+    Completed(db::QueryResult),
+    ConfirmationRequired(db::ConfirmationRequired),
+}
+
+/// On protected connections, destructive statements need a valid confirmation token.
+/// Returns the confirmation request to hand back when one is missing or invalid.
+async fn require_confirmation(
+    config: &ConnectionConfig,
+    client: &std::sync::Arc<tokio_postgres::Client>,
+    connection_id: &str,
+    sql: &str,
+    confirmation_token: Option<&str>,
+    confirmations: &db::PendingConfirmations,
+) -> Option<db::ConfirmationRequired> {
+    if !config.protected {
+        return None;
+    }
+    let mut destructive = db::classify_statements(sql);
+    if destructive.is_empty() {
+        return None;
+    }
+    let confirmed = match confirmation_token {
+        Some(token) => confirmations.redeem(token, connection_id, sql).await,
+        None => false,
+    };
+    if confirmed {
+        return None;
+    }
+    db::estimate_impact(client, &mut destructive).await;
+    Some(confirmations.issue(connection_id, sql, destructive).await)
+}
+
 #[tauri::command]
 pub async fn execute_query(
     connection_id: String,
     sql: String,
+    confirmation_token: Option<String>,
     manager: State<'_, ConnectionManager>,
+    confirmations: State<'_, db::PendingConfirmations>,
+    meta: State<'_, db::MetaState>,
     local_db: State<'_, LocalDb>,
-) -> Result<db::QueryResult, String> {
+) -> Result<ExecuteResponse, String> {
     let client = manager
         .get_client(&connection_id)
         .await
         .map_err(|e| e.to_string())?;
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // psql-style backslash commands are answered locally and not kept in history
+    if let Some(command) = db::parse_meta_command(&sql) {
+        return db::run_meta_command(&client, &connection_id, &config, &meta, &command)
+            .await
+            .map(ExecuteResponse::Completed)
+            .map_err(|e| e.to_string());
+    }
 
-    match db::execute_query(&client, &sql).await {
+    if let Some(request) = require_confirmation(
+        &config,
+        &client,
+        &connection_id,
+        &sql,
+        confirmation_token.as_deref(),
+        &confirmations,
+    )
+    .await
+    {
+        return Ok(ExecuteResponse::ConfirmationRequired(request));
+    }
+
+    match db::execute_query_limited(&client, &sql, config.query_limits()).await {
         Ok(result) => {
             // Save to history
             let _ = local_db
@@ -93,7 +199,10 @@ pub async fn execute_query(
                     None,
                 )
                 .await;
-            Ok(result)
+            let settings = meta.get(&connection_id).await;
+            Ok(ExecuteResponse::Completed(db::apply_display_settings(
+                result, settings,
+            )))
         }
         Err(e) => {
             let error_msg = e.to_string();
@@ -106,6 +215,190 @@ pub async fn execute_query(
     }
 }
 
+/// Re-run a SELECT that came back `truncated`, this time without the row limit.
+#[tauri::command]
+pub async fn fetch_all_rows(
+    connection_id: String,
+    sql: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::QueryResult, String> {
+    if !db::row_limit_applies(&sql) {
+        return Err("Only a single SELECT query can be fetched in full".into());
+    }
+    let client = manager
+        .get_client(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let limits = db::QueryLimits {
+        row_limit: 0,
+        ..config.query_limits()
+    };
+    db::execute_query_limited(&client, &sql, limits)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Fetch the untruncated value of a single cell, addressed by primary key.
+#[tauri::command]
+pub async fn fetch_cell(
+    connection_id: String,
+    schema: String,
+    table: String,
+    column: String,
+    key: HashMap<String, serde_json::Value>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<serde_json::Value, String> {
+    let client = manager
+        .get_client(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    db::fetch_cell(&client, &schema, &table, &column, &key)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ── Background query jobs ──
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SubmitResponse {
+    Submitted { job_id: String },
+    ConfirmationRequired(db::ConfirmationRequired),
+}
+
+/// Start a query in the background. Progress and completion are emitted as
+/// `query-job` events. Queries sharing a `session_id` run on the same backend
+/// session; without one the job gets a dedicated connection.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_query(
+    connection_id: String,
+    sql: String,
+    session_id: Option<String>,
+    fetch_all: Option<bool>,
+    confirmation_token: Option<String>,
+    app: tauri::AppHandle,
+    manager: State<'_, ConnectionManager>,
+    confirmations: State<'_, db::PendingConfirmations>,
+    jobs: State<'_, db::JobManager>,
+) -> Result<SubmitResponse, String> {
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let client = match session_id {
+        Some(ref session_id) => manager
+            .get_session_client(&connection_id, session_id)
+            .await
+            .map_err(|e| e.to_string())?,
+        None => std::sync::Arc::new(
+            manager
+                .open_dedicated(&connection_id)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+    };
+
+    if let Some(request) = require_confirmation(
+        &config,
+        &client,
+        &connection_id,
+        &sql,
+        confirmation_token.as_deref(),
+        &confirmations,
+    )
+    .await
+    {
+        return Ok(SubmitResponse::ConfirmationRequired(request));
+    }
+
+    let mut limits = config.query_limits();
+    if fetch_all.unwrap_or(false) {
+        limits.row_limit = 0;
+    }
+
+    let listener: db::JobListener = std::sync::Arc::new(move |info: &db::JobInfo| {
+        use tauri::{Emitter, Manager};
+        let _ = app.emit("query-job", info);
+        if info.status == db::JobStatus::Running {
+            return;
+        }
+        // Save to history once the job is done
+        let app = app.clone();
+        let info = info.clone();
+        tauri::async_runtime::spawn(async move {
+            let local_db = app.state::<LocalDb>();
+            let success = info.status == db::JobStatus::Completed;
+            let _ = local_db
+                .add_history(
+                    &info.connection_id,
+                    &info.sql,
+                    info.elapsed_ms as i64,
+                    if success { info.rows_received as i64 } else { 0 },
+                    success,
+                    info.error.as_deref(),
+                )
+                .await;
+        });
+    });
+
+    let job_id = jobs.submit(
+        client,
+        &connection_id,
+        session_id.as_deref(),
+        &sql,
+        limits,
+        listener,
+    );
+    Ok(SubmitResponse::Submitted { job_id })
+}
+
+#[tauri::command]
+pub async fn list_jobs(
+    connection_id: Option<String>,
+    jobs: State<'_, db::JobManager>,
+) -> Result<Vec<db::JobInfo>, String> {
+    Ok(jobs.list(connection_id.as_deref()))
+}
+
+#[tauri::command]
+pub async fn get_job_result(
+    job_id: String,
+    jobs: State<'_, db::JobManager>,
+) -> Result<db::QueryResult, String> {
+    jobs.result(&job_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cancel_job(job_id: String, jobs: State<'_, db::JobManager>) -> Result<(), String> {
+    jobs.cancel(&job_id).await.map_err(|e| e.to_string())
+}
+
+/// How long finished jobs and their results are kept around.
+#[tauri::command]
+pub async fn set_job_retention(
+    seconds: u64,
+    jobs: State<'_, db::JobManager>,
+) -> Result<(), String> {
+    jobs.set_retention(std::time::Duration::from_secs(seconds));
+    Ok(())
+}
+
+/// Close the backend session a tab was using, discarding its session state.
+#[tauri::command]
+pub async fn close_session(
+    connection_id: String,
+    session_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<(), String> {
+    manager.close_session(&connection_id, &session_id).await;
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn switch_database(
     connection_id: String,
@@ -133,6 +426,10 @@ pub async fn switch_database(
         password,
         ssl_mode: Default::default(),
         color: record.color.clone(),
+        protected: record.protected,
+        row_limit: record.row_limit,
+        cell_byte_budget: record.cell_byte_budget,
+        execution_mode: db::ExecutionMode::from_name(&record.execution_mode),
     };
 
     manager.connect(&config).await.map_err(|e| e.to_string())
@@ -166,13 +463,14 @@ pub async fn get_schemas(
 pub async fn get_tables(
     connection_id: String,
     schema: String,
+    nested: Option<bool>,
     manager: State<'_, ConnectionManager>,
 ) -> Result<Vec<db::TableInfo>, String> {
     let client = manager
         .get_client(&connection_id)
         .await
         .map_err(|e| e.to_string())?;
-    db::get_tables(&client, &schema)
+    db::get_tables(&client, &schema, nested.unwrap_or(false))
         .await
         .map_err(|e| e.to_string())
 }
@@ -248,6 +546,594 @@ pub async fn get_policies(
     db::get_policies(&client, &schema, &table).await.map_err(|e| e.to_string())
 }
 
+/// Run a query as another role inside a rolled-back transaction, with optional
+/// custom settings such as `app.tenant_id`, to see what RLS lets it read.
+#[tauri::command]
+pub async fn test_rls_as_role(
+    connection_id: String,
+    role: String,
+    sql: String,
+    settings: Option<HashMap<String, String>>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::RlsTestResult, String> {
+    let config = manager.get_config(&connection_id).await.map_err(|e| e.to_string())?;
+    let client = manager
+        .open_dedicated(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    db::run_as_role(
+        &std::sync::Arc::new(client),
+        &role,
+        &settings.unwrap_or_default(),
+        &sql,
+        config.query_limits(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_functions(
+    connection_id: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::FunctionInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_functions(&client, &schema).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_function_definition(
+    connection_id: String,
+    oid: u32,
+    manager: State<'_, ConnectionManager>,
+) -> Result<String, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_function_definition(&client, oid).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_views(
+    connection_id: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+    local_db: State<'_, LocalDb>,
+) -> Result<Vec<db::ViewInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    let config = manager.get_config(&connection_id).await.map_err(|e| e.to_string())?;
+    let mut views = db::get_views(&client, &schema).await.map_err(|e| e.to_string())?;
+    let refreshes = local_db
+        .get_matview_refreshes(&connection_id, &config.database, &schema)
+        .await
+        .unwrap_or_default();
+    for view in views.iter_mut().filter(|v| v.is_materialized) {
+        view.last_refresh = refreshes.get(&view.name).cloned();
+    }
+    Ok(views)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshProgress {
+    pub refresh_id: String,
+    pub elapsed_ms: u128,
+    /// What the refreshing backend is waiting on, e.g. "Lock: relation"
+    pub wait_event: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefreshResult {
+    pub elapsed_ms: u128,
+}
+
+/// Run REFRESH MATERIALIZED VIEW on its own connection. Postgres reports no
+/// progress for it, so `matview-refresh-progress` events carry the elapsed time
+/// and whatever the backend is waiting on, sampled from pg_stat_activity.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn refresh_materialized_view(
+    refresh_id: String,
+    connection_id: String,
+    schema: String,
+    name: String,
+    concurrently: Option<bool>,
+    with_data: Option<bool>,
+    app: tauri::AppHandle,
+    manager: State<'_, ConnectionManager>,
+    local_db: State<'_, LocalDb>,
+) -> Result<RefreshResult, String> {
+    use tauri::Emitter;
+
+    let concurrently = concurrently.unwrap_or(false);
+    let with_data = with_data.unwrap_or(true);
+    if concurrently && !with_data {
+        return Err("CONCURRENTLY cannot be combined with WITH NO DATA".into());
+    }
+    let started = std::time::Instant::now();
+    let monitor = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    let config = manager.get_config(&connection_id).await.map_err(|e| e.to_string())?;
+    let client = manager
+        .open_dedicated(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let pid: i32 = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .await
+        .map_err(|e| e.to_string())?
+        .get(0);
+
+    let sql = format!(
+        "REFRESH MATERIALIZED VIEW {}{}.{}{}",
+        if concurrently { "CONCURRENTLY " } else { "" },
+        db::quote_ident(&schema),
+        db::quote_ident(&name),
+        if with_data { "" } else { " WITH NO DATA" }
+    );
+    let refresh = client.batch_execute(&sql);
+    tokio::pin!(refresh);
+    let mut ticker = tokio::time::interval(std::time::Duration::from_millis(500));
+    let outcome = loop {
+        tokio::select! {
+            outcome = &mut refresh => break outcome,
+            _ = ticker.tick() => {
+                let wait_event: Option<String> = monitor
+                    .query_opt(
+                        "SELECT wait_event_type || ': ' || wait_event FROM pg_stat_activity WHERE pid = $1",
+                        &[&pid],
+                    )
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|row| row.get(0));
+                let _ = app.emit(
+                    "matview-refresh-progress",
+                    RefreshProgress {
+                        refresh_id: refresh_id.clone(),
+                        elapsed_ms: started.elapsed().as_millis(),
+                        wait_event,
+                    },
+                );
+            }
+        }
+    };
+    outcome.map_err(|e| db::db_error_message(&e))?;
+
+    let elapsed_ms = started.elapsed().as_millis();
+    let _ = local_db
+        .record_matview_refresh(&connection_id, &config.database, &schema, &name, elapsed_ms as i64)
+        .await;
+    Ok(RefreshResult { elapsed_ms })
+}
+
+#[tauri::command]
+pub async fn get_sequences(
+    connection_id: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::SequenceInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_sequences(&client, &schema).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_types(
+    connection_id: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::TypeInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_types(&client, &schema).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_extensions(
+    connection_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::ExtensionInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_extensions(&client).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_available_extensions(
+    connection_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::AvailableExtension>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_available_extensions(&client).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_extension_objects(
+    connection_id: String,
+    name: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::ExtensionObject>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_extension_objects(&client, &name).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn preview_extension_change(
+    connection_id: String,
+    name: String,
+    change: db::ExtensionChange,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::ExtensionChangePreview, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::preview_extension_change(&client, &name, &change)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Either the DDL that was applied, or a request to confirm it on a protected
+/// connection by calling again with the returned token.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ApplyResponse {
+    Applied { ddl: String },
+    ConfirmationRequired(db::ConfirmationRequired),
+}
+
+/// Create, update, move or drop an extension. Returns the statement that ran,
+/// or a confirmation request when the change is destructive on a protected
+/// connection.
+#[tauri::command]
+pub async fn apply_extension_change(
+    connection_id: String,
+    name: String,
+    change: db::ExtensionChange,
+    confirmation_token: Option<String>,
+    manager: State<'_, ConnectionManager>,
+    confirmations: State<'_, db::PendingConfirmations>,
+) -> Result<ApplyResponse, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let ddl = db::extension_ddl(&name, &change);
+    if let Some(request) = require_confirmation(
+        &config,
+        &client,
+        &connection_id,
+        &ddl,
+        confirmation_token.as_deref(),
+        &confirmations,
+    )
+    .await
+    {
+        return Ok(ApplyResponse::ConfirmationRequired(request));
+    }
+    client
+        .batch_execute(&ddl)
+        .await
+        .map_err(|e| db::db_error_message(&e))?;
+    Ok(ApplyResponse::Applied { ddl })
+}
+
+#[tauri::command]
+pub async fn get_partitioned_tables(
+    connection_id: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::PartitionedTableInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_partitioned_tables(&client, &schema)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_partition_tree(
+    connection_id: String,
+    schema: String,
+    table: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::PartitionNode, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_partition_tree(&client, &schema, &table)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn preview_partition_change(
+    connection_id: String,
+    schema: String,
+    table: String,
+    change: db::PartitionChange,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::PartitionChangePreview, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::preview_partition_change(&client, &schema, &table, &change)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Create, attach or detach a partition of `schema.table`. Returns the
+/// statement that ran, or a confirmation request when the change is
+/// destructive on a protected connection. It runs on a dedicated connection,
+/// as DETACH CONCURRENTLY cannot be part of a transaction block and waits for
+/// every query using the table.
+#[tauri::command]
+pub async fn apply_partition_change(
+    connection_id: String,
+    schema: String,
+    table: String,
+    change: db::PartitionChange,
+    confirmation_token: Option<String>,
+    manager: State<'_, ConnectionManager>,
+    confirmations: State<'_, db::PendingConfirmations>,
+) -> Result<ApplyResponse, String> {
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let client = std::sync::Arc::new(
+        manager
+            .open_dedicated(&connection_id)
+            .await
+            .map_err(|e| e.to_string())?,
+    );
+    let ddl = db::partition_ddl(&schema, &table, &change);
+    if let Some(request) = require_confirmation(
+        &config,
+        &client,
+        &connection_id,
+        &ddl,
+        confirmation_token.as_deref(),
+        &confirmations,
+    )
+    .await
+    {
+        return Ok(ApplyResponse::ConfirmationRequired(request));
+    }
+    client
+        .batch_execute(&ddl)
+        .await
+        .map_err(|e| db::db_error_message(&e))?;
+    Ok(ApplyResponse::Applied { ddl })
+}
+
+#[tauri::command]
+pub async fn get_roles(
+    connection_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::RoleInfo>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_roles(&client).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_role_memberships(
+    connection_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::RoleMembership>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_role_memberships(&client).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_privilege_matrix(
+    connection_id: String,
+    role: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::ObjectPrivileges>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_privilege_matrix(&client, &role, &schema)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn generate_privilege_statements(
+    connection_id: String,
+    changes: Vec<db::PrivilegeChange>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<String>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    let mut statements = Vec::with_capacity(changes.len());
+    for change in &changes {
+        statements.push(
+            db::privilege_statement(&client, change)
+                .await
+                .map_err(|e| e.to_string())?,
+        );
+    }
+    Ok(statements)
+}
+
+#[tauri::command]
+pub async fn apply_privilege_changes(
+    connection_id: String,
+    changes: Vec<db::PrivilegeChange>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<String>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::apply_privilege_changes(&client, &changes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn generate_ddl(
+    connection_id: String,
+    object: db::DdlObject,
+    manager: State<'_, ConnectionManager>,
+) -> Result<String, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::generate_ddl(&client, &object).await.map_err(|e| e.to_string())
+}
+
+/// A connection, optionally pointed at another database on the same server
+#[derive(Debug, Deserialize)]
+pub struct SchemaSource {
+    pub connection_id: String,
+    pub database: Option<String>,
+}
+
+async fn snapshot_source(
+    source: &SchemaSource,
+    schemas: &[String],
+    manager: &ConnectionManager,
+) -> Result<db::SchemaSnapshot, String> {
+    let client = match &source.database {
+        Some(database) => std::sync::Arc::new(
+            manager
+                .open_database(&source.connection_id, database)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+        None => manager.get_client(&source.connection_id).await.map_err(|e| e.to_string())?,
+    };
+    db::take_snapshot(&client, schemas).await.map_err(|e| e.to_string())
+}
+
+/// Diff `source` (the desired schema) against `target` and generate the
+/// script that brings `target` in line
+#[tauri::command]
+pub async fn compare_schemas(
+    source: SchemaSource,
+    target: SchemaSource,
+    schemas: Option<Vec<String>>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::SchemaDiff, String> {
+    let schemas = schemas.unwrap_or_default();
+    let source = snapshot_source(&source, &schemas, &manager).await?;
+    let target = snapshot_source(&target, &schemas, &manager).await?;
+    Ok(db::diff_snapshots(&source, &target))
+}
+
+async fn record_schema_snapshot(
+    connection_id: &str,
+    source: &str,
+    manager: &ConnectionManager,
+    local_db: &LocalDb,
+) -> Result<SchemaSnapshotRecord, String> {
+    let config = manager.get_config(connection_id).await.map_err(|e| e.to_string())?;
+    let client = manager.get_client(connection_id).await.map_err(|e| e.to_string())?;
+    let snapshot = db::take_snapshot(&client, &[]).await.map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(&snapshot).map_err(|e| e.to_string())?;
+    local_db
+        .save_schema_snapshot(
+            connection_id,
+            &config.database,
+            &snapshot.hash(),
+            snapshot.object_count() as i64,
+            source,
+            &json,
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn take_schema_snapshot(
+    connection_id: String,
+    manager: State<'_, ConnectionManager>,
+    local_db: State<'_, LocalDb>,
+) -> Result<SchemaSnapshotRecord, String> {
+    record_schema_snapshot(&connection_id, "manual", &manager, &local_db).await
+}
+
+#[tauri::command]
+pub async fn list_schema_snapshots(
+    connection_id: String,
+    limit: Option<i64>,
+    local_db: State<'_, LocalDb>,
+) -> Result<Vec<SchemaSnapshotRecord>, String> {
+    local_db
+        .list_schema_snapshots(&connection_id, limit.unwrap_or(100))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// What changed from snapshot `from_id` to snapshot `to_id`: objects only in
+/// `to_id` are reported as added
+#[tauri::command]
+pub async fn diff_schema_snapshots(
+    from_id: i64,
+    to_id: i64,
+    local_db: State<'_, LocalDb>,
+) -> Result<db::SchemaDiff, String> {
+    let from = load_schema_snapshot(&local_db, from_id).await?;
+    let to = load_schema_snapshot(&local_db, to_id).await?;
+    Ok(db::diff_snapshots(&to, &from))
+}
+
+async fn load_schema_snapshot(local_db: &LocalDb, id: i64) -> Result<db::SchemaSnapshot, String> {
+    let (_, json) = local_db.get_schema_snapshot(id).await.map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// Scan, vacuum, bloat, cache and wraparound statistics for the tables of
+/// `schema`, or for one table
+#[tauri::command]
+pub async fn get_table_stats(
+    connection_id: String,
+    schema: String,
+    table: Option<String>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<db::TableStats>, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_table_stats(&client, &schema, table.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_schema_health_summary(
+    connection_id: String,
+    schema: String,
+    limit: Option<usize>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::SchemaHealthSummary, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_schema_health_summary(&client, &schema, limit.unwrap_or(10))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_index_advice(
+    connection_id: String,
+    schema: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::IndexAdvice, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_index_advice(&client, &schema)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_relationship_graph(
+    connection_id: String,
+    schemas: Option<Vec<String>>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<db::RelationshipGraph, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    db::get_relationship_graph(&client, &schemas.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The relationship graph as Mermaid `erDiagram`, Graphviz DOT or JSON text
+#[tauri::command]
+pub async fn export_relationship_graph(
+    connection_id: String,
+    schemas: Option<Vec<String>>,
+    format: db::GraphFormat,
+    manager: State<'_, ConnectionManager>,
+) -> Result<String, String> {
+    let client = manager.get_client(&connection_id).await.map_err(|e| e.to_string())?;
+    let graph = db::get_relationship_graph(&client, &schemas.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())?;
+    db::export_relationship_graph(&graph, format).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_table_data(
     connection_id: String,
@@ -271,14 +1157,14 @@ pub async fn get_table_data(
                 Some("DESC") | Some("desc") => "DESC",
                 _ => "ASC",
             };
-            format!(" ORDER BY {} {}", quote_ident(col), dir)
+            format!(" ORDER BY {} {}", db::quote_ident(col), dir)
         }
         _ => String::new(),
     };
     let sql = format!(
         "SELECT * FROM {}.{}{} LIMIT {} OFFSET {}",
-        quote_ident(&schema),
-        quote_ident(&table),
+        db::quote_ident(&schema),
+        db::quote_ident(&table),
         order_clause,
         limit,
         offset
@@ -316,6 +1202,10 @@ pub async fn save_connection(
         user: input.user,
         ssl_mode: input.ssl_mode.unwrap_or_else(|| "prefer".into()),
         color: input.color,
+        protected: input.protected.unwrap_or(false),
+        row_limit: input.row_limit.unwrap_or(db::DEFAULT_ROW_LIMIT),
+        cell_byte_budget: input.cell_byte_budget.unwrap_or(db::DEFAULT_CELL_BYTE_BUDGET),
+        execution_mode: input.execution_mode.unwrap_or_default().as_str().to_string(),
         created_at: String::new(),
     };
 
@@ -378,6 +1268,7 @@ pub async fn save_query(
     sql: String,
     connection_id: Option<String>,
     description: Option<String>,
+    parameters: Option<Vec<QueryParameter>>,
     local_db: State<'_, LocalDb>,
 ) -> Result<i64, String> {
     let query = SavedQuery {
@@ -386,6 +1277,7 @@ pub async fn save_query(
         sql,
         connection_id,
         description,
+        parameters: parameters.unwrap_or_default(),
         created_at: String::new(),
         updated_at: String::new(),
     };
@@ -402,6 +1294,133 @@ pub async fn get_saved_queries(
         .map_err(|e| e.to_string())
 }
 
+/// Placeholder names (`:name`, `${name}`) found in `sql`, to prefill parameter declarations.
+#[tauri::command]
+pub async fn detect_query_parameters(sql: String) -> Result<Vec<String>, String> {
+    Ok(db::find_named_parameters(&sql))
+}
+
+/// Run a saved query, binding `params` to its placeholders as real query parameters.
+/// Parameters that aren't given fall back to their declared default.
+#[tauri::command]
+pub async fn run_saved_query(
+    id: i64,
+    connection_id: Option<String>,
+    params: HashMap<String, serde_json::Value>,
+    confirmation_token: Option<String>,
+    manager: State<'_, ConnectionManager>,
+    confirmations: State<'_, db::PendingConfirmations>,
+    local_db: State<'_, LocalDb>,
+) -> Result<ExecuteResponse, String> {
+    let saved = local_db.get_saved_query(id).await.map_err(|e| e.to_string())?;
+    let connection_id = connection_id
+        .or(saved.connection_id.clone())
+        .ok_or("Saved query has no connection; pass one to run it")?;
+    let client = manager
+        .get_client(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let types: HashMap<String, String> = saved
+        .parameters
+        .iter()
+        .map(|p| (p.name.clone(), p.data_type.clone()))
+        .collect();
+    db::check_parameter_types(&client, &types)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (sql, names) = db::bind_named_parameters(&saved.sql, &types);
+
+    let mut values: Vec<Option<String>> = Vec::with_capacity(names.len());
+    for name in &names {
+        let value = match params.get(name) {
+            Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(other) => Some(other.to_string()),
+            None => {
+                let declared = saved.parameters.iter().find(|p| p.name == *name);
+                match declared.and_then(|p| p.default.clone()) {
+                    Some(default) => Some(default),
+                    None => return Err(format!("Missing value for parameter :{}", name)),
+                }
+            }
+        };
+        values.push(value);
+    }
+    // Parameter values as used, for the history entry
+    let used: serde_json::Value = names
+        .iter()
+        .zip(&values)
+        .map(|(name, value)| {
+            let value = value.clone().map_or(serde_json::Value::Null, serde_json::Value::String);
+            (name.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into();
+
+    // Without bound parameters on the simple protocol, values are inlined as
+    // literals; the guard then checks the statement that actually runs
+    let inlined = (config.execution_mode == db::ExecutionMode::Simple).then(|| {
+        let literals: HashMap<String, Option<String>> =
+            names.iter().cloned().zip(values.iter().cloned()).collect();
+        db::inline_named_parameters(&saved.sql, &types, &literals)
+    });
+    if let Some(request) = require_confirmation(
+        &config,
+        &client,
+        &connection_id,
+        inlined.as_deref().unwrap_or(&saved.sql),
+        confirmation_token.as_deref(),
+        &confirmations,
+    )
+    .await
+    {
+        return Ok(ExecuteResponse::ConfirmationRequired(request));
+    }
+
+    let outcome = match inlined {
+        Some(inlined) => db::execute_query_limited(&client, &inlined, config.query_limits()).await,
+        None => db::execute_query_params(&client, &sql, &values, config.query_limits()).await,
+    };
+    match outcome {
+        Ok(result) => {
+            let _ = local_db
+                .add_saved_query_history(
+                    &connection_id,
+                    &saved.sql,
+                    result.execution_time_ms as i64,
+                    result.row_count as i64,
+                    true,
+                    None,
+                    id,
+                    &used,
+                )
+                .await;
+            Ok(ExecuteResponse::Completed(result))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            let _ = local_db
+                .add_saved_query_history(
+                    &connection_id,
+                    &saved.sql,
+                    0,
+                    0,
+                    false,
+                    Some(&error_msg),
+                    id,
+                    &used,
+                )
+                .await;
+            Err(error_msg)
+        }
+    }
+}
+
 #[tauri::command]
 pub async fn delete_saved_query(
     id: i64,
@@ -413,10 +1432,12 @@ pub async fn delete_saved_query(
         .map_err(|e| e.to_string())
 }
 
-/// Get full schema for all tables (used for AI context + editor autocomplete)
+/// The schema context of every table and view, from the connection's cached
+/// catalog while the server's catalog marker is unchanged
 #[tauri::command]
 pub async fn get_full_schema(
     connection_id: String,
+    refresh: Option<bool>,
     manager: State<'_, ConnectionManager>,
 ) -> Result<SchemaContext, String> {
     let client = manager
@@ -424,46 +1445,82 @@ pub async fn get_full_schema(
         .await
         .map_err(|e| e.to_string())?;
 
-    let schemas = db::get_schemas(&client).await.map_err(|e| e.to_string())?;
-    let mut tables_ctx = Vec::new();
-
-    for schema in &schemas {
-        let tables = db::get_tables(&client, &schema.name)
-            .await
-            .map_err(|e| e.to_string())?;
-        for table in &tables {
-            if table.table_type != "BASE TABLE" && table.table_type != "VIEW" {
-                continue;
+    if !refresh.unwrap_or(false) {
+        if let Some(cached) = manager.cached_catalog(&connection_id).await {
+            let marker = db::catalog_marker(&client).await.map_err(|e| e.to_string())?;
+            if cached.marker == marker {
+                return Ok(schema_context(&cached));
             }
-            let columns = db::get_columns(&client, &schema.name, &table.name)
+        }
+    }
+    let catalog = db::load_catalog(&client, None, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    let catalog = manager.cache_catalog(&connection_id, catalog).await;
+    Ok(schema_context(&catalog))
+}
+
+/// Reload one schema, or one table of it, into the cached catalog after a
+/// known change, without reading the rest of the database again
+#[tauri::command]
+pub async fn refresh_schema_catalog(
+    connection_id: String,
+    schema: String,
+    table: Option<String>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<SchemaContext, String> {
+    let client = manager
+        .get_client(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let catalog = match manager.cached_catalog(&connection_id).await {
+        Some(cached) => {
+            let partial = db::load_catalog(&client, Some(&schema), table.as_deref())
                 .await
                 .map_err(|e| e.to_string())?;
-            tables_ctx.push(TableContext {
-                schema: schema.name.clone(),
-                name: table.name.clone(),
-                columns: columns
+            let mut catalog = (*cached).clone();
+            catalog.merge(partial, &schema, table.as_deref());
+            catalog
+        }
+        None => db::load_catalog(&client, None, None)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    let catalog = manager.cache_catalog(&connection_id, catalog).await;
+    Ok(schema_context(&catalog))
+}
+
+fn schema_context(catalog: &db::Catalog) -> SchemaContext {
+    SchemaContext {
+        tables: catalog
+            .tables
+            .iter()
+            .map(|t| TableContext {
+                schema: t.schema.clone(),
+                name: t.name.clone(),
+                columns: t
+                    .columns
                     .iter()
                     .map(|c| ColumnContext {
                         name: c.name.clone(),
                         data_type: c.data_type.clone(),
                         is_primary_key: c.is_primary_key,
-                        is_foreign_key: c.is_foreign_key,
-                        foreign_ref: if c.is_foreign_key {
-                            Some(format!(
-                                "{}.{}",
-                                c.foreign_table.as_deref().unwrap_or("?"),
-                                c.foreign_column.as_deref().unwrap_or("?")
-                            ))
-                        } else {
-                            None
-                        },
+                        is_foreign_key: c.foreign_ref.is_some(),
+                        foreign_ref: c.foreign_ref.clone(),
                     })
                     .collect(),
-            });
-        }
+            })
+            .collect(),
+        enums: catalog
+            .enums
+            .iter()
+            .map(|e| EnumContext {
+                schema: e.schema.clone(),
+                name: e.name.clone(),
+                labels: e.labels.clone(),
+            })
+            .collect(),
     }
-
-    Ok(SchemaContext { tables: tables_ctx })
 }
 
 // ── AI Commands ──────────────────────────────────────────────────
@@ -647,7 +1704,3 @@ pub async fn export_file(
         None => Ok(false), // User cancelled
     }
 }
-
-fn quote_ident(s: &str) -> String {
-    format!("\"{}\"", s.replace('"', "\"\""))
-}
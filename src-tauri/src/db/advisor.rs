@@ -0,0 +1,456 @@
+use super::quote_ident;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexIssue {
+    /// Left behind by a failed CREATE INDEX CONCURRENTLY; maintained on writes, never used
+    Invalid,
+    /// Same columns, operator classes and predicate as another index
+    Duplicate,
+    /// Its columns are a leading prefix of another index on the table
+    Overlapping,
+    /// No scans since statistics were last reset
+    Unused,
+    /// A foreign key with no index starting with its columns
+    MissingForeignKeyIndex,
+    /// Read mostly by sequential scans
+    HighSeqScan,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexFinding {
+    pub issue: IndexIssue,
+    pub schema: String,
+    pub table: String,
+    /// The index (or foreign key, for a missing index) the finding is about
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub detail: String,
+    /// Size of the index, the estimated size of a missing one, or the table size
+    /// for sequential scans
+    pub size_bytes: i64,
+    pub size: String,
+    /// DROP or CREATE INDEX statement that addresses the finding, when there is one
+    pub sql: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexAdvice {
+    pub schema: String,
+    /// Scan counts start from here; unused indexes are only as reliable as this is old
+    pub stats_reset: Option<DateTime<Utc>>,
+    /// Space freed by dropping duplicate, overlapping and unused indexes
+    pub reclaimable_bytes: i64,
+    pub findings: Vec<IndexFinding>,
+}
+
+struct IndexRow {
+    schema: String,
+    table: String,
+    table_oid: u32,
+    name: String,
+    am: String,
+    definition: String,
+    is_unique: bool,
+    constraint: Option<String>,
+    is_valid: bool,
+    /// On a partitioned table
+    partitioned: bool,
+    /// A partition's index attached to an index of its parent; dropped with the parent
+    attached: bool,
+    keys: Vec<String>,
+    includes: Vec<String>,
+    classes: Vec<u32>,
+    options: Vec<i16>,
+    predicate: Option<String>,
+    idx_scan: i64,
+    size_bytes: i64,
+    size: String,
+}
+
+impl IndexRow {
+    /// Can be dropped without touching a constraint or a partitioned index
+    fn droppable(&self) -> bool {
+        self.constraint.is_none() && !self.attached
+    }
+
+    fn drop_sql(&self) -> String {
+        format!(
+            "DROP INDEX {}{}.{};",
+            if self.partitioned {
+                ""
+            } else {
+                "CONCURRENTLY "
+            },
+            quote_ident(&self.schema),
+            quote_ident(&self.name)
+        )
+    }
+
+    fn finding(&self, issue: IndexIssue, detail: String, sql: Option<String>) -> IndexFinding {
+        IndexFinding {
+            issue,
+            schema: self.schema.clone(),
+            table: self.table.clone(),
+            name: Some(self.name.clone()),
+            columns: self.keys.clone(),
+            detail,
+            size_bytes: self.size_bytes,
+            size: self.size.clone(),
+            sql,
+        }
+    }
+
+    /// Same leading `n` key columns, in the same order and with the same
+    /// operator classes and sort options
+    fn shares_prefix(&self, other: &IndexRow, n: usize) -> bool {
+        self.keys.len() >= n
+            && other.keys.len() >= n
+            && self.keys[..n] == other.keys[..n]
+            && self.classes[..n] == other.classes[..n]
+            && self.options[..n] == other.options[..n]
+    }
+
+    fn same_scope(&self, other: &IndexRow) -> bool {
+        self.table_oid == other.table_oid
+            && self.am == other.am
+            && self.predicate == other.predicate
+            && self.is_valid
+            && other.is_valid
+    }
+}
+
+/// Rebuild an invalid index: drop it, then create it again without blocking writes
+fn rebuild_sql(index: &IndexRow) -> String {
+    let create = index
+        .definition
+        .replacen(" INDEX ", " INDEX CONCURRENTLY ", 1);
+    format!("{}\n{};", index.drop_sql(), create)
+}
+
+async fn load_indexes(client: &Arc<Client>, schema: &str) -> Result<Vec<IndexRow>> {
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                t.relname,
+                t.oid,
+                c.relname,
+                am.amname,
+                pg_get_indexdef(c.oid),
+                ix.indisunique,
+                (SELECT con.conname FROM pg_constraint con
+                 WHERE con.conindid = c.oid AND con.conrelid = t.oid AND con.contype IN ('p', 'u', 'x')),
+                ix.indisvalid,
+                t.relkind = 'p',
+                EXISTS (SELECT 1 FROM pg_inherits inh WHERE inh.inhrelid = c.oid),
+                ARRAY(SELECT pg_get_indexdef(c.oid, k, true)
+                      FROM generate_series(1, ix.indnkeyatts) k ORDER BY k),
+                ARRAY(SELECT pg_get_indexdef(c.oid, k, true)
+                      FROM generate_series(ix.indnkeyatts + 1, ix.indnatts) k ORDER BY k),
+                ix.indclass::oid[],
+                ix.indoption::int2[],
+                pg_get_expr(ix.indpred, ix.indrelid),
+                COALESCE(s.idx_scan, 0),
+                pg_relation_size(c.oid),
+                pg_size_pretty(pg_relation_size(c.oid))
+             FROM pg_index ix
+             JOIN pg_class c ON c.oid = ix.indexrelid
+             JOIN pg_class t ON t.oid = ix.indrelid
+             JOIN pg_namespace n ON n.oid = t.relnamespace
+             JOIN pg_am am ON am.oid = c.relam
+             LEFT JOIN pg_stat_all_indexes s ON s.indexrelid = c.oid
+             WHERE n.nspname = $1 AND t.relkind IN ('r', 'p', 'm')
+             ORDER BY t.relname, c.relname",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| IndexRow {
+            schema: row.get(0),
+            table: row.get(1),
+            table_oid: row.get(2),
+            name: row.get(3),
+            am: row.get(4),
+            definition: row.get(5),
+            is_unique: row.get(6),
+            constraint: row.get(7),
+            is_valid: row.get(8),
+            partitioned: row.get(9),
+            attached: row.get(10),
+            keys: row.get(11),
+            includes: row.get(12),
+            classes: row.get(13),
+            options: row.get(14),
+            predicate: row.get(15),
+            idx_scan: row.get(16),
+            size_bytes: row.get(17),
+            size: row.get(18),
+        })
+        .collect())
+}
+
+/// Among identical indexes, the one to keep: constraint-backed, then unique,
+/// then the most scanned
+fn keeper_rank(index: &IndexRow) -> (bool, bool, i64) {
+    (index.constraint.is_some(), index.is_unique, index.idx_scan)
+}
+
+fn index_findings(indexes: &[IndexRow]) -> Vec<IndexFinding> {
+    let mut findings = Vec::new();
+    for (i, index) in indexes.iter().enumerate() {
+        if !index.is_valid && index.partitioned {
+            findings.push(index.finding(
+                IndexIssue::Invalid,
+                "Some partitions have no index attached to this one; create and attach them with ALTER INDEX ... ATTACH PARTITION".to_string(),
+                None,
+            ));
+            continue;
+        }
+        if !index.is_valid {
+            findings.push(index.finding(
+                IndexIssue::Invalid,
+                "Invalid index, most likely from a failed CREATE INDEX CONCURRENTLY; it slows down writes and is never used by queries".to_string(),
+                Some(rebuild_sql(index)),
+            ));
+            continue;
+        }
+        if !index.droppable() {
+            continue;
+        }
+
+        // An identical index that ranks higher, or ranks the same and comes first
+        let duplicate_of = indexes.iter().enumerate().find(|(j, other)| {
+            *j != i
+                && index.same_scope(other)
+                && index.keys.len() == other.keys.len()
+                && index.shares_prefix(other, index.keys.len())
+                && index.includes == other.includes
+                && (!index.is_unique || other.is_unique)
+                && (keeper_rank(other) > keeper_rank(index)
+                    || (keeper_rank(other) == keeper_rank(index) && *j < i))
+        });
+        if let Some((_, other)) = duplicate_of {
+            findings.push(index.finding(
+                IndexIssue::Duplicate,
+                format!(
+                    "Duplicates {} ({} scans vs {})",
+                    other.name, other.idx_scan, index.idx_scan
+                ),
+                Some(index.drop_sql()),
+            ));
+            continue;
+        }
+
+        // A longer btree index answers every query this one can
+        if !index.is_unique && index.am == "btree" && index.includes.is_empty() {
+            let covered_by = indexes.iter().find(|other| {
+                !std::ptr::eq(*other, index)
+                    && index.same_scope(other)
+                    && other.keys.len() > index.keys.len()
+                    && index.shares_prefix(other, index.keys.len())
+            });
+            if let Some(other) = covered_by {
+                findings.push(index.finding(
+                    IndexIssue::Overlapping,
+                    format!(
+                        "Its columns lead {} ({}), which can serve the same lookups",
+                        other.name,
+                        other.keys.join(", ")
+                    ),
+                    Some(index.drop_sql()),
+                ));
+                continue;
+            }
+        }
+
+        // Scans of indexes on partitioned tables are counted on the partitions
+        if index.idx_scan == 0 && !index.is_unique && !index.partitioned {
+            findings.push(index.finding(
+                IndexIssue::Unused,
+                "Never scanned since statistics were last reset".to_string(),
+                Some(index.drop_sql()),
+            ));
+        }
+    }
+    findings
+}
+
+/// Foreign keys whose referencing columns do not lead any valid index, with
+/// the index that would support them and its estimated size
+async fn missing_foreign_key_indexes(
+    client: &Arc<Client>,
+    schema: &str,
+) -> Result<Vec<IndexFinding>> {
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                con.conname,
+                c.relkind = 'p',
+                ARRAY(SELECT a.attname::text
+                      FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord)
+                      JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                      ORDER BY k.ord),
+                fn.nspname || '.' || fc.relname,
+                est.bytes,
+                pg_size_pretty(est.bytes)
+             FROM pg_constraint con
+             JOIN pg_class c ON c.oid = con.conrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_class fc ON fc.oid = con.confrelid
+             JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+             -- Btree entries of the key columns, one per row at a fillfactor of 90
+             CROSS JOIN LATERAL (
+                 SELECT (ceil(GREATEST(c.reltuples, 0)
+                             * (ceil((8 + sum(COALESCE(s.avg_width, NULLIF(t.typlen, -1), 8))) / 8.0) * 8 + 4)
+                             / ((current_setting('block_size')::numeric - 40) * 0.9))
+                         * current_setting('block_size')::numeric)::bigint AS bytes
+                 FROM unnest(con.conkey) k(attnum)
+                 JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                 JOIN pg_type t ON t.oid = a.atttypid
+                 LEFT JOIN pg_stats s ON s.schemaname = n.nspname AND s.tablename = c.relname
+                     AND s.attname = a.attname AND NOT s.inherited
+             ) est
+             WHERE con.contype = 'f' AND n.nspname = $1
+               AND NOT EXISTS (
+                   SELECT 1 FROM pg_index i
+                   WHERE i.indrelid = con.conrelid AND i.indisvalid AND i.indpred IS NULL
+                     AND i.indnkeyatts >= cardinality(con.conkey)
+                     AND (i.indkey::int2[])[0:cardinality(con.conkey) - 1] @> con.conkey
+               )
+             ORDER BY c.relname, con.conname",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let schema: String = row.get(0);
+            let table: String = row.get(1);
+            let partitioned: bool = row.get(3);
+            let columns: Vec<String> = row.get(4);
+            let referenced: String = row.get(5);
+            let mut index_name = format!("{}_{}_idx", table, columns.join("_"));
+            while index_name.len() > 63 {
+                index_name.pop();
+            }
+            let sql = format!(
+                "CREATE INDEX {}{} ON {}.{} ({});",
+                if partitioned { "" } else { "CONCURRENTLY " },
+                quote_ident(&index_name),
+                quote_ident(&schema),
+                quote_ident(&table),
+                columns
+                    .iter()
+                    .map(|c| quote_ident(c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            IndexFinding {
+                issue: IndexIssue::MissingForeignKeyIndex,
+                detail: format!(
+                    "References {}; deletes and key updates there scan {} for matching rows",
+                    referenced, table
+                ),
+                schema,
+                table,
+                name: row.get(2),
+                columns,
+                size_bytes: row.get(6),
+                size: row.get(7),
+                sql: Some(sql),
+            }
+        })
+        .collect())
+}
+
+/// Tables of some size read mostly by sequential scans
+async fn high_seq_scan_tables(client: &Arc<Client>, schema: &str) -> Result<Vec<IndexFinding>> {
+    let rows = client
+        .query(
+            "SELECT
+                s.schemaname,
+                s.relname,
+                s.seq_scan,
+                COALESCE(s.idx_scan, 0),
+                s.seq_tup_read / s.seq_scan,
+                pg_relation_size(s.relid),
+                pg_size_pretty(pg_relation_size(s.relid))
+             FROM pg_stat_all_tables s
+             WHERE s.schemaname = $1
+               AND s.seq_scan > COALESCE(s.idx_scan, 0)
+               AND s.n_live_tup > 10000
+               AND s.seq_tup_read > 1000000
+             ORDER BY s.seq_tup_read DESC",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let seq_scan: i64 = row.get(2);
+            let idx_scan: i64 = row.get(3);
+            let rows_per_scan: i64 = row.get(4);
+            IndexFinding {
+                issue: IndexIssue::HighSeqScan,
+                schema: row.get(0),
+                table: row.get(1),
+                name: None,
+                columns: Vec::new(),
+                detail: format!(
+                    "{} sequential scans reading about {} rows each, vs {} index scans; check the filters of frequent queries for a missing index",
+                    seq_scan, rows_per_scan, idx_scan
+                ),
+                size_bytes: row.get(5),
+                size: row.get(6),
+                sql: None,
+            }
+        })
+        .collect())
+}
+
+/// Index findings for `schema`, grouped by issue and largest first
+pub async fn get_index_advice(client: &Arc<Client>, schema: &str) -> Result<IndexAdvice> {
+    let stats_reset: Option<DateTime<Utc>> = client
+        .query_one(
+            "SELECT stats_reset FROM pg_stat_database WHERE datname = current_database()",
+            &[],
+        )
+        .await?
+        .get(0);
+
+    let indexes = load_indexes(client, schema).await?;
+    let mut findings = index_findings(&indexes);
+    findings.extend(missing_foreign_key_indexes(client, schema).await?);
+    findings.extend(high_seq_scan_tables(client, schema).await?);
+    findings.sort_by(|a, b| a.issue.cmp(&b.issue).then(b.size_bytes.cmp(&a.size_bytes)));
+
+    let reclaimable_bytes = findings
+        .iter()
+        .filter(|f| {
+            matches!(
+                f.issue,
+                IndexIssue::Duplicate | IndexIssue::Overlapping | IndexIssue::Unused
+            )
+        })
+        .map(|f| f.size_bytes)
+        .sum();
+
+    Ok(IndexAdvice {
+        schema: schema.to_string(),
+        stats_reset,
+        reclaimable_bytes,
+        findings,
+    })
+}
@@ -0,0 +1,213 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+/// Tables, views and enums of every visible schema, as read by a handful of
+/// bulk catalog queries
+#[derive(Debug, Clone, Serialize)]
+pub struct Catalog {
+    /// `catalog_marker` at the time of loading
+    pub marker: String,
+    pub tables: Vec<CatalogTable>,
+    pub enums: Vec<CatalogEnum>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogTable {
+    pub schema: String,
+    pub name: String,
+    /// "BASE TABLE" or "VIEW", as in information_schema
+    pub table_type: String,
+    pub columns: Vec<CatalogColumn>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogColumn {
+    pub name: String,
+    pub data_type: String,
+    pub is_nullable: bool,
+    pub is_primary_key: bool,
+    /// `table.column` the column references, from its first foreign key
+    pub foreign_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEnum {
+    pub schema: String,
+    pub name: String,
+    pub labels: Vec<String>,
+}
+
+/// A value that changes whenever DDL touches relations, columns, constraints
+/// or types. New and updated catalog rows carry a new xmin and drops lower the
+/// row count, so comparing markers is enough to tell if a cache is stale.
+pub async fn catalog_marker(client: &Arc<Client>) -> Result<String> {
+    let row = client
+        .query_one(
+            "SELECT concat_ws(':',
+                (SELECT count(*) || '/' || max(xmin::text::bigint) FROM pg_namespace),
+                (SELECT count(*) || '/' || max(xmin::text::bigint) FROM pg_class),
+                (SELECT count(*) || '/' || max(xmin::text::bigint) FROM pg_attribute),
+                (SELECT count(*) || '/' || max(xmin::text::bigint) FROM pg_constraint),
+                (SELECT count(*) || '/' || max(xmin::text::bigint) FROM pg_type),
+                (SELECT count(*) || '/' || max(xmin::text::bigint) FROM pg_enum))",
+            &[],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Load the catalog, or only the part of it in `schema` (and `table`, when
+/// given). The marker is read first, so changes made while loading show up
+/// as stale on the next check rather than being missed.
+pub async fn load_catalog(
+    client: &Arc<Client>,
+    schema: Option<&str>,
+    table: Option<&str>,
+) -> Result<Catalog> {
+    let marker = catalog_marker(client).await?;
+
+    // Relations the user can see, with every column
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                CASE WHEN c.relkind = 'v' THEN 'VIEW' ELSE 'BASE TABLE' END,
+                a.attname,
+                format_type(a.atttypid, a.atttypmod),
+                NOT a.attnotnull
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+             WHERE c.relkind IN ('r', 'p', 'v')
+               AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+               AND ($1::text IS NULL OR n.nspname = $1)
+               AND ($2::text IS NULL OR c.relname = $2)
+               AND (pg_has_role(c.relowner, 'USAGE')
+                    OR has_table_privilege(c.oid, 'SELECT, INSERT, UPDATE, DELETE, TRUNCATE, REFERENCES, TRIGGER')
+                    OR has_any_column_privilege(c.oid, 'SELECT, INSERT, UPDATE, REFERENCES'))
+             ORDER BY n.nspname, c.relname, a.attnum",
+            &[&schema, &table],
+        )
+        .await?;
+    let mut tables: Vec<CatalogTable> = Vec::new();
+    for row in &rows {
+        let schema: String = row.get(0);
+        let name: String = row.get(1);
+        let column = CatalogColumn {
+            name: row.get(3),
+            data_type: row.get(4),
+            is_nullable: row.get(5),
+            is_primary_key: false,
+            foreign_ref: None,
+        };
+        match tables.last_mut() {
+            Some(t) if t.schema == schema && t.name == name => t.columns.push(column),
+            _ => tables.push(CatalogTable {
+                schema,
+                name,
+                table_type: row.get(2),
+                columns: vec![column],
+            }),
+        }
+    }
+
+    // Primary and foreign key columns, one row per key column
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                a.attname,
+                con.contype = 'p',
+                fc.relname,
+                fa.attname
+             FROM pg_constraint con
+             JOIN pg_class c ON c.oid = con.conrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             CROSS JOIN LATERAL unnest(con.conkey) WITH ORDINALITY k(attnum, ord)
+             JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+             LEFT JOIN pg_class fc ON fc.oid = con.confrelid
+             LEFT JOIN pg_attribute fa ON fa.attrelid = con.confrelid AND fa.attnum = con.confkey[k.ord]
+             WHERE con.contype IN ('p', 'f')
+               AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+               AND ($1::text IS NULL OR n.nspname = $1)
+               AND ($2::text IS NULL OR c.relname = $2)
+             ORDER BY n.nspname, c.relname, con.conname",
+            &[&schema, &table],
+        )
+        .await?;
+    let mut positions: HashMap<(String, String), usize> = HashMap::new();
+    for (i, t) in tables.iter().enumerate() {
+        positions.insert((t.schema.clone(), t.name.clone()), i);
+    }
+    for row in &rows {
+        let Some(&i) = positions.get(&(row.get(0), row.get(1))) else {
+            continue;
+        };
+        let attname: String = row.get(2);
+        let Some(column) = tables[i].columns.iter_mut().find(|c| c.name == attname) else {
+            continue;
+        };
+        if row.get(3) {
+            column.is_primary_key = true;
+        } else if column.foreign_ref.is_none() {
+            let foreign_table: String = row.get(4);
+            let foreign_column: String = row.get(5);
+            column.foreign_ref = Some(format!("{}.{}", foreign_table, foreign_column));
+        }
+    }
+
+    let enums = if table.is_some() {
+        Vec::new()
+    } else {
+        let rows = client
+            .query(
+                "SELECT
+                    n.nspname,
+                    t.typname,
+                    ARRAY(SELECT e.enumlabel::text FROM pg_enum e
+                          WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder)
+                 FROM pg_type t
+                 JOIN pg_namespace n ON n.oid = t.typnamespace
+                 WHERE t.typtype = 'e'
+                   AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+                   AND ($1::text IS NULL OR n.nspname = $1)
+                 ORDER BY n.nspname, t.typname",
+                &[&schema],
+            )
+            .await?;
+        rows.iter()
+            .map(|row| CatalogEnum {
+                schema: row.get(0),
+                name: row.get(1),
+                labels: row.get(2),
+            })
+            .collect()
+    };
+
+    Ok(Catalog {
+        marker,
+        tables,
+        enums,
+    })
+}
+
+impl Catalog {
+    /// Replace the part of the catalog that `partial` was loaded for: a whole
+    /// schema, or one table when `table` is given. The old marker is kept, since
+    /// other schemas may have changed too, so the next check reloads everything.
+    pub fn merge(&mut self, partial: Catalog, schema: &str, table: Option<&str>) {
+        self.tables.retain(|t| t.schema != schema || table.is_some_and(|name| t.name != name));
+        self.tables.extend(partial.tables);
+        self.tables.sort_by(|a, b| (&a.schema, &a.name).cmp(&(&b.schema, &b.name)));
+        if table.is_none() {
+            self.enums.retain(|e| e.schema != schema);
+            self.enums.extend(partial.enums);
+            self.enums.sort_by(|a, b| (&a.schema, &a.name).cmp(&(&b.schema, &b.name)));
+        }
+    }
+}
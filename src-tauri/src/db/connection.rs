@@ -1,3 +1,4 @@
+use super::{Catalog, QueryLimits, DEFAULT_CELL_BYTE_BUDGET, DEFAULT_ROW_LIMIT};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -18,6 +19,63 @@ pub struct ConnectionConfig {
     pub password: String,
     pub ssl_mode: SslMode,
     pub color: Option<String>,
+    /// Destructive statements need an explicit confirmation before they run
+    #[serde(default)]
+    pub protected: bool,
+    /// Rows fetched by ad-hoc SELECTs without a LIMIT; 0 fetches everything
+    #[serde(default = "default_row_limit")]
+    pub row_limit: u32,
+    /// Bytes kept per text/bytea cell before it is truncated; 0 keeps everything
+    #[serde(default = "default_cell_byte_budget")]
+    pub cell_byte_budget: u32,
+    /// Wire protocol used for queries from the editor
+    #[serde(default)]
+    pub execution_mode: ExecutionMode,
+}
+
+fn default_row_limit() -> u32 {
+    DEFAULT_ROW_LIMIT
+}
+
+fn default_cell_byte_budget() -> u32 {
+    DEFAULT_CELL_BYTE_BUDGET
+}
+
+impl ConnectionConfig {
+    pub fn query_limits(&self) -> QueryLimits {
+        QueryLimits {
+            row_limit: self.row_limit,
+            cell_byte_budget: self.cell_byte_budget,
+            execution_mode: self.execution_mode,
+        }
+    }
+}
+
+/// How editor queries are sent. `Simple` uses the simple query protocol
+/// (no prepared statements), which works behind PgBouncer in transaction
+/// mode and Postgres-compatible proxies, at the cost of text-only results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionMode {
+    #[default]
+    Extended,
+    Simple,
+}
+
+impl ExecutionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionMode::Extended => "extended",
+            ExecutionMode::Simple => "simple",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "simple" => ExecutionMode::Simple,
+            _ => ExecutionMode::Extended,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -32,16 +90,26 @@ pub enum SslMode {
 /// Manages active database connections
 pub struct ConnectionManager {
     connections: RwLock<HashMap<String, Arc<Client>>>,
+    configs: RwLock<HashMap<String, ConnectionConfig>>,
+    /// Extra connections keyed by (connection id, session id), so that
+    /// several editor tabs can run queries against one server in parallel
+    sessions: RwLock<HashMap<(String, String), Arc<Client>>>,
+    /// Last catalog loaded per connection, checked against the server's
+    /// catalog marker before it is reused
+    catalogs: RwLock<HashMap<String, Arc<Catalog>>>,
 }
 
 impl ConnectionManager {
     pub fn new() -> Self {
         Self {
             connections: RwLock::new(HashMap::new()),
+            configs: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+            catalogs: RwLock::new(HashMap::new()),
         }
     }
 
-    pub async fn connect(&self, config: &ConnectionConfig) -> Result<()> {
+    async fn open_client(config: &ConnectionConfig) -> Result<Client> {
         let conn_string = format!(
             "host={} port={} dbname={} user={} password={}",
             config.host, config.port, config.database, config.user, config.password
@@ -56,18 +124,92 @@ impl ConnectionManager {
             }
         });
 
+        Ok(client)
+    }
+
+    pub async fn connect(&self, config: &ConnectionConfig) -> Result<()> {
+        let client = Self::open_client(config).await?;
+
         let mut connections = self.connections.write().await;
         connections.insert(config.id.clone(), Arc::new(client));
 
+        let mut configs = self.configs.write().await;
+        configs.insert(config.id.clone(), config.clone());
+
+        self.catalogs.write().await.remove(&config.id);
         Ok(())
     }
 
     pub async fn disconnect(&self, connection_id: &str) -> Result<()> {
         let mut connections = self.connections.write().await;
         connections.remove(connection_id);
+        let mut configs = self.configs.write().await;
+        configs.remove(connection_id);
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|(conn_id, _), _| conn_id != connection_id);
+        self.catalogs.write().await.remove(connection_id);
         Ok(())
     }
 
+    /// Get the client for a session of an active connection, opening a new
+    /// server connection the first time the session is used.
+    pub async fn get_session_client(
+        &self,
+        connection_id: &str,
+        session_id: &str,
+    ) -> Result<Arc<Client>> {
+        let key = (connection_id.to_string(), session_id.to_string());
+        {
+            let sessions = self.sessions.read().await;
+            if let Some(client) = sessions.get(&key).filter(|c| !c.is_closed()) {
+                return Ok(client.clone());
+            }
+        }
+
+        let client = Arc::new(self.open_dedicated(connection_id).await?);
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(key, client.clone());
+        Ok(client)
+    }
+
+    pub async fn close_session(&self, connection_id: &str, session_id: &str) {
+        let mut sessions = self.sessions.write().await;
+        sessions.remove(&(connection_id.to_string(), session_id.to_string()));
+    }
+
+    /// Open a private connection with the settings of an active connection.
+    /// The caller owns it; it is closed when dropped.
+    pub async fn open_dedicated(&self, connection_id: &str) -> Result<Client> {
+        let config = self.get_config(connection_id).await?;
+        Self::open_client(&config).await
+    }
+
+    /// Like `open_dedicated`, but to another database on the same server
+    pub async fn open_database(&self, connection_id: &str, database: &str) -> Result<Client> {
+        let mut config = self.get_config(connection_id).await?;
+        config.database = database.to_string();
+        Self::open_client(&config).await
+    }
+
+    pub async fn cached_catalog(&self, connection_id: &str) -> Option<Arc<Catalog>> {
+        self.catalogs.read().await.get(connection_id).cloned()
+    }
+
+    pub async fn cache_catalog(&self, connection_id: &str, catalog: Catalog) -> Arc<Catalog> {
+        let catalog = Arc::new(catalog);
+        let mut catalogs = self.catalogs.write().await;
+        catalogs.insert(connection_id.to_string(), catalog.clone());
+        catalog
+    }
+
+    pub async fn get_config(&self, connection_id: &str) -> Result<ConnectionConfig> {
+        let configs = self.configs.read().await;
+        configs
+            .get(connection_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No active connection with id: {}", connection_id))
+    }
+
     pub async fn get_client(&self, connection_id: &str) -> Result<Arc<Client>> {
         let connections = self.connections.read().await;
         connections
@@ -0,0 +1,1004 @@
+use super::{get_function_definition, parse_acl_item, quote_ident, quote_literal};
+use anyhow::Result;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DdlObjectKind {
+    Table,
+    /// Plain or materialized view
+    View,
+    Index,
+    Sequence,
+    Function,
+    Type,
+    Trigger,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DdlObject {
+    pub kind: DdlObjectKind,
+    pub schema: String,
+    pub name: String,
+    /// The table a trigger belongs to
+    #[serde(default)]
+    pub table: Option<String>,
+    /// Identity arguments of a function, to pick one overload
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+/// CREATE statements for `object` and everything that belongs to it:
+/// ownership, comments, grants and, for tables, indexes, triggers and policies.
+pub async fn generate_ddl(client: &Arc<Client>, object: &DdlObject) -> Result<String> {
+    let schema = object.schema.as_str();
+    let name = object.name.as_str();
+    let statements = match object.kind {
+        DdlObjectKind::Table => table_ddl(client, schema, name).await?,
+        DdlObjectKind::View => view_ddl(client, schema, name).await?,
+        DdlObjectKind::Index => index_ddl(client, schema, name).await?,
+        DdlObjectKind::Sequence => {
+            let oid = relation_oid(client, schema, name, &["S"]).await?;
+            let (mut statements, owned_by) = sequence_ddl(client, oid).await?;
+            statements.extend(owned_by);
+            statements
+        }
+        DdlObjectKind::Function => {
+            function_ddl(client, schema, name, object.arguments.as_deref()).await?
+        }
+        DdlObjectKind::Type => type_ddl(client, schema, name).await?,
+        DdlObjectKind::Trigger => {
+            let table = object
+                .table
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("A trigger needs its table"))?;
+            trigger_ddl(client, schema, table, name).await?
+        }
+    };
+    Ok(statements.join("\n\n"))
+}
+
+fn qualified(schema: &str, name: &str) -> String {
+    format!("{}.{}", quote_ident(schema), quote_ident(name))
+}
+
+fn comment_statement(target: &str, comment: Option<String>) -> Option<String> {
+    comment.map(|c| format!("COMMENT ON {} IS {};", target, quote_literal(&c)))
+}
+
+fn grantee_sql(grantee: &str) -> String {
+    if grantee.is_empty() {
+        "PUBLIC".to_string()
+    } else {
+        quote_ident(grantee)
+    }
+}
+
+/// GRANT/REVOKE statements that turn the built-in default ACL into `acl`.
+/// A NULL ACL means the default is in effect and nothing needs to be said.
+fn acl_statements(target: &str, acl: Option<Vec<String>>, default: Vec<String>) -> Vec<String> {
+    let Some(acl) = acl else {
+        return Vec::new();
+    };
+    let mut statements = Vec::new();
+    for item in default.iter().filter(|d| !acl.contains(d)).filter_map(|d| parse_acl_item(d)) {
+        statements.push(format!(
+            "REVOKE ALL ON {} FROM {};",
+            target,
+            grantee_sql(&item.grantee)
+        ));
+    }
+    for item in acl.iter().filter(|a| !default.contains(a)).filter_map(|a| parse_acl_item(a)) {
+        let grantee = grantee_sql(&item.grantee);
+        let plain: Vec<&str> = item
+            .privileges
+            .iter()
+            .filter(|p| !item.grantable.contains(p))
+            .map(String::as_str)
+            .collect();
+        if !plain.is_empty() {
+            statements.push(format!("GRANT {} ON {} TO {};", plain.join(", "), target, grantee));
+        }
+        if !item.grantable.is_empty() {
+            statements.push(format!(
+                "GRANT {} ON {} TO {} WITH GRANT OPTION;",
+                item.grantable.join(", "),
+                target,
+                grantee
+            ));
+        }
+    }
+    statements
+}
+
+async fn relation_oid(client: &Client, schema: &str, name: &str, kinds: &[&str]) -> Result<u32> {
+    let row = client
+        .query_opt(
+            "SELECT c.oid
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2 AND c.relkind::text = ANY($3)",
+            &[&schema, &name, &kinds],
+        )
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("{} not found", qualified(schema, name)))?;
+    Ok(row.get(0))
+}
+
+// ── Tables ──
+
+async fn table_ddl(client: &Client, schema: &str, name: &str) -> Result<Vec<String>> {
+    let oid = relation_oid(client, schema, name, &["r", "p"]).await?;
+    let table = qualified(schema, name);
+    let rel = client
+        .query_one(
+            "SELECT
+                c.relpersistence = 'u',
+                CASE WHEN c.relispartition THEN
+                    (SELECT i.inhparent::regclass::text FROM pg_inherits i WHERE i.inhrelid = c.oid)
+                END,
+                pg_get_expr(c.relpartbound, c.oid),
+                CASE WHEN c.relkind = 'p' THEN pg_get_partkeydef(c.oid) END,
+                ARRAY(
+                    SELECT i.inhparent::regclass::text
+                    FROM pg_inherits i
+                    WHERE i.inhrelid = c.oid AND NOT c.relispartition
+                    ORDER BY i.inhseqno
+                ),
+                c.reloptions,
+                t.spcname,
+                pg_get_userbyid(c.relowner),
+                obj_description(c.oid, 'pg_class'),
+                c.relacl::text[],
+                acldefault('r', c.relowner)::text[],
+                c.relrowsecurity,
+                c.relforcerowsecurity
+             FROM pg_class c
+             LEFT JOIN pg_tablespace t ON t.oid = c.reltablespace
+             WHERE c.oid = $1",
+            &[&oid],
+        )
+        .await?;
+    let unlogged: bool = rel.get(0);
+    let partition_of: Option<String> = rel.get(1);
+    let partition_bound: Option<String> = rel.get(2);
+    let partition_key: Option<String> = rel.get(3);
+    let inherits: Vec<String> = rel.get(4);
+    let reloptions: Option<Vec<String>> = rel.get(5);
+    let tablespace: Option<String> = rel.get(6);
+    let owner: String = rel.get(7);
+
+    let columns = client
+        .query(
+            "SELECT
+                a.attname,
+                format_type(a.atttypid, a.atttypmod),
+                a.attnotnull,
+                pg_get_expr(d.adbin, d.adrelid),
+                a.attidentity::text,
+                a.attgenerated::text,
+                CASE WHEN a.attcollation <> t.typcollation
+                    THEN quote_ident(cn.nspname) || '.' || quote_ident(co.collname)
+                END,
+                a.attislocal,
+                CASE a.attstorage
+                    WHEN t.typstorage THEN NULL
+                    WHEN 'p' THEN 'PLAIN'
+                    WHEN 'e' THEN 'EXTERNAL'
+                    WHEN 'm' THEN 'MAIN'
+                    WHEN 'x' THEN 'EXTENDED'
+                END,
+                col_description(a.attrelid, a.attnum),
+                a.attacl::text[],
+                ident.seqstart,
+                ident.seqincrement
+             FROM pg_attribute a
+             JOIN pg_type t ON t.oid = a.atttypid
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             LEFT JOIN pg_collation co ON co.oid = a.attcollation
+             LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+             LEFT JOIN LATERAL (
+                SELECT s.seqstart, s.seqincrement
+                FROM pg_depend dep
+                JOIN pg_sequence s ON s.seqrelid = dep.objid
+                WHERE dep.classid = 'pg_class'::regclass
+                  AND dep.refobjid = a.attrelid
+                  AND dep.refobjsubid = a.attnum
+                  AND dep.deptype = 'i'
+             ) ident ON true
+             WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped
+             ORDER BY a.attnum",
+            &[&oid],
+        )
+        .await?;
+
+    let mut body = Vec::new();
+    let mut after = Vec::new();
+    let mut column_comments = Vec::new();
+    let mut column_grants = Vec::new();
+    for col in &columns {
+        let col_name = quote_ident(col.get::<_, &str>(0));
+        let local: bool = col.get(7);
+        // Partitions and inheritance children get their columns from the parent
+        if local && partition_of.is_none() {
+            let mut def = format!("{} {}", col_name, col.get::<_, String>(1));
+            if let Some(collation) = col.get::<_, Option<String>>(6) {
+                def.push_str(&format!(" COLLATE {}", collation));
+            }
+            let identity: &str = col.get(4);
+            let generated: &str = col.get(5);
+            let default: Option<String> = col.get(3);
+            if !identity.is_empty() {
+                def.push_str(if identity == "a" {
+                    " GENERATED ALWAYS AS IDENTITY"
+                } else {
+                    " GENERATED BY DEFAULT AS IDENTITY"
+                });
+                let start: Option<i64> = col.get(11);
+                let increment: Option<i64> = col.get(12);
+                let mut options = Vec::new();
+                if let Some(start) = start.filter(|s| *s != 1) {
+                    options.push(format!("START WITH {}", start));
+                }
+                if let Some(increment) = increment.filter(|i| *i != 1) {
+                    options.push(format!("INCREMENT BY {}", increment));
+                }
+                if !options.is_empty() {
+                    def.push_str(&format!(" ({})", options.join(" ")));
+                }
+            } else if !generated.is_empty() {
+                // 's' is stored, 'v' is virtual (PostgreSQL 18)
+                def.push_str(&format!(
+                    " GENERATED ALWAYS AS ({}) {}",
+                    default.unwrap_or_default(),
+                    if generated == "v" { "VIRTUAL" } else { "STORED" }
+                ));
+            } else if let Some(default) = default {
+                def.push_str(&format!(" DEFAULT {}", default));
+            }
+            if col.get(2) {
+                def.push_str(" NOT NULL");
+            }
+            body.push(def);
+        }
+        if let Some(storage) = col.get::<_, Option<&str>>(8) {
+            after.push(format!(
+                "ALTER TABLE ONLY {} ALTER COLUMN {} SET STORAGE {};",
+                table, col_name, storage
+            ));
+        }
+        column_comments.extend(comment_statement(
+            &format!("COLUMN {}.{}", table, col_name),
+            col.get(9),
+        ));
+        for item in col
+            .get::<_, Option<Vec<String>>>(10)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|a| parse_acl_item(a))
+        {
+            let privileges: Vec<String> = item
+                .privileges
+                .iter()
+                .map(|p| format!("{} ({})", p, col_name))
+                .collect();
+            column_grants.push(format!(
+                "GRANT {} ON {} TO {};",
+                privileges.join(", "),
+                table,
+                grantee_sql(&item.grantee)
+            ));
+        }
+    }
+
+    let constraints = client
+        .query(
+            "SELECT conname, pg_get_constraintdef(oid, true)
+             FROM pg_constraint
+             WHERE conrelid = $1
+               AND contype IN ('p', 'u', 'c', 'x', 'f')
+               AND conislocal
+               AND conparentid = 0
+             ORDER BY array_position(ARRAY['p', 'u', 'c', 'x', 'f'], contype::text), conname",
+            &[&oid],
+        )
+        .await?;
+    for con in &constraints {
+        body.push(format!(
+            "CONSTRAINT {} {}",
+            quote_ident(con.get::<_, &str>(0)),
+            con.get::<_, String>(1)
+        ));
+    }
+
+    let mut create = format!(
+        "CREATE {}TABLE {}",
+        if unlogged { "UNLOGGED " } else { "" },
+        table
+    );
+    if let Some(parent) = &partition_of {
+        create.push_str(&format!(" PARTITION OF {}", parent));
+    }
+    if !body.is_empty() {
+        create.push_str(&format!(" (\n    {}\n)", body.join(",\n    ")));
+    } else if partition_of.is_none() {
+        create.push_str(" ()");
+    }
+    if let Some(bound) = &partition_bound {
+        create.push_str(&format!("\n{}", bound));
+    }
+    if !inherits.is_empty() {
+        create.push_str(&format!("\nINHERITS ({})", inherits.join(", ")));
+    }
+    if let Some(key) = &partition_key {
+        create.push_str(&format!("\nPARTITION BY {}", key));
+    }
+    if let Some(options) = reloptions.filter(|o| !o.is_empty()) {
+        create.push_str(&format!("\nWITH ({})", options.join(", ")));
+    }
+    if let Some(tablespace) = &tablespace {
+        create.push_str(&format!("\nTABLESPACE {}", quote_ident(tablespace)));
+    }
+    create.push(';');
+
+    // Sequences behind serial columns must exist before the table's defaults
+    let serials = client
+        .query(
+            "SELECT d.objid
+             FROM pg_depend d
+             JOIN pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+             WHERE d.classid = 'pg_class'::regclass
+               AND d.refobjid = $1
+               AND d.deptype = 'a'
+             ORDER BY s.relname",
+            &[&oid],
+        )
+        .await?;
+    let mut statements = Vec::new();
+    let mut owned_by = Vec::new();
+    for row in &serials {
+        let (sequence, owned) = sequence_ddl(client, row.get(0)).await?;
+        statements.extend(sequence);
+        owned_by.extend(owned);
+    }
+    statements.push(create);
+    statements.extend(owned_by);
+    statements.extend(after);
+    statements.push(format!("ALTER TABLE {} OWNER TO {};", table, quote_ident(&owner)));
+    statements.extend(comment_statement(&format!("TABLE {}", table), rel.get(8)));
+    statements.extend(column_comments);
+    statements.extend(table_indexes(client, oid).await?);
+    statements.extend(table_triggers(client, oid, &table).await?);
+
+    if rel.get(11) {
+        statements.push(format!("ALTER TABLE {} ENABLE ROW LEVEL SECURITY;", table));
+    }
+    if rel.get(12) {
+        statements.push(format!("ALTER TABLE {} FORCE ROW LEVEL SECURITY;", table));
+    }
+    statements.extend(table_policies(client, oid, &table).await?);
+    statements.extend(acl_statements(&format!("TABLE {}", table), rel.get(9), rel.get(10)));
+    statements.extend(column_grants);
+    Ok(statements)
+}
+
+/// Indexes that are not created by a constraint or attached to a parent index
+async fn table_indexes(client: &Client, oid: u32) -> Result<Vec<String>> {
+    // On a partitioned table the definition reads ON ONLY, which would create
+    // an invalid index that covers none of the partitions
+    let rows = client
+        .query(
+            "SELECT
+                CASE WHEN t.relkind = 'p'
+                     THEN regexp_replace(pg_get_indexdef(i.indexrelid), ' ON ONLY ', ' ON ')
+                     ELSE pg_get_indexdef(i.indexrelid)
+                END,
+                quote_ident(n.nspname) || '.' || quote_ident(ic.relname),
+                obj_description(i.indexrelid, 'pg_class')
+             FROM pg_index i
+             JOIN pg_class t ON t.oid = i.indrelid
+             JOIN pg_class ic ON ic.oid = i.indexrelid
+             JOIN pg_namespace n ON n.oid = ic.relnamespace
+             WHERE i.indrelid = $1
+               AND NOT EXISTS (
+                   SELECT 1 FROM pg_constraint con
+                   WHERE con.conrelid = i.indrelid
+                     AND con.conindid = i.indexrelid
+                     AND con.contype IN ('p', 'u', 'x')
+               )
+               AND NOT EXISTS (SELECT 1 FROM pg_inherits inh WHERE inh.inhrelid = i.indexrelid)
+             ORDER BY ic.relname",
+            &[&oid],
+        )
+        .await?;
+    let mut statements = Vec::new();
+    for row in &rows {
+        statements.push(format!("{};", row.get::<_, String>(0)));
+        statements.extend(comment_statement(
+            &format!("INDEX {}", row.get::<_, String>(1)),
+            row.get(2),
+        ));
+    }
+    Ok(statements)
+}
+
+async fn table_triggers(client: &Client, oid: u32, table: &str) -> Result<Vec<String>> {
+    let rows = client
+        .query(
+            "SELECT pg_get_triggerdef(oid, true), tgname, obj_description(oid, 'pg_trigger')
+             FROM pg_trigger
+             WHERE tgrelid = $1 AND NOT tgisinternal AND tgparentid = 0
+             ORDER BY tgname",
+            &[&oid],
+        )
+        .await?;
+    let mut statements = Vec::new();
+    for row in &rows {
+        statements.push(format!("{};", row.get::<_, String>(0)));
+        statements.extend(comment_statement(
+            &format!("TRIGGER {} ON {}", quote_ident(row.get::<_, &str>(1)), table),
+            row.get(2),
+        ));
+    }
+    Ok(statements)
+}
+
+async fn table_policies(client: &Client, oid: u32, table: &str) -> Result<Vec<String>> {
+    let rows = client
+        .query(
+            "SELECT
+                pol.polname,
+                pol.polpermissive,
+                CASE pol.polcmd
+                    WHEN 'r' THEN 'SELECT'
+                    WHEN 'a' THEN 'INSERT'
+                    WHEN 'w' THEN 'UPDATE'
+                    WHEN 'd' THEN 'DELETE'
+                    ELSE 'ALL'
+                END,
+                COALESCE(
+                    (SELECT array_agg(quote_ident(r.rolname) ORDER BY r.rolname)
+                     FROM unnest(pol.polroles) AS role_oid
+                     JOIN pg_roles r ON r.oid = role_oid),
+                    ARRAY['PUBLIC']::text[]
+                ),
+                pg_get_expr(pol.polqual, pol.polrelid, true),
+                pg_get_expr(pol.polwithcheck, pol.polrelid, true)
+             FROM pg_policy pol
+             WHERE pol.polrelid = $1
+             ORDER BY pol.polname",
+            &[&oid],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            create_policy_sql(
+                row.get(0),
+                table,
+                row.get(1),
+                row.get(2),
+                &row.get::<_, Vec<String>>(3),
+                row.get(4),
+                row.get(5),
+            )
+        })
+        .collect())
+}
+
+/// `roles` are already quoted, or PUBLIC
+pub(super) fn create_policy_sql(
+    name: &str,
+    table: &str,
+    permissive: bool,
+    command: &str,
+    roles: &[String],
+    using: Option<String>,
+    check: Option<String>,
+) -> String {
+    let mut sql = format!(
+        "CREATE POLICY {} ON {}\n    AS {}\n    FOR {}\n    TO {}",
+        quote_ident(name),
+        table,
+        if permissive { "PERMISSIVE" } else { "RESTRICTIVE" },
+        command,
+        roles.join(", ")
+    );
+    if let Some(using) = using {
+        sql.push_str(&format!("\n    USING ({})", using));
+    }
+    if let Some(check) = check {
+        sql.push_str(&format!("\n    WITH CHECK ({})", check));
+    }
+    sql.push(';');
+    sql
+}
+
+// ── Views ──
+
+async fn view_ddl(client: &Client, schema: &str, name: &str) -> Result<Vec<String>> {
+    let oid = relation_oid(client, schema, name, &["v", "m"]).await?;
+    let view = qualified(schema, name);
+    let row = client
+        .query_one(
+            "SELECT
+                c.relkind = 'm',
+                pg_get_viewdef(c.oid, true),
+                c.reloptions,
+                t.spcname,
+                c.relispopulated,
+                pg_get_userbyid(c.relowner),
+                obj_description(c.oid, 'pg_class'),
+                c.relacl::text[],
+                acldefault('r', c.relowner)::text[]
+             FROM pg_class c
+             LEFT JOIN pg_tablespace t ON t.oid = c.reltablespace
+             WHERE c.oid = $1",
+            &[&oid],
+        )
+        .await?;
+    let materialized: bool = row.get(0);
+    let definition: String = row.get(1);
+    let definition = definition.trim().trim_end_matches(';');
+    let mut options: Vec<String> = row.get::<_, Option<Vec<String>>>(2).unwrap_or_default();
+    // check_option is stored with the options but written as a trailing clause
+    let check_option = options
+        .iter()
+        .position(|o| o.starts_with("check_option="))
+        .map(|i| options.remove(i)["check_option=".len()..].to_uppercase());
+    let with = if options.is_empty() {
+        String::new()
+    } else {
+        format!(" WITH ({})", options.join(", "))
+    };
+
+    let keyword = if materialized { "MATERIALIZED VIEW" } else { "VIEW" };
+    let mut create = format!("CREATE {} {}{}", keyword, view, with);
+    if let Some(tablespace) = row.get::<_, Option<&str>>(3) {
+        create.push_str(&format!(" TABLESPACE {}", quote_ident(tablespace)));
+    }
+    create.push_str(&format!(" AS\n{}", definition));
+    if materialized {
+        create.push_str(if row.get(4) { "\nWITH DATA" } else { "\nWITH NO DATA" });
+    }
+    if let Some(check_option) = check_option {
+        create.push_str(&format!("\nWITH {} CHECK OPTION", check_option));
+    }
+    create.push(';');
+
+    let mut statements = vec![
+        create,
+        format!(
+            "ALTER {} {} OWNER TO {};",
+            if materialized { "MATERIALIZED VIEW" } else { "VIEW" },
+            view,
+            quote_ident(row.get::<_, &str>(5))
+        ),
+    ];
+    statements.extend(comment_statement(&format!("{} {}", keyword, view), row.get(6)));
+    let comments = client
+        .query(
+            "SELECT a.attname, col_description(a.attrelid, a.attnum)
+             FROM pg_attribute a
+             WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped
+               AND col_description(a.attrelid, a.attnum) IS NOT NULL
+             ORDER BY a.attnum",
+            &[&oid],
+        )
+        .await?;
+    for c in &comments {
+        statements.extend(comment_statement(
+            &format!("COLUMN {}.{}", view, quote_ident(c.get::<_, &str>(0))),
+            c.get(1),
+        ));
+    }
+    if materialized {
+        statements.extend(table_indexes(client, oid).await?);
+    } else {
+        statements.extend(table_triggers(client, oid, &view).await?);
+    }
+    statements.extend(acl_statements(&format!("TABLE {}", view), row.get(7), row.get(8)));
+    Ok(statements)
+}
+
+// ── Indexes ──
+
+async fn index_ddl(client: &Client, schema: &str, name: &str) -> Result<Vec<String>> {
+    let oid = relation_oid(client, schema, name, &["i", "I"]).await?;
+    // Partitioned indexes are created without ONLY so they cover the partitions
+    let row = client
+        .query_one(
+            "SELECT
+                CASE WHEN ic.relkind = 'I'
+                     THEN regexp_replace(pg_get_indexdef(i.indexrelid), ' ON ONLY ', ' ON ')
+                     ELSE pg_get_indexdef(i.indexrelid)
+                END,
+                i.indrelid::regclass::text,
+                con.conname,
+                pg_get_constraintdef(con.oid, true),
+                obj_description(i.indexrelid, 'pg_class')
+             FROM pg_index i
+             JOIN pg_class ic ON ic.oid = i.indexrelid
+             LEFT JOIN pg_constraint con
+                ON con.conindid = i.indexrelid
+               AND con.conrelid = i.indrelid
+               AND con.contype IN ('p', 'u', 'x')
+             WHERE i.indexrelid = $1",
+            &[&oid],
+        )
+        .await?;
+    // An index that backs a constraint is created by the constraint
+    let create = match row.get::<_, Option<&str>>(2) {
+        Some(constraint) => format!(
+            "ALTER TABLE {} ADD CONSTRAINT {} {};",
+            row.get::<_, &str>(1),
+            quote_ident(constraint),
+            row.get::<_, String>(3)
+        ),
+        None => format!("{};", row.get::<_, String>(0)),
+    };
+    let mut statements = vec![create];
+    statements.extend(comment_statement(
+        &format!("INDEX {}", qualified(schema, name)),
+        row.get(4),
+    ));
+    Ok(statements)
+}
+
+// ── Sequences ──
+
+/// The CREATE SEQUENCE with owner, comment and grants, and separately the
+/// OWNED BY statement, which has to wait until the owning table exists.
+async fn sequence_ddl(client: &Client, oid: u32) -> Result<(Vec<String>, Option<String>)> {
+    let row = client
+        .query_one(
+            "SELECT
+                quote_ident(n.nspname) || '.' || quote_ident(c.relname),
+                format_type(s.seqtypid, NULL),
+                s.seqstart,
+                s.seqincrement,
+                s.seqmin,
+                s.seqmax,
+                s.seqcache,
+                s.seqcycle,
+                pg_get_userbyid(c.relowner),
+                obj_description(c.oid, 'pg_class'),
+                c.relacl::text[],
+                acldefault('s', c.relowner)::text[],
+                (SELECT d.refobjid::regclass::text || '.' || quote_ident(a.attname)
+                 FROM pg_depend d
+                 JOIN pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+                 WHERE d.classid = 'pg_class'::regclass
+                   AND d.objid = c.oid
+                   AND d.refclassid = 'pg_class'::regclass
+                   AND d.deptype = 'a')
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_sequence s ON s.seqrelid = c.oid
+             WHERE c.oid = $1",
+            &[&oid],
+        )
+        .await?;
+    let sequence: String = row.get(0);
+    let create = create_sequence_sql(
+        &sequence,
+        row.get(1),
+        row.get(2),
+        row.get(3),
+        row.get(4),
+        row.get(5),
+        row.get(6),
+        row.get(7),
+    );
+
+    let mut statements = vec![
+        create,
+        format!(
+            "ALTER SEQUENCE {} OWNER TO {};",
+            sequence,
+            quote_ident(row.get::<_, &str>(8))
+        ),
+    ];
+    statements.extend(comment_statement(&format!("SEQUENCE {}", sequence), row.get(9)));
+    statements.extend(acl_statements(
+        &format!("SEQUENCE {}", sequence),
+        row.get(10),
+        row.get(11),
+    ));
+    let owned_by = row
+        .get::<_, Option<String>>(12)
+        .map(|column| format!("ALTER SEQUENCE {} OWNED BY {};", sequence, column));
+    Ok((statements, owned_by))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn create_sequence_sql(
+    sequence: &str,
+    data_type: &str,
+    start: i64,
+    increment: i64,
+    min: i64,
+    max: i64,
+    cache: i64,
+    cycle: bool,
+) -> String {
+    format!(
+        "CREATE SEQUENCE {}\n    AS {}\n    START WITH {}\n    INCREMENT BY {}\n    MINVALUE {}\n    MAXVALUE {}\n    CACHE {}{};",
+        sequence,
+        data_type,
+        start,
+        increment,
+        min,
+        max,
+        cache,
+        if cycle { "\n    CYCLE" } else { "" }
+    )
+}
+
+// ── Functions ──
+
+async fn function_ddl(
+    client: &Arc<Client>,
+    schema: &str,
+    name: &str,
+    arguments: Option<&str>,
+) -> Result<Vec<String>> {
+    let rows = client
+        .query(
+            "SELECT p.oid
+             FROM pg_proc p
+             JOIN pg_namespace n ON n.oid = p.pronamespace
+             WHERE n.nspname = $1 AND p.proname = $2
+               AND ($3::text IS NULL OR pg_get_function_identity_arguments(p.oid) = $3)",
+            &[&schema, &name, &arguments],
+        )
+        .await?;
+    let oid: u32 = match rows.as_slice() {
+        [row] => row.get(0),
+        [] => anyhow::bail!("Function {} not found", qualified(schema, name)),
+        _ => anyhow::bail!(
+            "{} is overloaded; give the argument types to pick one",
+            qualified(schema, name)
+        ),
+    };
+
+    let definition = get_function_definition(client, oid).await?;
+    let definition = definition.trim_end();
+    let mut statements = vec![if definition.ends_with(';') {
+        definition.to_string()
+    } else {
+        format!("{};", definition)
+    }];
+
+    let row = client
+        .query_one(
+            "SELECT
+                CASE p.prokind
+                    WHEN 'p' THEN 'PROCEDURE'
+                    WHEN 'a' THEN 'AGGREGATE'
+                    ELSE 'FUNCTION'
+                END,
+                pg_get_function_identity_arguments(p.oid),
+                pg_get_userbyid(p.proowner),
+                obj_description(p.oid, 'pg_proc'),
+                p.proacl::text[],
+                acldefault('f', p.proowner)::text[]
+             FROM pg_proc p
+             WHERE p.oid = $1",
+            &[&oid],
+        )
+        .await?;
+    let keyword: &str = row.get(0);
+    let identity_arguments: &str = row.get(1);
+    let signature = format!(
+        "{}({})",
+        qualified(schema, name),
+        if keyword == "AGGREGATE" && identity_arguments.is_empty() { "*" } else { identity_arguments }
+    );
+    statements.push(format!(
+        "ALTER {} {} OWNER TO {};",
+        keyword,
+        signature,
+        quote_ident(row.get::<_, &str>(2))
+    ));
+    statements.extend(comment_statement(&format!("{} {}", keyword, signature), row.get(3)));
+    // Privileges on aggregates and procedures are granted ON FUNCTION/PROCEDURE
+    let grant_keyword = if keyword == "PROCEDURE" { "PROCEDURE" } else { "FUNCTION" };
+    statements.extend(acl_statements(
+        &format!("{} {}", grant_keyword, signature),
+        row.get(4),
+        row.get(5),
+    ));
+    Ok(statements)
+}
+
+// ── Types ──
+
+pub(super) async fn type_ddl(client: &Client, schema: &str, name: &str) -> Result<Vec<String>> {
+    let row = client
+        .query_opt(
+            "SELECT
+                t.oid,
+                t.typtype::text,
+                t.typrelid,
+                format_type(t.typbasetype, t.typtypmod),
+                CASE WHEN t.typcollation <> 0 AND t.typcollation <> bt.typcollation
+                    THEN quote_ident(cn.nspname) || '.' || quote_ident(co.collname)
+                END,
+                t.typdefault,
+                t.typnotnull,
+                pg_get_userbyid(t.typowner),
+                obj_description(t.oid, 'pg_type'),
+                t.typacl::text[],
+                acldefault('T', t.typowner)::text[]
+             FROM pg_type t
+             JOIN pg_namespace n ON n.oid = t.typnamespace
+             LEFT JOIN pg_type bt ON bt.oid = t.typbasetype
+             LEFT JOIN pg_collation co ON co.oid = t.typcollation
+             LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+             WHERE n.nspname = $1 AND t.typname = $2",
+            &[&schema, &name],
+        )
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Type {} not found", qualified(schema, name)))?;
+    let oid: u32 = row.get(0);
+    let typtype: &str = row.get(1);
+    let type_name = qualified(schema, name);
+
+    let (keyword, create) = match typtype {
+        "e" => {
+            let labels = client
+                .query(
+                    "SELECT enumlabel FROM pg_enum WHERE enumtypid = $1 ORDER BY enumsortorder",
+                    &[&oid],
+                )
+                .await?;
+            let labels: Vec<String> = labels.iter().map(|l| quote_literal(l.get(0))).collect();
+            (
+                "TYPE",
+                format!("CREATE TYPE {} AS ENUM (\n    {}\n);", type_name, labels.join(",\n    ")),
+            )
+        }
+        "d" => {
+            let mut create = format!("CREATE DOMAIN {} AS {}", type_name, row.get::<_, String>(3));
+            if let Some(collation) = row.get::<_, Option<String>>(4) {
+                create.push_str(&format!(" COLLATE {}", collation));
+            }
+            if let Some(default) = row.get::<_, Option<String>>(5) {
+                create.push_str(&format!(" DEFAULT {}", default));
+            }
+            if row.get(6) {
+                create.push_str(" NOT NULL");
+            }
+            let checks = client
+                .query(
+                    "SELECT conname, pg_get_constraintdef(oid, true)
+                     FROM pg_constraint
+                     WHERE contypid = $1 AND contype = 'c'
+                     ORDER BY conname",
+                    &[&oid],
+                )
+                .await?;
+            for check in &checks {
+                create.push_str(&format!(
+                    "\n    CONSTRAINT {} {}",
+                    quote_ident(check.get::<_, &str>(0)),
+                    check.get::<_, String>(1)
+                ));
+            }
+            create.push(';');
+            ("DOMAIN", create)
+        }
+        "c" => {
+            let typrelid: u32 = row.get(2);
+            let attributes = client
+                .query(
+                    "SELECT
+                        a.attname,
+                        format_type(a.atttypid, a.atttypmod),
+                        CASE WHEN a.attcollation <> t.typcollation
+                            THEN quote_ident(cn.nspname) || '.' || quote_ident(co.collname)
+                        END
+                     FROM pg_attribute a
+                     JOIN pg_type t ON t.oid = a.atttypid
+                     LEFT JOIN pg_collation co ON co.oid = a.attcollation
+                     LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+                     WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped
+                     ORDER BY a.attnum",
+                    &[&typrelid],
+                )
+                .await?;
+            let attributes: Vec<String> = attributes
+                .iter()
+                .map(|a| {
+                    let mut def = format!("{} {}", quote_ident(a.get::<_, &str>(0)), a.get::<_, String>(1));
+                    if let Some(collation) = a.get::<_, Option<String>>(2) {
+                        def.push_str(&format!(" COLLATE {}", collation));
+                    }
+                    def
+                })
+                .collect();
+            (
+                "TYPE",
+                format!("CREATE TYPE {} AS (\n    {}\n);", type_name, attributes.join(",\n    ")),
+            )
+        }
+        "r" => {
+            let range = client
+                .query_one(
+                    "SELECT
+                        format_type(r.rngsubtype, NULL),
+                        CASE WHEN NOT opc.opcdefault
+                            THEN quote_ident(opn.nspname) || '.' || quote_ident(opc.opcname)
+                        END,
+                        CASE WHEN r.rngcollation <> 0 AND r.rngcollation <> st.typcollation
+                            THEN quote_ident(cn.nspname) || '.' || quote_ident(co.collname)
+                        END,
+                        NULLIF(r.rngcanonical, 0)::regproc::text,
+                        NULLIF(r.rngsubdiff, 0)::regproc::text
+                     FROM pg_range r
+                     JOIN pg_type st ON st.oid = r.rngsubtype
+                     JOIN pg_opclass opc ON opc.oid = r.rngsubopc
+                     JOIN pg_namespace opn ON opn.oid = opc.opcnamespace
+                     LEFT JOIN pg_collation co ON co.oid = r.rngcollation
+                     LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+                     WHERE r.rngtypid = $1",
+                    &[&oid],
+                )
+                .await?;
+            let mut options = vec![format!("SUBTYPE = {}", range.get::<_, String>(0))];
+            let named = ["SUBTYPE_OPCLASS", "COLLATION", "CANONICAL", "SUBTYPE_DIFF"];
+            for (i, option) in named.iter().enumerate() {
+                if let Some(value) = range.get::<_, Option<String>>(i + 1) {
+                    options.push(format!("{} = {}", option, value));
+                }
+            }
+            (
+                "TYPE",
+                format!("CREATE TYPE {} AS RANGE (\n    {}\n);", type_name, options.join(",\n    ")),
+            )
+        }
+        _ => anyhow::bail!("{} is not an enum, domain, composite or range type", type_name),
+    };
+
+    let mut statements = vec![
+        create,
+        format!(
+            "ALTER {} {} OWNER TO {};",
+            keyword,
+            type_name,
+            quote_ident(row.get::<_, &str>(7))
+        ),
+    ];
+    statements.extend(comment_statement(&format!("{} {}", keyword, type_name), row.get(8)));
+    statements.extend(acl_statements(
+        &format!("{} {}", keyword, type_name),
+        row.get(9),
+        row.get(10),
+    ));
+    Ok(statements)
+}
+
+// ── Triggers ──
+
+async fn trigger_ddl(client: &Client, schema: &str, table: &str, name: &str) -> Result<Vec<String>> {
+    let row = client
+        .query_opt(
+            "SELECT pg_get_triggerdef(t.oid, true), obj_description(t.oid, 'pg_trigger')
+             FROM pg_trigger t
+             JOIN pg_class c ON c.oid = t.tgrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2 AND t.tgname = $3",
+            &[&schema, &table, &name],
+        )
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Trigger {} on {} not found", name, qualified(schema, table)))?;
+    let mut statements = vec![format!("{};", row.get::<_, String>(0))];
+    statements.extend(comment_statement(
+        &format!("TRIGGER {} ON {}", quote_ident(name), qualified(schema, table)),
+        row.get(1),
+    ));
+    Ok(statements)
+}
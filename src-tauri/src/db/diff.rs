@@ -0,0 +1,1299 @@
+use super::snapshot::{
+    ColumnSnapshot, ConstraintSnapshot, FunctionSnapshot, NamedDefinition, SchemaSnapshot,
+    TableSnapshot, TypeSnapshot, ViewSnapshot,
+};
+use super::{quote_ident, quote_literal};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Only in the source
+    Added,
+    /// Only in the target
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaChange {
+    /// schema, table, column, constraint, index, trigger, policy, view,
+    /// sequence, function, type or grant
+    pub object_type: String,
+    /// `schema.name`; parts of a table as `schema.table.name`
+    pub name: String,
+    pub kind: ChangeKind,
+    pub details: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStep {
+    pub description: String,
+    pub sql: String,
+    /// Drops data or objects, or rewrites values
+    pub destructive: bool,
+}
+
+/// How the target differs from the source, and the script that makes the
+/// target look like the source.
+#[derive(Debug, Serialize)]
+pub struct SchemaDiff {
+    pub changes: Vec<SchemaChange>,
+    pub steps: Vec<MigrationStep>,
+    pub warnings: Vec<String>,
+    pub script: String,
+}
+
+/// Order in which steps run. Things that block changes are dropped first,
+/// objects are created before what refers to them, and data goes last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    DropViews,
+    DropTableParts,
+    CreateSchemas,
+    Types,
+    Sequences,
+    Functions,
+    CreateTables,
+    AlterTables,
+    DropColumns,
+    Constraints,
+    ForeignKeys,
+    Indexes,
+    CreateViews,
+    Triggers,
+    Security,
+    DropTables,
+    DropFunctions,
+    DropTypes,
+    DropSequences,
+    DropSchemas,
+}
+
+#[derive(Default)]
+struct Plan {
+    changes: Vec<SchemaChange>,
+    steps: Vec<(Phase, MigrationStep)>,
+    warnings: Vec<String>,
+}
+
+impl Plan {
+    fn change(&mut self, object_type: &str, name: String, kind: ChangeKind, details: Vec<String>) {
+        self.changes.push(SchemaChange {
+            object_type: object_type.to_string(),
+            name,
+            kind,
+            details,
+        });
+    }
+
+    fn step(&mut self, phase: Phase, description: String, sql: String) {
+        self.steps.push((
+            phase,
+            MigrationStep {
+                description,
+                sql,
+                destructive: false,
+            },
+        ));
+    }
+
+    fn destructive(&mut self, phase: Phase, description: String, sql: String) {
+        self.warnings.push(description.clone());
+        self.steps.push((
+            phase,
+            MigrationStep {
+                description,
+                sql,
+                destructive: true,
+            },
+        ));
+    }
+}
+
+fn qualified(schema: &str, name: &str) -> String {
+    format!("{}.{}", quote_ident(schema), quote_ident(name))
+}
+
+/// Match items of both lists by key: source order first, then target-only items
+fn pair<'a, T, K>(
+    source: &'a [T],
+    target: &'a [T],
+    key: K,
+) -> Vec<(String, Option<&'a T>, Option<&'a T>)>
+where
+    K: Fn(&T) -> String,
+{
+    let target_by_key: HashMap<String, &T> = target.iter().map(|t| (key(t), t)).collect();
+    let source_keys: HashSet<String> = source.iter().map(&key).collect();
+    let mut pairs: Vec<_> = source
+        .iter()
+        .map(|s| {
+            let k = key(s);
+            let t = target_by_key.get(&k).copied();
+            (k, Some(s), t)
+        })
+        .collect();
+    pairs.extend(
+        target
+            .iter()
+            .filter(|t| !source_keys.contains(&key(t)))
+            .map(|t| (key(t), None, Some(t))),
+    );
+    pairs
+}
+
+/// Compare two snapshots. `source` is the desired state; the generated steps
+/// change `target` to match it.
+pub fn diff_snapshots(source: &SchemaSnapshot, target: &SchemaSnapshot) -> SchemaDiff {
+    let mut plan = Plan::default();
+
+    for (name, s, t) in pair(&source.schemas, &target.schemas, |s| s.clone()) {
+        match (s, t) {
+            (Some(_), None) => {
+                plan.change("schema", name.clone(), ChangeKind::Added, Vec::new());
+                plan.step(
+                    Phase::CreateSchemas,
+                    format!("Create schema {}", name),
+                    format!("CREATE SCHEMA {};", quote_ident(&name)),
+                );
+            }
+            (None, Some(_)) => {
+                plan.change("schema", name.clone(), ChangeKind::Removed, Vec::new());
+                plan.destructive(
+                    Phase::DropSchemas,
+                    format!("Drop schema {}", name),
+                    format!("DROP SCHEMA {};", quote_ident(&name)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    diff_types(&mut plan, &source.types, &target.types);
+    diff_sequences(&mut plan, source, target);
+    diff_functions(&mut plan, &source.functions, &target.functions);
+    let rewritten = diff_tables(&mut plan, &source.tables, &target.tables);
+    diff_views(&mut plan, &source.views, &target.views, &rewritten);
+
+    plan.steps.sort_by_key(|(phase, _)| *phase);
+    let steps: Vec<MigrationStep> = plan.steps.into_iter().map(|(_, step)| step).collect();
+    let mut script = String::from("SET check_function_bodies = false;\n");
+    for step in &steps {
+        script.push_str(&format!(
+            "\n-- {}{}\n{}\n",
+            if step.destructive {
+                "DESTRUCTIVE: "
+            } else {
+                ""
+            },
+            step.description,
+            step.sql
+        ));
+    }
+    SchemaDiff {
+        changes: plan.changes,
+        steps,
+        warnings: plan.warnings,
+        script,
+    }
+}
+
+fn changed<T: PartialEq + std::fmt::Debug>(
+    details: &mut Vec<String>,
+    what: &str,
+    source: &T,
+    target: &T,
+) {
+    if source != target {
+        details.push(format!("{}: {:?} → {:?}", what, target, source));
+    }
+}
+
+// ── Types ──
+
+fn type_keyword(t: &TypeSnapshot) -> &'static str {
+    if t.kind == "domain" {
+        "DOMAIN"
+    } else {
+        "TYPE"
+    }
+}
+
+fn diff_types(plan: &mut Plan, source: &[TypeSnapshot], target: &[TypeSnapshot]) {
+    for (key, s, t) in pair(source, target, |t| format!("{}.{}", t.schema, t.name)) {
+        match (s, t) {
+            (Some(s), None) => {
+                plan.change("type", key.clone(), ChangeKind::Added, vec![s.kind.clone()]);
+                plan.step(
+                    Phase::Types,
+                    format!("Create {} {}", s.kind, key),
+                    s.definition.clone(),
+                );
+            }
+            (None, Some(t)) => {
+                plan.change(
+                    "type",
+                    key.clone(),
+                    ChangeKind::Removed,
+                    vec![t.kind.clone()],
+                );
+                plan.destructive(
+                    Phase::DropTypes,
+                    format!("Drop {} {}", t.kind, key),
+                    format!(
+                        "DROP {} {};",
+                        type_keyword(t),
+                        qualified(&t.schema, &t.name)
+                    ),
+                );
+            }
+            (Some(s), Some(t)) if s.definition != t.definition => {
+                let name = qualified(&s.schema, &s.name);
+                // New enum labels can be added in place if none were removed or reordered
+                let kept: Vec<&String> = s
+                    .enum_labels
+                    .iter()
+                    .filter(|l| t.enum_labels.contains(l))
+                    .collect();
+                let appendable = s.kind == "enum"
+                    && t.kind == "enum"
+                    && kept.len() == t.enum_labels.len()
+                    && kept.iter().zip(&t.enum_labels).all(|(a, b)| *a == b);
+                if appendable {
+                    let added: Vec<String> = s
+                        .enum_labels
+                        .iter()
+                        .filter(|l| !t.enum_labels.contains(l))
+                        .cloned()
+                        .collect();
+                    plan.change(
+                        "type",
+                        key.clone(),
+                        ChangeKind::Changed,
+                        vec![format!("new labels: {}", added.join(", "))],
+                    );
+                    // Each label goes after the one before it, which exists by then;
+                    // a new first label goes before the first existing one
+                    for (i, label) in s.enum_labels.iter().enumerate() {
+                        if t.enum_labels.contains(label) {
+                            continue;
+                        }
+                        let position = match (i, kept.first()) {
+                            (0, Some(first)) => format!(" BEFORE {}", quote_literal(first)),
+                            (0, None) => String::new(),
+                            _ => format!(" AFTER {}", quote_literal(&s.enum_labels[i - 1])),
+                        };
+                        plan.step(
+                            Phase::Types,
+                            format!("Add label {} to {}", label, key),
+                            format!(
+                                "ALTER TYPE {} ADD VALUE {}{};",
+                                name,
+                                quote_literal(label),
+                                position
+                            ),
+                        );
+                    }
+                    plan.warnings.push(format!(
+                        "New values of enum {} cannot be used in the same transaction that adds them",
+                        key
+                    ));
+                } else {
+                    plan.change(
+                        "type",
+                        key.clone(),
+                        ChangeKind::Changed,
+                        vec![format!("definition: {} → {}", t.definition, s.definition)],
+                    );
+                    plan.destructive(
+                        Phase::Types,
+                        format!("Recreate {} {}; fails while columns use it", s.kind, key),
+                        format!("DROP {} {};\n{}", type_keyword(t), name, s.definition),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// ── Sequences ──
+
+fn diff_sequences(plan: &mut Plan, source: &SchemaSnapshot, target: &SchemaSnapshot) {
+    for (key, s, t) in pair(&source.sequences, &target.sequences, |s| {
+        format!("{}.{}", s.schema, s.name)
+    }) {
+        match (s, t) {
+            (Some(s), None) => {
+                plan.change("sequence", key.clone(), ChangeKind::Added, Vec::new());
+                plan.step(
+                    Phase::Sequences,
+                    format!("Create sequence {}", key),
+                    s.definition.clone(),
+                );
+            }
+            (None, Some(t)) => {
+                plan.change("sequence", key.clone(), ChangeKind::Removed, Vec::new());
+                // Serial sequences go with their table, so this may already be done
+                plan.destructive(
+                    Phase::DropSequences,
+                    format!("Drop sequence {}", key),
+                    format!("DROP SEQUENCE IF EXISTS {};", qualified(&t.schema, &t.name)),
+                );
+            }
+            (Some(s), Some(t)) if s.definition != t.definition => {
+                plan.change(
+                    "sequence",
+                    key.clone(),
+                    ChangeKind::Changed,
+                    vec![format!("definition: {} → {}", t.definition, s.definition)],
+                );
+                // Every CREATE SEQUENCE option is also an ALTER SEQUENCE option
+                let mut sql = s
+                    .definition
+                    .trim_end_matches(';')
+                    .replacen("CREATE", "ALTER", 1);
+                if !sql.ends_with("CYCLE") {
+                    sql.push_str("\n    NO CYCLE");
+                }
+                sql.push(';');
+                plan.step(Phase::Sequences, format!("Alter sequence {}", key), sql);
+            }
+            _ => {}
+        }
+    }
+}
+
+// ── Functions ──
+
+fn function_signature(f: &FunctionSnapshot) -> String {
+    let arguments = if f.kind == "AGGREGATE" && f.identity_arguments.is_empty() {
+        "*"
+    } else {
+        &f.identity_arguments
+    };
+    format!(
+        "{} {}({})",
+        f.kind,
+        qualified(&f.schema, &f.name),
+        arguments
+    )
+}
+
+fn diff_functions(plan: &mut Plan, source: &[FunctionSnapshot], target: &[FunctionSnapshot]) {
+    for (key, s, t) in pair(source, target, FunctionSnapshot::key) {
+        match (s, t) {
+            (Some(s), None) => {
+                plan.change("function", key.clone(), ChangeKind::Added, Vec::new());
+                plan.step(
+                    Phase::Functions,
+                    format!("Create {}", key),
+                    format!("{};", s.definition),
+                );
+                diff_grants(plan, &function_signature(s), &key, &s.grants, &[]);
+            }
+            (None, Some(t)) => {
+                plan.change("function", key.clone(), ChangeKind::Removed, Vec::new());
+                plan.destructive(
+                    Phase::DropFunctions,
+                    format!("Drop {}", key),
+                    format!("DROP {};", function_signature(t)),
+                );
+            }
+            (Some(s), Some(t)) => {
+                if s.definition != t.definition {
+                    let mut details = Vec::new();
+                    changed(&mut details, "returns", &s.return_type, &t.return_type);
+                    if details.is_empty() {
+                        details.push("body".to_string());
+                    }
+                    plan.change("function", key.clone(), ChangeKind::Changed, details);
+                    // CREATE OR REPLACE cannot change the result type, and there is no such form for aggregates
+                    if s.return_type != t.return_type || s.kind != t.kind || s.kind == "AGGREGATE" {
+                        plan.destructive(
+                            Phase::Functions,
+                            format!("Recreate {}; fails while other objects depend on it", key),
+                            format!("DROP {};\n{};", function_signature(t), s.definition),
+                        );
+                    } else {
+                        plan.step(
+                            Phase::Functions,
+                            format!("Replace {}", key),
+                            format!("{};", s.definition),
+                        );
+                    }
+                }
+                diff_grants(plan, &function_signature(s), &key, &s.grants, &t.grants);
+            }
+            _ => {}
+        }
+    }
+}
+
+// ── Grants ──
+
+/// Split `grantee:PRIVILEGE[*]`
+fn parse_grant(grant: &str) -> (String, &str, bool) {
+    let (grantee, privilege) = grant.rsplit_once(':').unwrap_or(("PUBLIC", grant));
+    let grantee = if grantee == "PUBLIC" {
+        "PUBLIC".to_string()
+    } else {
+        quote_ident(grantee)
+    };
+    match privilege.strip_suffix('*') {
+        Some(privilege) => (grantee, privilege, true),
+        None => (grantee, privilege, false),
+    }
+}
+
+/// `object` is the ON clause target, e.g. `TABLE "s"."t"`
+fn diff_grants(plan: &mut Plan, object: &str, key: &str, source: &[String], target: &[String]) {
+    // For functions the ON clause is FUNCTION or PROCEDURE, never AGGREGATE
+    let object = object.replacen("AGGREGATE ", "FUNCTION ", 1);
+    for grant in target.iter().filter(|g| !source.contains(g)) {
+        let (grantee, privilege, _) = parse_grant(grant);
+        plan.change(
+            "grant",
+            key.to_string(),
+            ChangeKind::Removed,
+            vec![grant.clone()],
+        );
+        plan.step(
+            Phase::Security,
+            format!("Revoke {} on {} from {}", privilege, key, grantee),
+            format!("REVOKE {} ON {} FROM {};", privilege, object, grantee),
+        );
+    }
+    for grant in source.iter().filter(|g| !target.contains(g)) {
+        let (grantee, privilege, grantable) = parse_grant(grant);
+        plan.change(
+            "grant",
+            key.to_string(),
+            ChangeKind::Added,
+            vec![grant.clone()],
+        );
+        plan.step(
+            Phase::Security,
+            format!("Grant {} on {} to {}", privilege, key, grantee),
+            format!(
+                "GRANT {} ON {} TO {}{};",
+                privilege,
+                object,
+                grantee,
+                if grantable { " WITH GRANT OPTION" } else { "" }
+            ),
+        );
+    }
+}
+
+// ── Tables ──
+
+fn column_sql(c: &ColumnSnapshot) -> String {
+    let mut sql = format!("{} {}", quote_ident(&c.name), c.data_type);
+    if let Some(collation) = &c.collation {
+        sql.push_str(&format!(" COLLATE {}", collation));
+    }
+    if let Some(identity) = &c.identity {
+        sql.push_str(&format!(" GENERATED {} AS IDENTITY", identity));
+    } else if let Some(expression) = &c.generated {
+        sql.push_str(&format!(
+            " GENERATED ALWAYS AS ({}) {}",
+            expression,
+            c.generated_kind.as_deref().unwrap_or("STORED")
+        ));
+    } else if let Some(default) = &c.default {
+        sql.push_str(&format!(" DEFAULT {}", default));
+    }
+    if !c.nullable {
+        sql.push_str(" NOT NULL");
+    }
+    sql
+}
+
+fn create_table_sql(t: &TableSnapshot) -> String {
+    let name = qualified(&t.schema, &t.name);
+    if let Some(parent) = &t.partition_of {
+        return format!(
+            "CREATE TABLE {} PARTITION OF {}\n{};",
+            name,
+            parent,
+            t.partition_bound.as_deref().unwrap_or("DEFAULT")
+        );
+    }
+    let columns: Vec<String> = t.columns.iter().map(column_sql).collect();
+    let mut sql = if columns.is_empty() {
+        format!("CREATE TABLE {} ()", name)
+    } else {
+        format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            name,
+            columns.join(",\n    ")
+        )
+    };
+    if let Some(key) = &t.partition_key {
+        sql.push_str(&format!("\nPARTITION BY {}", key));
+    }
+    sql.push(';');
+    sql
+}
+
+/// Returns the tables whose existing columns are dropped or retyped; views
+/// reading them have to be recreated around the change.
+fn diff_tables(
+    plan: &mut Plan,
+    source: &[TableSnapshot],
+    target: &[TableSnapshot],
+) -> HashSet<String> {
+    let mut rewritten = HashSet::new();
+    let empty = TableSnapshot {
+        schema: String::new(),
+        name: String::new(),
+        partition_key: None,
+        partition_of: None,
+        partition_bound: None,
+        columns: Vec::new(),
+        constraints: Vec::new(),
+        indexes: Vec::new(),
+        triggers: Vec::new(),
+        policies: Vec::new(),
+        rls_enabled: false,
+        grants: Vec::new(),
+        comment: None,
+    };
+
+    // Parents before partitions, and partitioned partitions before their own
+    let mut pairs = pair(source, target, TableSnapshot::key);
+    pairs.sort_by_key(|(_, s, t)| {
+        let table = s.or(*t).expect("paired table");
+        match (&table.partition_of, &table.partition_key) {
+            (None, _) => 0,
+            (Some(_), Some(_)) => 1,
+            (Some(_), None) => 2,
+        }
+    });
+    for (key, s, t) in pairs {
+        match (s, t) {
+            (Some(s), None) => {
+                plan.change("table", key.clone(), ChangeKind::Added, Vec::new());
+                plan.step(
+                    Phase::CreateTables,
+                    format!("Create table {}", key),
+                    create_table_sql(s),
+                );
+                diff_table_parts(plan, s, &empty, &key);
+            }
+            (None, Some(t)) => {
+                plan.change("table", key.clone(), ChangeKind::Removed, Vec::new());
+                // Foreign keys go first, so removed tables that reference each
+                // other can be dropped in any order
+                for fk in t.constraints.iter().filter(|c| c.kind == "f") {
+                    plan.step(
+                        Phase::DropTableParts,
+                        format!("Drop constraint {}.{}", key, fk.name),
+                        format!(
+                            "ALTER TABLE {} DROP CONSTRAINT IF EXISTS {};",
+                            qualified(&t.schema, &t.name),
+                            quote_ident(&fk.name)
+                        ),
+                    );
+                }
+                plan.destructive(
+                    Phase::DropTables,
+                    format!("Drop table {} and its data", key),
+                    format!("DROP TABLE IF EXISTS {};", qualified(&t.schema, &t.name)),
+                );
+            }
+            (Some(s), Some(t)) => {
+                let name = qualified(&s.schema, &s.name);
+                let mut details = Vec::new();
+                changed(
+                    &mut details,
+                    "partition key",
+                    &s.partition_key,
+                    &t.partition_key,
+                );
+                changed(
+                    &mut details,
+                    "partition of",
+                    &s.partition_of,
+                    &t.partition_of,
+                );
+                changed(
+                    &mut details,
+                    "partition bound",
+                    &s.partition_bound,
+                    &t.partition_bound,
+                );
+                if !details.is_empty() {
+                    plan.warnings.push(format!(
+                        "Partitioning of {} differs ({}); the table has to be rebuilt by hand",
+                        key,
+                        details.join("; ")
+                    ));
+                    plan.change("table", key.clone(), ChangeKind::Changed, details);
+                }
+                if diff_columns(plan, s, t, &key, &name) {
+                    rewritten.insert(key.clone());
+                }
+                diff_table_parts(plan, s, t, &key);
+            }
+            _ => {}
+        }
+    }
+    rewritten
+}
+
+/// Returns true when a column is dropped or changes type
+fn diff_columns(
+    plan: &mut Plan,
+    s: &TableSnapshot,
+    t: &TableSnapshot,
+    key: &str,
+    name: &str,
+) -> bool {
+    let mut rewrites = false;
+    for (column, sc, tc) in pair(&s.columns, &t.columns, |c| c.name.clone()) {
+        let column_key = format!("{}.{}", key, column);
+        let col = quote_ident(&column);
+        match (sc, tc) {
+            (Some(sc), None) => {
+                plan.change(
+                    "column",
+                    column_key.clone(),
+                    ChangeKind::Added,
+                    vec![sc.data_type.clone()],
+                );
+                if !sc.nullable
+                    && sc.default.is_none()
+                    && sc.identity.is_none()
+                    && sc.generated.is_none()
+                {
+                    plan.warnings.push(format!(
+                        "Adding NOT NULL column {} without a default fails if the table has rows",
+                        column_key
+                    ));
+                }
+                plan.step(
+                    Phase::AlterTables,
+                    format!("Add column {}", column_key),
+                    format!("ALTER TABLE {} ADD COLUMN {};", name, column_sql(sc)),
+                );
+            }
+            (None, Some(_)) => {
+                rewrites = true;
+                plan.change(
+                    "column",
+                    column_key.clone(),
+                    ChangeKind::Removed,
+                    Vec::new(),
+                );
+                plan.destructive(
+                    Phase::DropColumns,
+                    format!("Drop column {} and its data", column_key),
+                    format!("ALTER TABLE {} DROP COLUMN {};", name, col),
+                );
+            }
+            (Some(sc), Some(tc)) if sc != tc => {
+                let mut details = Vec::new();
+                changed(&mut details, "type", &sc.data_type, &tc.data_type);
+                changed(&mut details, "collation", &sc.collation, &tc.collation);
+                changed(&mut details, "nullable", &sc.nullable, &tc.nullable);
+                changed(&mut details, "default", &sc.default, &tc.default);
+                changed(&mut details, "identity", &sc.identity, &tc.identity);
+                changed(&mut details, "generated", &sc.generated, &tc.generated);
+                changed(
+                    &mut details,
+                    "generated kind",
+                    &sc.generated_kind,
+                    &tc.generated_kind,
+                );
+                plan.change("column", column_key.clone(), ChangeKind::Changed, details);
+
+                if sc.generated != tc.generated || sc.generated_kind != tc.generated_kind {
+                    // A generation expression cannot be altered in place
+                    rewrites = true;
+                    plan.destructive(
+                        Phase::AlterTables,
+                        format!("Recreate generated column {}", column_key),
+                        format!(
+                            "ALTER TABLE {} DROP COLUMN {};\nALTER TABLE {} ADD COLUMN {};",
+                            name,
+                            col,
+                            name,
+                            column_sql(sc)
+                        ),
+                    );
+                    continue;
+                }
+                let alter = |action: String| {
+                    format!("ALTER TABLE {} ALTER COLUMN {} {};", name, col, action)
+                };
+                if sc.data_type != tc.data_type || sc.collation != tc.collation {
+                    rewrites = true;
+                    let collate = sc
+                        .collation
+                        .as_ref()
+                        .map(|c| format!(" COLLATE {}", c))
+                        .unwrap_or_default();
+                    plan.destructive(
+                        Phase::AlterTables,
+                        format!(
+                            "Change type of {} from {} to {}; values are converted",
+                            column_key, tc.data_type, sc.data_type
+                        ),
+                        alter(format!(
+                            "TYPE {}{} USING {}::{}",
+                            sc.data_type, collate, col, sc.data_type
+                        )),
+                    );
+                }
+                if sc.default != tc.default {
+                    let action = match &sc.default {
+                        Some(default) => format!("SET DEFAULT {}", default),
+                        None => "DROP DEFAULT".to_string(),
+                    };
+                    plan.step(
+                        Phase::AlterTables,
+                        format!("Change default of {}", column_key),
+                        alter(action),
+                    );
+                }
+                if sc.identity != tc.identity {
+                    let action = match (&sc.identity, &tc.identity) {
+                        (Some(identity), None) => format!("ADD GENERATED {} AS IDENTITY", identity),
+                        (Some(identity), Some(_)) => format!("SET GENERATED {}", identity),
+                        _ => "DROP IDENTITY".to_string(),
+                    };
+                    plan.step(
+                        Phase::AlterTables,
+                        format!("Change identity of {}", column_key),
+                        alter(action),
+                    );
+                }
+                if sc.nullable != tc.nullable {
+                    if sc.nullable {
+                        plan.step(
+                            Phase::AlterTables,
+                            format!("Allow NULL in {}", column_key),
+                            alter("DROP NOT NULL".to_string()),
+                        );
+                    } else {
+                        plan.warnings.push(format!(
+                            "SET NOT NULL on {} fails if it holds NULLs",
+                            column_key
+                        ));
+                        plan.step(
+                            Phase::AlterTables,
+                            format!("Forbid NULL in {}", column_key),
+                            alter("SET NOT NULL".to_string()),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    rewrites
+}
+
+/// Constraints, indexes, triggers, policies, RLS, grants and the comment.
+/// For a new table, `t` is empty and everything is added.
+fn diff_table_parts(plan: &mut Plan, s: &TableSnapshot, t: &TableSnapshot, key: &str) {
+    let name = qualified(&s.schema, &s.name);
+
+    for (constraint, sc, tc) in pair(&s.constraints, &t.constraints, |c| c.name.clone()) {
+        let constraint_key = format!("{}.{}", key, constraint);
+        let drop = format!(
+            "ALTER TABLE {} DROP CONSTRAINT {};",
+            name,
+            quote_ident(&constraint)
+        );
+        let add = |c: &ConstraintSnapshot| {
+            let phase = if c.kind == "f" {
+                Phase::ForeignKeys
+            } else {
+                Phase::Constraints
+            };
+            let sql = format!(
+                "ALTER TABLE {} ADD CONSTRAINT {} {};",
+                name,
+                quote_ident(&c.name),
+                c.definition
+            );
+            (phase, sql)
+        };
+        match (sc, tc) {
+            (Some(sc), None) => {
+                if !t.name.is_empty() {
+                    plan.change(
+                        "constraint",
+                        constraint_key.clone(),
+                        ChangeKind::Added,
+                        vec![sc.definition.clone()],
+                    );
+                }
+                let (phase, sql) = add(sc);
+                plan.step(phase, format!("Add constraint {}", constraint_key), sql);
+            }
+            (None, Some(tc)) => {
+                plan.change(
+                    "constraint",
+                    constraint_key.clone(),
+                    ChangeKind::Removed,
+                    vec![tc.definition.clone()],
+                );
+                plan.step(
+                    Phase::DropTableParts,
+                    format!("Drop constraint {}", constraint_key),
+                    drop,
+                );
+            }
+            (Some(sc), Some(tc)) if sc != tc => {
+                plan.change(
+                    "constraint",
+                    constraint_key.clone(),
+                    ChangeKind::Changed,
+                    vec![format!("{} → {}", tc.definition, sc.definition)],
+                );
+                plan.step(
+                    Phase::DropTableParts,
+                    format!("Drop changed constraint {}", constraint_key),
+                    drop,
+                );
+                let (phase, sql) = add(sc);
+                plan.step(phase, format!("Add constraint {}", constraint_key), sql);
+            }
+            _ => {}
+        }
+    }
+
+    let index_drop = |i: &NamedDefinition| format!("DROP INDEX {};", qualified(&s.schema, &i.name));
+    diff_named(
+        plan,
+        "index",
+        key,
+        &s.indexes,
+        &t.indexes,
+        Phase::Indexes,
+        index_drop,
+        !t.name.is_empty(),
+    );
+    let trigger_drop =
+        |i: &NamedDefinition| format!("DROP TRIGGER {} ON {};", quote_ident(&i.name), name);
+    diff_named(
+        plan,
+        "trigger",
+        key,
+        &s.triggers,
+        &t.triggers,
+        Phase::Triggers,
+        trigger_drop,
+        !t.name.is_empty(),
+    );
+    let policy_drop =
+        |i: &NamedDefinition| format!("DROP POLICY {} ON {};", quote_ident(&i.name), name);
+    diff_named(
+        plan,
+        "policy",
+        key,
+        &s.policies,
+        &t.policies,
+        Phase::Security,
+        policy_drop,
+        !t.name.is_empty(),
+    );
+
+    if s.rls_enabled != t.rls_enabled {
+        if !t.name.is_empty() {
+            plan.change(
+                "table",
+                key.to_string(),
+                ChangeKind::Changed,
+                vec![format!(
+                    "row level security: {} → {}",
+                    t.rls_enabled, s.rls_enabled
+                )],
+            );
+        }
+        let action = if s.rls_enabled { "ENABLE" } else { "DISABLE" };
+        plan.step(
+            Phase::Security,
+            format!("{} row level security on {}", action.to_lowercase(), key),
+            format!("ALTER TABLE {} {} ROW LEVEL SECURITY;", name, action),
+        );
+    }
+    if s.comment != t.comment {
+        let comment = s
+            .comment
+            .as_deref()
+            .map(quote_literal)
+            .unwrap_or_else(|| "NULL".to_string());
+        plan.step(
+            Phase::Security,
+            format!("Comment on table {}", key),
+            format!("COMMENT ON TABLE {} IS {};", name, comment),
+        );
+    }
+    diff_grants(plan, &format!("TABLE {}", name), key, &s.grants, &t.grants);
+}
+
+/// Indexes, triggers and policies: compared by name, changed ones are dropped
+/// and created again
+#[allow(clippy::too_many_arguments)]
+fn diff_named<D>(
+    plan: &mut Plan,
+    object_type: &str,
+    key: &str,
+    source: &[NamedDefinition],
+    target: &[NamedDefinition],
+    phase: Phase,
+    drop: D,
+    report_added: bool,
+) where
+    D: Fn(&NamedDefinition) -> String,
+{
+    for (name, s, t) in pair(source, target, |d| d.name.clone()) {
+        let object_key = format!("{}.{}", key, name);
+        let create = |d: &NamedDefinition| {
+            let sql = d.definition.trim_end_matches(';');
+            format!("{};", sql)
+        };
+        match (s, t) {
+            (Some(s), None) => {
+                if report_added {
+                    plan.change(
+                        object_type,
+                        object_key.clone(),
+                        ChangeKind::Added,
+                        Vec::new(),
+                    );
+                }
+                plan.step(
+                    phase,
+                    format!("Create {} {}", object_type, object_key),
+                    create(s),
+                );
+            }
+            (None, Some(t)) => {
+                plan.change(
+                    object_type,
+                    object_key.clone(),
+                    ChangeKind::Removed,
+                    Vec::new(),
+                );
+                plan.step(
+                    Phase::DropTableParts,
+                    format!("Drop {} {}", object_type, object_key),
+                    drop(t),
+                );
+            }
+            (Some(s), Some(t)) if s.definition != t.definition => {
+                plan.change(
+                    object_type,
+                    object_key.clone(),
+                    ChangeKind::Changed,
+                    vec![format!("{} → {}", t.definition, s.definition)],
+                );
+                plan.step(
+                    Phase::DropTableParts,
+                    format!("Drop changed {} {}", object_type, object_key),
+                    drop(t),
+                );
+                plan.step(
+                    phase,
+                    format!("Create {} {}", object_type, object_key),
+                    create(s),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+// ── Views ──
+
+fn view_keyword(v: &ViewSnapshot) -> &'static str {
+    if v.materialized {
+        "MATERIALIZED VIEW"
+    } else {
+        "VIEW"
+    }
+}
+
+/// `views` ordered so that every view comes after the views it reads
+fn dependency_order<'a>(views: &[&'a ViewSnapshot]) -> Vec<&'a ViewSnapshot> {
+    fn visit<'a>(
+        view: &'a ViewSnapshot,
+        by_key: &HashMap<String, &'a ViewSnapshot>,
+        seen: &mut HashSet<String>,
+        out: &mut Vec<&'a ViewSnapshot>,
+    ) {
+        if !seen.insert(view.key()) {
+            return;
+        }
+        for dependency in &view.depends_on {
+            if let Some(dep) = by_key.get(dependency) {
+                visit(dep, by_key, seen, out);
+            }
+        }
+        out.push(view);
+    }
+    let by_key: HashMap<String, &ViewSnapshot> = views.iter().map(|v| (v.key(), *v)).collect();
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for view in views {
+        visit(view, &by_key, &mut seen, &mut out);
+    }
+    out
+}
+
+fn diff_views(
+    plan: &mut Plan,
+    source: &[ViewSnapshot],
+    target: &[ViewSnapshot],
+    rewritten: &HashSet<String>,
+) {
+    let source_by_key: HashMap<String, &ViewSnapshot> =
+        source.iter().map(|v| (v.key(), v)).collect();
+
+    // Views to drop and create again: changed ones, those over rewritten
+    // columns, and everything in the target that reads one of them
+    let mut recreate: HashSet<String> = target
+        .iter()
+        .filter(|t| match source_by_key.get(&t.key()) {
+            Some(s) => {
+                s.definition != t.definition
+                    || s.materialized != t.materialized
+                    || s.options != t.options
+            }
+            None => false,
+        })
+        .map(ViewSnapshot::key)
+        .collect();
+    recreate.extend(
+        target
+            .iter()
+            .filter(|t| {
+                source_by_key.contains_key(&t.key())
+                    && t.depends_on.iter().any(|d| rewritten.contains(d))
+            })
+            .map(ViewSnapshot::key),
+    );
+    loop {
+        let dependents: Vec<String> = target
+            .iter()
+            .filter(|t| {
+                !recreate.contains(&t.key()) && t.depends_on.iter().any(|d| recreate.contains(d))
+            })
+            .map(ViewSnapshot::key)
+            .collect();
+        if dependents.is_empty() {
+            break;
+        }
+        recreate.extend(dependents);
+    }
+
+    let target_keys: HashSet<String> = target.iter().map(ViewSnapshot::key).collect();
+    let dropped: Vec<&ViewSnapshot> = target
+        .iter()
+        .filter(|t| recreate.contains(&t.key()) || !source_by_key.contains_key(&t.key()))
+        .collect();
+    // Dependents first
+    for view in dependency_order(&dropped).into_iter().rev() {
+        let key = view.key();
+        let sql = format!(
+            "DROP {} IF EXISTS {};",
+            view_keyword(view),
+            qualified(&view.schema, &view.name)
+        );
+        match source_by_key.get(&key) {
+            Some(s) => {
+                let details = if s.definition != view.definition {
+                    vec![format!(
+                        "definition: {} → {}",
+                        view.definition, s.definition
+                    )]
+                } else {
+                    vec!["recreated for a dependency".to_string()]
+                };
+                plan.change("view", key.clone(), ChangeKind::Changed, details);
+                plan.step(
+                    Phase::DropViews,
+                    format!("Drop {} for recreation", key),
+                    sql,
+                );
+            }
+            None => {
+                plan.change("view", key.clone(), ChangeKind::Removed, Vec::new());
+                plan.destructive(Phase::DropViews, format!("Drop {}", key), sql);
+            }
+        }
+    }
+
+    let created: Vec<&ViewSnapshot> = source
+        .iter()
+        .filter(|s| recreate.contains(&s.key()) || !target_keys.contains(&s.key()))
+        .collect();
+    for view in dependency_order(&created) {
+        let key = view.key();
+        if !target_keys.contains(&key) {
+            plan.change("view", key.clone(), ChangeKind::Added, Vec::new());
+        }
+        let name = qualified(&view.schema, &view.name);
+        let options = if view.options.is_empty() {
+            String::new()
+        } else {
+            format!(" WITH ({})", view.options.join(", "))
+        };
+        plan.step(
+            Phase::CreateViews,
+            format!("Create {}", key),
+            format!(
+                "CREATE {} {}{} AS\n{};",
+                view_keyword(view),
+                name,
+                options,
+                view.definition
+            ),
+        );
+        for index in &view.indexes {
+            plan.step(
+                Phase::CreateViews,
+                format!("Create index {}.{}", key, index.name),
+                format!("{};", index.definition),
+            );
+        }
+        diff_grants(plan, &format!("TABLE {}", name), &key, &view.grants, &[]);
+    }
+
+    // Views left in place keep their indexes and grants; compare those
+    let target_by_key: HashMap<String, &ViewSnapshot> =
+        target.iter().map(|v| (v.key(), v)).collect();
+    for s in source.iter().filter(|s| !recreate.contains(&s.key())) {
+        let Some(t) = target_by_key.get(&s.key()) else {
+            continue;
+        };
+        let key = s.key();
+        let index_drop =
+            |i: &NamedDefinition| format!("DROP INDEX {};", qualified(&s.schema, &i.name));
+        diff_named(
+            plan,
+            "index",
+            &key,
+            &s.indexes,
+            &t.indexes,
+            Phase::CreateViews,
+            index_drop,
+            true,
+        );
+        diff_grants(
+            plan,
+            &format!("TABLE {}", qualified(&s.schema, &s.name)),
+            &key,
+            &s.grants,
+            &t.grants,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_enum(labels: &[&str]) -> SchemaSnapshot {
+        SchemaSnapshot {
+            schemas: vec!["s".into()],
+            types: vec![TypeSnapshot {
+                schema: "s".into(),
+                name: "mood".into(),
+                kind: "enum".into(),
+                definition: format!("{:?}", labels),
+                enum_labels: labels.iter().map(|l| l.to_string()).collect(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn steps(source: &[&str], target: &[&str]) -> Vec<String> {
+        diff_snapshots(&with_enum(source), &with_enum(target))
+            .steps
+            .into_iter()
+            .map(|s| s.sql)
+            .collect()
+    }
+
+    fn table(name: &str, constraints: Vec<ConstraintSnapshot>) -> TableSnapshot {
+        TableSnapshot {
+            schema: "s".into(),
+            name: name.into(),
+            partition_key: None,
+            partition_of: None,
+            partition_bound: None,
+            columns: Vec::new(),
+            constraints,
+            indexes: Vec::new(),
+            triggers: Vec::new(),
+            policies: Vec::new(),
+            rls_enabled: false,
+            grants: Vec::new(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn removed_tables_drop_their_foreign_keys_first() {
+        let target = SchemaSnapshot {
+            schemas: vec!["s".into()],
+            tables: vec![
+                table("customers", Vec::new()),
+                table(
+                    "orders",
+                    vec![ConstraintSnapshot {
+                        name: "orders_customer_fkey".into(),
+                        kind: "f".into(),
+                        definition: "FOREIGN KEY (customer_id) REFERENCES s.customers(id)".into(),
+                    }],
+                ),
+            ],
+            ..Default::default()
+        };
+        let source = SchemaSnapshot {
+            schemas: vec!["s".into()],
+            ..Default::default()
+        };
+        let steps: Vec<String> = diff_snapshots(&source, &target)
+            .steps
+            .into_iter()
+            .map(|s| s.sql)
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                "ALTER TABLE \"s\".\"orders\" DROP CONSTRAINT IF EXISTS \"orders_customer_fkey\";",
+                "DROP TABLE IF EXISTS \"s\".\"customers\";",
+                "DROP TABLE IF EXISTS \"s\".\"orders\";",
+            ]
+        );
+    }
+
+    #[test]
+    fn enum_labels_are_added_in_place() {
+        assert_eq!(
+            steps(&["a", "b", "x", "c"], &["x"]),
+            vec![
+                "ALTER TYPE \"s\".\"mood\" ADD VALUE 'a' BEFORE 'x';",
+                "ALTER TYPE \"s\".\"mood\" ADD VALUE 'b' AFTER 'a';",
+                "ALTER TYPE \"s\".\"mood\" ADD VALUE 'c' AFTER 'x';",
+            ]
+        );
+        assert_eq!(
+            steps(&["a", "b"], &[]),
+            vec![
+                "ALTER TYPE \"s\".\"mood\" ADD VALUE 'a';",
+                "ALTER TYPE \"s\".\"mood\" ADD VALUE 'b' AFTER 'a';",
+            ]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use super::quote_ident;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Serialize)]
+pub struct ExtensionInfo {
+    pub name: String,
+    pub version: String,
+    pub schema: String,
+    pub relocatable: bool,
+    pub description: Option<String>,
+    pub default_version: Option<String>,
+    /// Versions reachable from the installed one with ALTER EXTENSION ... UPDATE
+    pub available_updates: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailableExtension {
+    pub name: String,
+    pub default_version: Option<String>,
+    pub installed_version: Option<String>,
+    pub description: Option<String>,
+    pub versions: Vec<String>,
+    pub requires: Vec<String>,
+    pub superuser: bool,
+    pub trusted: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExtensionObject {
+    pub kind: String,
+    pub schema: Option<String>,
+    pub identity: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ExtensionChange {
+    Create {
+        schema: Option<String>,
+        version: Option<String>,
+        #[serde(default)]
+        cascade: bool,
+    },
+    Update {
+        version: Option<String>,
+    },
+    SetSchema {
+        schema: String,
+    },
+    Drop {
+        #[serde(default)]
+        cascade: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExtensionChangePreview {
+    pub ddl: String,
+    /// Objects currently owned by the extension; for a drop, what goes with it
+    pub objects: Vec<ExtensionObject>,
+}
+
+pub async fn get_extensions(client: &Arc<Client>) -> Result<Vec<ExtensionInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                e.extname,
+                e.extversion,
+                n.nspname,
+                e.extrelocatable,
+                a.comment,
+                a.default_version,
+                COALESCE((
+                    SELECT array_agg(u.target ORDER BY u.target)
+                    FROM pg_extension_update_paths(e.extname) u
+                    WHERE u.source = e.extversion AND u.path IS NOT NULL
+                ), '{}') as available_updates
+             FROM pg_extension e
+             JOIN pg_namespace n ON n.oid = e.extnamespace
+             LEFT JOIN pg_available_extensions a ON a.name = e.extname
+             ORDER BY e.extname",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ExtensionInfo {
+            name: row.get(0),
+            version: row.get(1),
+            schema: row.get(2),
+            relocatable: row.get(3),
+            description: row.get(4),
+            default_version: row.get(5),
+            available_updates: row.get(6),
+        })
+        .collect())
+}
+
+pub async fn get_available_extensions(client: &Arc<Client>) -> Result<Vec<AvailableExtension>> {
+    let rows = client
+        .query(
+            "SELECT
+                a.name,
+                a.default_version,
+                a.installed_version,
+                a.comment,
+                COALESCE(array_agg(v.version ORDER BY v.version)
+                    FILTER (WHERE v.version IS NOT NULL), '{}') as versions,
+                COALESCE((
+                    SELECT array_agg(DISTINCT r ORDER BY r)
+                    FROM pg_available_extension_versions d, unnest(d.requires) r
+                    WHERE d.name = a.name AND d.version = a.default_version
+                ), '{}') as requires,
+                COALESCE(bool_or(v.superuser) FILTER (WHERE v.version = a.default_version), false),
+                COALESCE(bool_or(v.trusted) FILTER (WHERE v.version = a.default_version), false)
+             FROM pg_available_extensions a
+             LEFT JOIN pg_available_extension_versions v ON v.name = a.name
+             GROUP BY a.name, a.default_version, a.installed_version, a.comment
+             ORDER BY a.name",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| AvailableExtension {
+            name: row.get(0),
+            default_version: row.get(1),
+            installed_version: row.get(2),
+            description: row.get(3),
+            versions: row.get(4),
+            requires: row.get(5),
+            superuser: row.get(6),
+            trusted: row.get(7),
+        })
+        .collect())
+}
+
+/// Objects that belong to an installed extension (deptype 'e' in pg_depend)
+pub async fn get_extension_objects(client: &Arc<Client>, name: &str) -> Result<Vec<ExtensionObject>> {
+    let rows = client
+        .query(
+            "SELECT o.type, o.schema, o.identity
+             FROM pg_depend d
+             JOIN pg_extension e ON e.oid = d.refobjid
+             CROSS JOIN LATERAL pg_identify_object(d.classid, d.objid, d.objsubid) o
+             WHERE d.refclassid = 'pg_extension'::regclass
+               AND d.deptype = 'e'
+               AND e.extname = $1
+             ORDER BY o.type, o.identity",
+            &[&name],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ExtensionObject {
+            kind: row.get(0),
+            schema: row.get(1),
+            identity: row.get(2),
+        })
+        .collect())
+}
+
+/// The statement that applies `change` to extension `name`
+pub fn extension_ddl(name: &str, change: &ExtensionChange) -> String {
+    let name = quote_ident(name);
+    match change {
+        ExtensionChange::Create {
+            schema,
+            version,
+            cascade,
+        } => {
+            let mut sql = format!("CREATE EXTENSION IF NOT EXISTS {}", name);
+            if let Some(schema) = schema.as_deref().filter(|s| !s.is_empty()) {
+                sql.push_str(&format!(" SCHEMA {}", quote_ident(schema)));
+            }
+            if let Some(version) = version.as_deref().filter(|v| !v.is_empty()) {
+                sql.push_str(&format!(" VERSION {}", quote_ident(version)));
+            }
+            if *cascade {
+                sql.push_str(" CASCADE");
+            }
+            sql
+        }
+        ExtensionChange::Update { version } => match version.as_deref().filter(|v| !v.is_empty()) {
+            Some(version) => format!("ALTER EXTENSION {} UPDATE TO {}", name, quote_ident(version)),
+            None => format!("ALTER EXTENSION {} UPDATE", name),
+        },
+        ExtensionChange::SetSchema { schema } => {
+            format!("ALTER EXTENSION {} SET SCHEMA {}", name, quote_ident(schema))
+        }
+        ExtensionChange::Drop { cascade } => format!(
+            "DROP EXTENSION {}{}",
+            name,
+            if *cascade { " CASCADE" } else { "" }
+        ),
+    }
+}
+
+pub async fn preview_extension_change(
+    client: &Arc<Client>,
+    name: &str,
+    change: &ExtensionChange,
+) -> Result<ExtensionChangePreview> {
+    Ok(ExtensionChangePreview {
+        ddl: extension_ddl(name, change),
+        objects: get_extension_objects(client, name).await?,
+    })
+}
@@ -0,0 +1,405 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Serialize)]
+pub struct GraphColumn {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub primary_key: bool,
+    /// Part of at least one foreign key of the table
+    pub foreign_key: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    /// `schema.table`
+    pub id: String,
+    pub schema: String,
+    pub name: String,
+    /// table, partitioned_table or foreign_table
+    pub kind: String,
+    /// Outside the requested schemas, included because an edge reaches it
+    pub external: bool,
+    pub columns: Vec<GraphColumn>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    /// Each parent row has at most one child row: the FK columns are unique
+    OneToOne,
+    ManyToOne,
+}
+
+/// A foreign key, pointing from the referencing table to the referenced one
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    /// `schema.table.constraint`
+    pub id: String,
+    pub name: String,
+    /// Node id of the referencing table
+    pub source: String,
+    /// Node id of the referenced table
+    pub target: String,
+    /// Referencing columns, in key order
+    pub columns: Vec<String>,
+    /// Referenced columns, matching `columns` position by position
+    pub referenced_columns: Vec<String>,
+    pub cardinality: Cardinality,
+    /// A child row may have no parent because an FK column is nullable
+    pub optional: bool,
+    pub self_reference: bool,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelationshipGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphFormat {
+    Mermaid,
+    Dot,
+    Json,
+}
+
+fn fk_action(code: &str) -> &'static str {
+    match code {
+        "r" => "RESTRICT",
+        "c" => "CASCADE",
+        "n" => "SET NULL",
+        "d" => "SET DEFAULT",
+        _ => "NO ACTION",
+    }
+}
+
+/// Tables of `schemas` (every user schema when empty) and the foreign keys
+/// that start or end in them. Tables on the far side of an edge are added as
+/// external nodes.
+pub async fn get_relationship_graph(
+    client: &Arc<Client>,
+    schemas: &[String],
+) -> Result<RelationshipGraph> {
+    let rows = client
+        .query(
+            "SELECT n.nspname FROM pg_namespace n
+             WHERE n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+               AND (cardinality($1::text[]) = 0 OR n.nspname = ANY($1))",
+            &[&schemas],
+        )
+        .await?;
+    let schemas: Vec<String> = rows.iter().map(|r| r.get(0)).collect();
+
+    // Constraints cloned onto partitions have a parent and are left out
+    let rows = client
+        .query(
+            "SELECT
+                con.conname,
+                sn.nspname,
+                sc.relname,
+                tn.nspname,
+                tc.relname,
+                ARRAY(
+                    SELECT a.attname::text
+                    FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord)
+                    JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                    ORDER BY k.ord
+                ),
+                ARRAY(
+                    SELECT a.attname::text
+                    FROM unnest(con.confkey) WITH ORDINALITY k(attnum, ord)
+                    JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+                    ORDER BY k.ord
+                ),
+                EXISTS (
+                    SELECT 1 FROM pg_index i
+                    WHERE i.indrelid = con.conrelid AND i.indisunique AND i.indpred IS NULL
+                      AND NOT (0 = ANY ((i.indkey::int2[])[0:i.indnkeyatts - 1]))
+                      AND (i.indkey::int2[])[0:i.indnkeyatts - 1] <@ con.conkey
+                ),
+                EXISTS (
+                    SELECT 1 FROM pg_attribute a
+                    WHERE a.attrelid = con.conrelid AND a.attnum = ANY (con.conkey) AND NOT a.attnotnull
+                ),
+                con.confupdtype::text,
+                con.confdeltype::text,
+                con.conrelid,
+                con.confrelid
+             FROM pg_constraint con
+             JOIN pg_class sc ON sc.oid = con.conrelid
+             JOIN pg_namespace sn ON sn.oid = sc.relnamespace
+             JOIN pg_class tc ON tc.oid = con.confrelid
+             JOIN pg_namespace tn ON tn.oid = tc.relnamespace
+             WHERE con.contype = 'f' AND con.conparentid = 0
+               AND (sn.nspname = ANY($1) OR tn.nspname = ANY($1))
+             ORDER BY sn.nspname, sc.relname, con.conname",
+            &[&schemas],
+        )
+        .await?;
+
+    let mut endpoints: Vec<u32> = Vec::new();
+    let mut fk_columns: HashSet<(String, String)> = HashSet::new();
+    let edges: Vec<GraphEdge> = rows
+        .iter()
+        .map(|row| {
+            let name: String = row.get(0);
+            let source = format!("{}.{}", row.get::<_, String>(1), row.get::<_, String>(2));
+            let target = format!("{}.{}", row.get::<_, String>(3), row.get::<_, String>(4));
+            let columns: Vec<String> = row.get(5);
+            endpoints.push(row.get(11));
+            endpoints.push(row.get(12));
+            for column in &columns {
+                fk_columns.insert((source.clone(), column.clone()));
+            }
+            GraphEdge {
+                id: format!("{}.{}", source, name),
+                name,
+                self_reference: source == target,
+                source,
+                target,
+                columns,
+                referenced_columns: row.get(6),
+                cardinality: if row.get(7) {
+                    Cardinality::OneToOne
+                } else {
+                    Cardinality::ManyToOne
+                },
+                optional: row.get(8),
+                on_update: fk_action(row.get(9)).to_string(),
+                on_delete: fk_action(row.get(10)).to_string(),
+            }
+        })
+        .collect();
+
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                CASE c.relkind
+                    WHEN 'p' THEN 'partitioned_table'
+                    WHEN 'f' THEN 'foreign_table'
+                    ELSE 'table'
+                END,
+                NOT (n.nspname = ANY($1)),
+                a.attname,
+                format_type(a.atttypid, a.atttypmod),
+                NOT a.attnotnull,
+                EXISTS (
+                    SELECT 1 FROM pg_constraint pk
+                    WHERE pk.conrelid = c.oid AND pk.contype = 'p' AND a.attnum = ANY (pk.conkey)
+                )
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             LEFT JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+             WHERE c.relkind IN ('r', 'p', 'f') AND NOT c.relispartition
+               AND (n.nspname = ANY($1) OR c.oid = ANY($2))
+             ORDER BY n.nspname, c.relname, a.attnum",
+            &[&schemas, &endpoints],
+        )
+        .await?;
+
+    let mut nodes: Vec<GraphNode> = Vec::new();
+    for row in &rows {
+        let schema: String = row.get(0);
+        let name: String = row.get(1);
+        let id = format!("{}.{}", schema, name);
+        if nodes.last().map(|n| n.id != id).unwrap_or(true) {
+            nodes.push(GraphNode {
+                id: id.clone(),
+                schema,
+                name,
+                kind: row.get(2),
+                external: row.get(3),
+                columns: Vec::new(),
+            });
+        }
+        let column: Option<String> = row.get(4);
+        if let (Some(column), Some(node)) = (column, nodes.last_mut()) {
+            node.columns.push(GraphColumn {
+                foreign_key: fk_columns.contains(&(id, column.clone())),
+                name: column,
+                data_type: row.get(5),
+                nullable: row.get(6),
+                primary_key: row.get(7),
+            });
+        }
+    }
+    Ok(RelationshipGraph { nodes, edges })
+}
+
+pub fn export_relationship_graph(graph: &RelationshipGraph, format: GraphFormat) -> Result<String> {
+    Ok(match format {
+        GraphFormat::Mermaid => to_mermaid(graph),
+        GraphFormat::Dot => to_dot(graph),
+        GraphFormat::Json => serde_json::to_string_pretty(graph)?,
+    })
+}
+
+/// Mermaid identifiers and attribute types allow only word characters
+fn mermaid_word(text: &str) -> String {
+    let word: String = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let word = word.trim_matches('_').to_string();
+    if word.is_empty() {
+        "_".to_string()
+    } else {
+        word
+    }
+}
+
+pub fn to_mermaid(graph: &RelationshipGraph) -> String {
+    let entity = |id: &str| mermaid_word(id);
+    let mut out = String::from("erDiagram\n");
+    for node in &graph.nodes {
+        out.push_str(&format!(
+            "    {}[\"{}\"] {{\n",
+            entity(&node.id),
+            node.id.replace('"', "'")
+        ));
+        for column in &node.columns {
+            let keys: Vec<&str> = [(column.primary_key, "PK"), (column.foreign_key, "FK")]
+                .iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, key)| *key)
+                .collect();
+            out.push_str(&format!(
+                "        {} {}{}\n",
+                mermaid_word(&column.data_type),
+                mermaid_word(&column.name),
+                if keys.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", keys.join(", "))
+                }
+            ));
+        }
+        out.push_str("    }\n");
+    }
+    for edge in &graph.edges {
+        // Parent on the left; a child row needs a parent unless the key is nullable
+        let parent = if edge.optional { "|o" } else { "||" };
+        let child = match edge.cardinality {
+            Cardinality::OneToOne => "o|",
+            Cardinality::ManyToOne => "o{",
+        };
+        out.push_str(&format!(
+            "    {} {}--{} {} : \"{}\"\n",
+            entity(&edge.target),
+            parent,
+            child,
+            entity(&edge.source),
+            edge.name.replace('"', "'")
+        ));
+    }
+    out
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Graphviz digraph with one HTML-table node per table, a cluster per schema,
+/// and edges drawn between the first column of each key
+pub fn to_dot(graph: &RelationshipGraph) -> String {
+    let mut by_schema: BTreeMap<&str, Vec<&GraphNode>> = BTreeMap::new();
+    for node in &graph.nodes {
+        by_schema.entry(&node.schema).or_default().push(node);
+    }
+    let nodes_by_id: HashMap<&str, &GraphNode> =
+        graph.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+
+    let mut out = String::from(
+        "digraph relationships {\n    rankdir=LR;\n    node [shape=plaintext, fontname=\"Helvetica\"];\n    edge [fontname=\"Helvetica\", fontsize=10];\n",
+    );
+    for (i, (schema, nodes)) in by_schema.iter().enumerate() {
+        out.push_str(&format!(
+            "\n    subgraph cluster_{} {{\n        label={};\n",
+            i,
+            dot_string(schema)
+        ));
+        for node in nodes {
+            let mut label = format!(
+                "<TABLE BORDER=\"0\" CELLBORDER=\"1\" CELLSPACING=\"0\"{}>\n            <TR><TD COLSPAN=\"2\" BGCOLOR=\"lightgrey\"><B>{}</B></TD></TR>\n",
+                if node.external { " COLOR=\"grey\"" } else { "" },
+                html_escape(&node.name)
+            );
+            for (position, column) in node.columns.iter().enumerate() {
+                let marker = match (column.primary_key, column.foreign_key) {
+                    (true, true) => "PK FK",
+                    (true, false) => "PK",
+                    (false, true) => "FK",
+                    _ => "",
+                };
+                label.push_str(&format!(
+                    "            <TR><TD ALIGN=\"LEFT\" PORT=\"c{}\">{}{}</TD><TD ALIGN=\"LEFT\">{}</TD></TR>\n",
+                    position,
+                    html_escape(&column.name),
+                    if marker.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" <I>{}</I>", marker)
+                    },
+                    html_escape(&column.data_type)
+                ));
+            }
+            label.push_str("        </TABLE>");
+            out.push_str(&format!(
+                "        {} [label=<{}>];\n",
+                dot_string(&node.id),
+                label
+            ));
+        }
+        out.push_str("    }\n");
+    }
+    out.push('\n');
+    for edge in &graph.edges {
+        let endpoint = |node: &str, columns: &[String]| {
+            let position = nodes_by_id.get(node).and_then(|n| {
+                let first = columns.first()?;
+                n.columns.iter().position(|c| &c.name == first)
+            });
+            match position {
+                Some(position) => format!("{}:\"c{}\"", dot_string(node), position),
+                None => dot_string(node),
+            }
+        };
+        out.push_str(&format!(
+            "    {} -> {} [label={}, taillabel=\"{}\", headlabel=\"{}\"{}];\n",
+            endpoint(&edge.source, &edge.columns),
+            endpoint(&edge.target, &edge.referenced_columns),
+            dot_string(&edge.name),
+            match edge.cardinality {
+                Cardinality::OneToOne => "0..1",
+                Cardinality::ManyToOne => "*",
+            },
+            if edge.optional { "0..1" } else { "1" },
+            if edge.optional { ", style=dashed" } else { "" }
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,524 @@
+use serde::Serialize;
+use sqlparser::ast::{
+    AlterTableOperation, FromTable, MergeAction, ObjectType, Query, SetExpr, Statement, TableFactor,
+};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_postgres::Client;
+
+/// How long a confirmation token stays valid after it was issued
+const TOKEN_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DestructiveKind {
+    Drop,
+    DropDatabase,
+    Truncate,
+    DeleteWithoutWhere,
+    UpdateWithoutWhere,
+    AlterDropColumn,
+    /// The parser could not analyze the statement but it starts with a destructive keyword
+    Unrecognized,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestructiveStatement {
+    pub kind: DestructiveKind,
+    pub description: String,
+    pub statement: String,
+    pub targets: Vec<String>,
+    pub estimated_rows: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfirmationRequired {
+    pub token: String,
+    pub statements: Vec<DestructiveStatement>,
+    pub expires_in_secs: u64,
+}
+
+/// Classify every statement in `sql` and return the destructive ones.
+pub fn classify_statements(sql: &str) -> Vec<DestructiveStatement> {
+    match Parser::parse_sql(&PostgreSqlDialect {}, sql) {
+        Ok(statements) => {
+            let mut result = Vec::new();
+            for stmt in &statements {
+                classify_nested(stmt, &mut result);
+            }
+            result
+        }
+        Err(_) => classify_unparsed(sql),
+    }
+}
+
+/// Classify `stmt` along with the statements that run as part of it: the
+/// target of EXPLAIN ANALYZE and data-modifying CTEs.
+fn classify_nested(stmt: &Statement, result: &mut Vec<DestructiveStatement>) {
+    match stmt {
+        Statement::Explain {
+            analyze,
+            options,
+            statement,
+            ..
+        } => {
+            let analyze_option = options.iter().flatten().any(|o| {
+                o.name.value.eq_ignore_ascii_case("analyze")
+                    && o.arg.as_ref().is_none_or(|arg| {
+                        let arg = arg.to_string();
+                        !["false", "off", "0"]
+                            .iter()
+                            .any(|v| arg.eq_ignore_ascii_case(v))
+                    })
+            });
+            if *analyze || analyze_option {
+                classify_nested(statement, result);
+            }
+        }
+        Statement::Query(query) => classify_query(query, result),
+        Statement::Insert(insert) => {
+            if let Some(source) = &insert.source {
+                classify_query(source, result);
+            }
+        }
+        _ => result.extend(classify(stmt)),
+    }
+}
+
+fn classify_query(query: &Query, result: &mut Vec<DestructiveStatement>) {
+    for cte in query.with.iter().flat_map(|w| &w.cte_tables) {
+        classify_query(&cte.query, result);
+    }
+    classify_set_expr(&query.body, result);
+}
+
+fn classify_set_expr(expr: &SetExpr, result: &mut Vec<DestructiveStatement>) {
+    match expr {
+        SetExpr::Insert(stmt) | SetExpr::Update(stmt) => classify_nested(stmt, result),
+        SetExpr::Query(query) => classify_query(query, result),
+        SetExpr::SetOperation { left, right, .. } => {
+            classify_set_expr(left, result);
+            classify_set_expr(right, result);
+        }
+        _ => {}
+    }
+}
+
+fn classify(stmt: &Statement) -> Option<DestructiveStatement> {
+    let (kind, description, targets) = match stmt {
+        Statement::Drop {
+            object_type: ObjectType::Database,
+            names,
+            ..
+        } => (
+            DestructiveKind::DropDatabase,
+            "DROP DATABASE".to_string(),
+            names.iter().map(|n| n.to_string()).collect(),
+        ),
+        Statement::Drop {
+            object_type,
+            names,
+            cascade,
+            ..
+        } => (
+            DestructiveKind::Drop,
+            format!(
+                "DROP {}{}",
+                object_type,
+                if *cascade { " ... CASCADE" } else { "" }
+            ),
+            names.iter().map(|n| n.to_string()).collect(),
+        ),
+        Statement::DropFunction { func_desc, .. } => (
+            DestructiveKind::Drop,
+            "DROP FUNCTION".to_string(),
+            func_desc.iter().map(|f| f.name.to_string()).collect(),
+        ),
+        Statement::DropProcedure { proc_desc, .. } => (
+            DestructiveKind::Drop,
+            "DROP PROCEDURE".to_string(),
+            proc_desc.iter().map(|f| f.name.to_string()).collect(),
+        ),
+        Statement::DropTrigger { trigger_name, .. } => (
+            DestructiveKind::Drop,
+            "DROP TRIGGER".to_string(),
+            vec![trigger_name.to_string()],
+        ),
+        Statement::DropPolicy {
+            name, table_name, ..
+        } => (
+            DestructiveKind::Drop,
+            format!("DROP POLICY on {}", table_name),
+            vec![name.to_string()],
+        ),
+        Statement::Truncate { table_names, .. } => (
+            DestructiveKind::Truncate,
+            "TRUNCATE".to_string(),
+            table_names.iter().map(|t| t.name.to_string()).collect(),
+        ),
+        Statement::Delete(delete) if delete.selection.is_none() => {
+            let tables = match &delete.from {
+                FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from,
+            };
+            (
+                DestructiveKind::DeleteWithoutWhere,
+                "DELETE without WHERE".to_string(),
+                tables
+                    .iter()
+                    .filter_map(|t| relation_name(&t.relation))
+                    .collect(),
+            )
+        }
+        Statement::Update {
+            table,
+            selection: None,
+            ..
+        } => (
+            DestructiveKind::UpdateWithoutWhere,
+            "UPDATE without WHERE".to_string(),
+            relation_name(&table.relation).into_iter().collect(),
+        ),
+        // MERGE clauses only narrow the join, so any DELETE or UPDATE action
+        // can reach every row of the target
+        Statement::Merge { table, clauses, .. } => {
+            let kind = if clauses
+                .iter()
+                .any(|c| matches!(c.action, MergeAction::Delete))
+            {
+                DestructiveKind::DeleteWithoutWhere
+            } else if clauses
+                .iter()
+                .any(|c| matches!(c.action, MergeAction::Update { .. }))
+            {
+                DestructiveKind::UpdateWithoutWhere
+            } else {
+                return None;
+            };
+            (
+                kind,
+                "MERGE with DELETE or UPDATE".to_string(),
+                relation_name(table).into_iter().collect(),
+            )
+        }
+        Statement::AlterTable {
+            name, operations, ..
+        } => {
+            let dropped: Vec<String> = operations
+                .iter()
+                .filter_map(|op| match op {
+                    AlterTableOperation::DropColumn { column_name, .. } => {
+                        Some(column_name.to_string())
+                    }
+                    _ => None,
+                })
+                .collect();
+            if dropped.is_empty() {
+                return None;
+            }
+            (
+                DestructiveKind::AlterDropColumn,
+                format!("ALTER TABLE {} DROP COLUMN {}", name, dropped.join(", ")),
+                vec![name.to_string()],
+            )
+        }
+        _ => return None,
+    };
+
+    Some(DestructiveStatement {
+        kind,
+        description,
+        statement: stmt.to_string(),
+        targets,
+        estimated_rows: None,
+    })
+}
+
+fn relation_name(factor: &TableFactor) -> Option<String> {
+    match factor {
+        TableFactor::Table { name, .. } => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Fallback for statements the parser rejects (uncommon Postgres syntax, or
+/// data-modifying CTEs the parser does not support). Any destructive keyword
+/// in a statement flags it, so that protection fails closed; the only
+/// exception is a plain DELETE or UPDATE with a WHERE clause. DO blocks are
+/// always flagged, as their body is a single dollar-quoted token.
+fn classify_unparsed(sql: &str) -> Vec<DestructiveStatement> {
+    let unrecognized = |statement: String| DestructiveStatement {
+        kind: DestructiveKind::Unrecognized,
+        description: "Statement could not be analyzed".to_string(),
+        statement,
+        targets: Vec::new(),
+        estimated_rows: None,
+    };
+    let Ok(tokens) = Tokenizer::new(&PostgreSqlDialect {}, sql).tokenize() else {
+        return vec![unrecognized(sql.trim().to_string())];
+    };
+
+    let mut result = Vec::new();
+    for segment in tokens.split(|t| *t == Token::SemiColon) {
+        let keywords: Vec<Keyword> = segment
+            .iter()
+            .filter_map(|t| match t {
+                Token::Word(w) => Some(w.keyword),
+                _ => None,
+            })
+            .collect();
+        let dml = keywords
+            .iter()
+            .filter(|k| matches!(k, Keyword::DELETE | Keyword::UPDATE))
+            .count();
+        let destructive = keywords.contains(&Keyword::DROP)
+            || keywords.contains(&Keyword::TRUNCATE)
+            || keywords.contains(&Keyword::DETACH)
+            || match keywords.first() {
+                Some(Keyword::DO) => true,
+                Some(Keyword::DELETE) | Some(Keyword::UPDATE) => {
+                    dml > 1 || !keywords.contains(&Keyword::WHERE)
+                }
+                _ => dml > 0,
+            };
+        if destructive {
+            let text: String = segment.iter().map(|t| t.to_string()).collect();
+            result.push(unrecognized(text.trim().to_string()));
+        }
+    }
+    result
+}
+
+/// Attach an estimated number of affected rows to each statement.
+/// Estimates come from the planner (DELETE/UPDATE) or `pg_class.reltuples`.
+pub async fn estimate_impact(client: &Arc<Client>, statements: &mut [DestructiveStatement]) {
+    for stmt in statements.iter_mut() {
+        stmt.estimated_rows = match stmt.kind {
+            DestructiveKind::DeleteWithoutWhere | DestructiveKind::UpdateWithoutWhere => {
+                explain_row_estimate(client, &stmt.statement).await
+            }
+            DestructiveKind::Truncate | DestructiveKind::AlterDropColumn => {
+                relation_row_estimate(client, &stmt.targets).await
+            }
+            DestructiveKind::Drop if stmt.description.starts_with("DROP TABLE") => {
+                relation_row_estimate(client, &stmt.targets).await
+            }
+            DestructiveKind::Drop if stmt.description.starts_with("DROP SCHEMA") => {
+                schema_row_estimate(client, &stmt.targets).await
+            }
+            _ => None,
+        };
+    }
+}
+
+async fn explain_row_estimate(client: &Client, statement: &str) -> Option<i64> {
+    let row = client
+        .query_one(&format!("EXPLAIN (FORMAT JSON) {}", statement), &[])
+        .await
+        .ok()?;
+    let plan: serde_json::Value = row.try_get(0).ok()?;
+    let top = &plan[0]["Plan"];
+    // ModifyTable reports 0 rows itself; the rows it touches come from its input
+    let rows = top["Plans"][0]["Plan Rows"]
+        .as_f64()
+        .or_else(|| top["Plan Rows"].as_f64())?;
+    Some(rows as i64)
+}
+
+async fn relation_row_estimate(client: &Client, targets: &[String]) -> Option<i64> {
+    let row = client
+        .query_one(
+            "SELECT sum(GREATEST(c.reltuples, 0))::bigint
+             FROM unnest($1::text[]) AS t(name)
+             JOIN pg_class c ON c.oid = to_regclass(t.name)",
+            &[&targets],
+        )
+        .await
+        .ok()?;
+    row.get(0)
+}
+
+async fn schema_row_estimate(client: &Client, schemas: &[String]) -> Option<i64> {
+    let row = client
+        .query_one(
+            "SELECT sum(GREATEST(c.reltuples, 0))::bigint
+             FROM unnest($1::text[]) AS s(name)
+             JOIN pg_class c ON c.relnamespace = to_regnamespace(s.name)
+             WHERE c.relkind IN ('r', 'm')",
+            &[&schemas],
+        )
+        .await
+        .ok()?;
+    row.get(0)
+}
+
+struct PendingConfirmation {
+    connection_id: String,
+    sql: String,
+    issued_at: Instant,
+}
+
+/// One-time tokens handed out for destructive statements on protected connections.
+/// A token is bound to the exact SQL text and connection it was issued for.
+pub struct PendingConfirmations {
+    pending: Mutex<HashMap<String, PendingConfirmation>>,
+}
+
+impl PendingConfirmations {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn issue(
+        &self,
+        connection_id: &str,
+        sql: &str,
+        statements: Vec<DestructiveStatement>,
+    ) -> ConfirmationRequired {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut pending = self.pending.lock().await;
+        pending.retain(|_, p| p.issued_at.elapsed() < TOKEN_TTL);
+        pending.insert(
+            token.clone(),
+            PendingConfirmation {
+                connection_id: connection_id.to_string(),
+                sql: sql.to_string(),
+                issued_at: Instant::now(),
+            },
+        );
+        ConfirmationRequired {
+            token,
+            statements,
+            expires_in_secs: TOKEN_TTL.as_secs(),
+        }
+    }
+
+    /// Consume a token. Returns true only if it matches the connection and SQL
+    /// and has not expired; the token is invalidated either way.
+    pub async fn redeem(&self, token: &str, connection_id: &str, sql: &str) -> bool {
+        let mut pending = self.pending.lock().await;
+        match pending.remove(token) {
+            Some(p) => {
+                p.connection_id == connection_id
+                    && p.sql == sql
+                    && p.issued_at.elapsed() < TOKEN_TTL
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<DestructiveKind> {
+        classify_statements(sql)
+            .into_iter()
+            .map(|s| s.kind)
+            .collect()
+    }
+
+    #[test]
+    fn plain_statements() {
+        assert_eq!(kinds("SELECT * FROM orders"), vec![]);
+        assert_eq!(kinds("DELETE FROM orders WHERE id = 1"), vec![]);
+        assert_eq!(
+            kinds("DELETE FROM orders"),
+            vec![DestructiveKind::DeleteWithoutWhere]
+        );
+        assert_eq!(
+            kinds("UPDATE orders SET total = 0"),
+            vec![DestructiveKind::UpdateWithoutWhere]
+        );
+        assert_eq!(
+            kinds("SELECT 1; DROP TABLE orders; TRUNCATE items"),
+            vec![DestructiveKind::Drop, DestructiveKind::Truncate]
+        );
+        assert_eq!(
+            kinds("ALTER TABLE orders DROP COLUMN total"),
+            vec![DestructiveKind::AlterDropColumn]
+        );
+        assert_eq!(
+            kinds("DROP DATABASE shop"),
+            vec![DestructiveKind::DropDatabase]
+        );
+        assert_eq!(
+            kinds("MERGE INTO orders USING staged ON true WHEN MATCHED THEN DELETE"),
+            vec![DestructiveKind::DeleteWithoutWhere]
+        );
+        assert_eq!(
+            kinds("MERGE INTO orders o USING staged s ON o.id = s.id WHEN MATCHED THEN UPDATE SET total = s.total"),
+            vec![DestructiveKind::UpdateWithoutWhere]
+        );
+        assert_eq!(
+            kinds("MERGE INTO orders o USING staged s ON o.id = s.id WHEN NOT MATCHED THEN INSERT (id) VALUES (s.id)"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn explain_analyze_runs_the_statement() {
+        assert_eq!(kinds("EXPLAIN DELETE FROM orders"), vec![]);
+        assert_eq!(
+            kinds("EXPLAIN ANALYZE DELETE FROM orders"),
+            vec![DestructiveKind::DeleteWithoutWhere]
+        );
+        assert_eq!(
+            kinds("EXPLAIN (ANALYZE, BUFFERS) UPDATE orders SET total = 0"),
+            vec![DestructiveKind::UpdateWithoutWhere]
+        );
+        assert_eq!(kinds("EXPLAIN (ANALYZE false) DELETE FROM orders"), vec![]);
+    }
+
+    #[test]
+    fn data_modifying_ctes() {
+        assert_eq!(
+            kinds("WITH u AS (UPDATE orders SET total = 0 RETURNING *) SELECT * FROM u"),
+            vec![DestructiveKind::UpdateWithoutWhere]
+        );
+        assert_eq!(
+            kinds(
+                "WITH u AS (UPDATE orders SET total = 0 WHERE id = 1 RETURNING *) SELECT * FROM u"
+            ),
+            vec![]
+        );
+        // Not supported by the parser, so it takes the keyword fallback
+        assert_eq!(
+            kinds("WITH d AS (DELETE FROM orders WHERE id = 1 RETURNING *) SELECT * FROM d"),
+            vec![DestructiveKind::Unrecognized]
+        );
+    }
+
+    #[test]
+    fn unparsed_statements_fail_closed() {
+        assert_eq!(
+            kinds("DROP TABLE orders CASCADE CONSTRAINTS PURGE"),
+            vec![DestructiveKind::Unrecognized]
+        );
+        assert_eq!(
+            kinds("SELECT 'unterminated"),
+            vec![DestructiveKind::Unrecognized]
+        );
+        assert_eq!(
+            kinds("ALTER TABLE m DETACH PARTITION m_2024 CONCURRENTLY"),
+            vec![DestructiveKind::Unrecognized]
+        );
+        assert_eq!(
+            kinds("DO $$ BEGIN DROP TABLE orders; END $$"),
+            vec![DestructiveKind::Unrecognized]
+        );
+        assert_eq!(
+            kinds("INSERT INTO orders (id) VALUES (1) ON CONFLICT DO NOTHING"),
+            vec![]
+        );
+        assert_eq!(kinds("VACUUM (VERBOSE, PARALLEL 2) orders"), vec![]);
+    }
+}
@@ -16,6 +16,14 @@ pub struct TableInfo {
     pub table_type: String, // "BASE TABLE" or "VIEW"
     pub row_estimate: i64,
     pub size: String,
+    /// Partition strategy and key, e.g. "RANGE (created_at)", for partitioned tables
+    pub partition_key: Option<String>,
+    /// `schema.table` this table is a partition of
+    pub partition_of: Option<String>,
+    pub partition_bound: Option<String>,
+    /// Partitions in the same schema, nested under their parent instead of
+    /// being listed on their own
+    pub partitions: Vec<TableInfo>,
 }
 
 #[derive(Debug, Serialize)]
@@ -29,6 +37,10 @@ pub struct ColumnInfo {
     pub foreign_table: Option<String>,
     pub foreign_column: Option<String>,
     pub ordinal_position: i32,
+    /// Underlying type name, e.g. "int4" or the name of an enum or domain
+    pub udt_name: String,
+    /// Allowed labels when the column's type is an enum
+    pub enum_values: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -77,25 +89,43 @@ pub async fn get_schemas(client: &Arc<Client>) -> Result<Vec<SchemaInfo>> {
         .collect())
 }
 
-pub async fn get_tables(client: &Arc<Client>, schema: &str) -> Result<Vec<TableInfo>> {
+/// Tables and views of `schema`. With `nested`, partitions are listed under
+/// their parent instead of alongside it.
+pub async fn get_tables(client: &Arc<Client>, schema: &str, nested: bool) -> Result<Vec<TableInfo>> {
+    // Partitioned tables report the size and rows of all their partitions
     let rows = client
         .query(
             "SELECT
                 t.table_schema,
                 t.table_name,
                 t.table_type,
-                COALESCE(c.reltuples::bigint, 0) as row_estimate,
-                COALESCE(pg_size_pretty(pg_total_relation_size(quote_ident(t.table_schema) || '.' || quote_ident(t.table_name))), '0 bytes') as size
+                CASE WHEN c.relkind = 'p'
+                    THEN (SELECT COALESCE(sum(GREATEST(pc.reltuples, 0)), 0)
+                          FROM pg_partition_tree(c.oid) pt JOIN pg_class pc ON pc.oid = pt.relid
+                          WHERE pt.isleaf)::bigint
+                    ELSE COALESCE(c.reltuples::bigint, 0)
+                END as row_estimate,
+                COALESCE(pg_size_pretty(CASE WHEN c.relkind = 'p'
+                    THEN (SELECT sum(pg_total_relation_size(pt.relid)) FROM pg_partition_tree(c.oid) pt)::bigint
+                    ELSE pg_total_relation_size(c.oid)
+                END), '0 bytes') as size,
+                CASE WHEN c.relkind = 'p' THEN pg_get_partkeydef(c.oid) END as partition_key,
+                (SELECT pn.nspname || '.' || p.relname
+                 FROM pg_inherits i
+                 JOIN pg_class p ON p.oid = i.inhparent
+                 JOIN pg_namespace pn ON pn.oid = p.relnamespace
+                 WHERE i.inhrelid = c.oid AND c.relispartition) as partition_of,
+                CASE WHEN c.relispartition THEN pg_get_expr(c.relpartbound, c.oid) END as partition_bound
              FROM information_schema.tables t
-             LEFT JOIN pg_class c ON c.relname = t.table_name
-             LEFT JOIN pg_namespace n ON n.oid = c.relnamespace AND n.nspname = t.table_schema
+             LEFT JOIN pg_namespace n ON n.nspname = t.table_schema
+             LEFT JOIN pg_class c ON c.relname = t.table_name AND c.relnamespace = n.oid
              WHERE t.table_schema = $1
              ORDER BY t.table_name",
             &[&schema],
         )
         .await?;
 
-    Ok(rows
+    let tables: Vec<TableInfo> = rows
         .iter()
         .map(|row| TableInfo {
             schema: row.get(0),
@@ -103,8 +133,40 @@ pub async fn get_tables(client: &Arc<Client>, schema: &str) -> Result<Vec<TableI
             table_type: row.get(2),
             row_estimate: row.get(3),
             size: row.get(4),
+            partition_key: row.get(5),
+            partition_of: row.get(6),
+            partition_bound: row.get(7),
+            partitions: Vec::new(),
         })
-        .collect())
+        .collect();
+    if !nested {
+        return Ok(tables);
+    }
+
+    // Partitions whose parent is listed here move under it
+    let keys: Vec<String> = tables
+        .iter()
+        .map(|t| format!("{}.{}", t.schema, t.name))
+        .collect();
+    let (mut partitions, mut top): (Vec<TableInfo>, Vec<TableInfo>) = tables
+        .into_iter()
+        .partition(|t| t.partition_of.as_ref().is_some_and(|p| keys.contains(p)));
+    for table in &mut top {
+        nest_partitions(table, &mut partitions);
+    }
+    Ok(top)
+}
+
+fn nest_partitions(table: &mut TableInfo, partitions: &mut Vec<TableInfo>) {
+    let key = format!("{}.{}", table.schema, table.name);
+    let (mut own, rest): (Vec<TableInfo>, Vec<TableInfo>) = std::mem::take(partitions)
+        .into_iter()
+        .partition(|t| t.partition_of.as_deref() == Some(key.as_str()));
+    *partitions = rest;
+    for partition in &mut own {
+        nest_partitions(partition, partitions);
+    }
+    table.partitions = own;
 }
 
 pub async fn get_columns(
@@ -123,7 +185,13 @@ pub async fn get_columns(
                 COALESCE(fk.is_fk, false) as is_foreign_key,
                 fk.foreign_table,
                 fk.foreign_column,
-                c.ordinal_position::int
+                c.ordinal_position::int,
+                c.udt_name::text,
+                (SELECT array_agg(e.enumlabel::text ORDER BY e.enumsortorder)
+                 FROM pg_enum e
+                 JOIN pg_type et ON et.oid = e.enumtypid
+                 JOIN pg_namespace en ON en.oid = et.typnamespace
+                 WHERE en.nspname = c.udt_schema AND et.typname = c.udt_name) as enum_values
              FROM information_schema.columns c
              LEFT JOIN (
                 SELECT kcu.column_name, true as is_pk
@@ -169,6 +237,8 @@ pub async fn get_columns(
             foreign_table: row.get(6),
             foreign_column: row.get(7),
             ordinal_position: row.get(8),
+            udt_name: row.get(9),
+            enum_values: row.get(10),
         })
         .collect())
 }
@@ -478,3 +548,416 @@ pub async fn get_policies(
         })
         .collect())
 }
+
+// ── Functions ──
+
+#[derive(Debug, Serialize)]
+pub struct FunctionInfo {
+    pub oid: u32,
+    pub schema: String,
+    pub name: String,
+    pub kind: String, // "function", "procedure", "aggregate" or "window"
+    pub arguments: String,
+    /// Argument types only, as needed to name an overload in DROP/ALTER
+    pub identity_arguments: String,
+    pub return_type: Option<String>,
+    pub language: String,
+    pub volatility: String,
+    pub security_definer: bool,
+}
+
+pub async fn get_functions(client: &Arc<Client>, schema: &str) -> Result<Vec<FunctionInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                p.oid,
+                n.nspname,
+                p.proname,
+                CASE p.prokind
+                    WHEN 'p' THEN 'procedure'
+                    WHEN 'a' THEN 'aggregate'
+                    WHEN 'w' THEN 'window'
+                    ELSE 'function'
+                END as kind,
+                pg_get_function_arguments(p.oid) as arguments,
+                pg_get_function_identity_arguments(p.oid) as identity_arguments,
+                pg_get_function_result(p.oid) as return_type,
+                l.lanname as language,
+                CASE p.provolatile
+                    WHEN 'i' THEN 'immutable'
+                    WHEN 's' THEN 'stable'
+                    ELSE 'volatile'
+                END as volatility,
+                p.prosecdef as security_definer
+             FROM pg_proc p
+             JOIN pg_namespace n ON n.oid = p.pronamespace
+             JOIN pg_language l ON l.oid = p.prolang
+             WHERE n.nspname = $1
+             ORDER BY p.proname, identity_arguments",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| FunctionInfo {
+            oid: row.get(0),
+            schema: row.get(1),
+            name: row.get(2),
+            kind: row.get(3),
+            arguments: row.get(4),
+            identity_arguments: row.get(5),
+            return_type: row.get(6),
+            language: row.get(7),
+            volatility: row.get(8),
+            security_definer: row.get(9),
+        })
+        .collect())
+}
+
+/// CREATE statement for a function or procedure. `pg_get_functiondef` does not
+/// handle aggregates, so their definition is put together from `pg_aggregate`.
+pub async fn get_function_definition(client: &Arc<Client>, oid: u32) -> Result<String> {
+    let row = client
+        .query_opt("SELECT prokind = 'a' FROM pg_proc WHERE oid = $1", &[&oid])
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Function not found"))?;
+    let is_aggregate: bool = row.get(0);
+    if !is_aggregate {
+        let row = client
+            .query_one("SELECT pg_get_functiondef($1)", &[&oid])
+            .await?;
+        return Ok(row.get(0));
+    }
+
+    let row = client
+        .query_one(
+            "SELECT
+                quote_ident(n.nspname) || '.' || quote_ident(p.proname),
+                pg_get_function_arguments(p.oid),
+                (SELECT quote_ident(fn.nspname) || '.' || quote_ident(f.proname)
+                 FROM pg_proc f JOIN pg_namespace fn ON fn.oid = f.pronamespace
+                 WHERE f.oid = a.aggtransfn),
+                format_type(a.aggtranstype, NULL),
+                (SELECT quote_ident(fn.nspname) || '.' || quote_ident(f.proname)
+                 FROM pg_proc f JOIN pg_namespace fn ON fn.oid = f.pronamespace
+                 WHERE f.oid = a.aggfinalfn),
+                (SELECT quote_ident(fn.nspname) || '.' || quote_ident(f.proname)
+                 FROM pg_proc f JOIN pg_namespace fn ON fn.oid = f.pronamespace
+                 WHERE f.oid = a.aggcombinefn),
+                a.agginitval,
+                NULLIF(a.aggsortop, 0)::regoperator::text
+             FROM pg_proc p
+             JOIN pg_namespace n ON n.oid = p.pronamespace
+             JOIN pg_aggregate a ON a.aggfnoid = p.oid
+             WHERE p.oid = $1",
+            &[&oid],
+        )
+        .await?;
+    let name: String = row.get(0);
+    let arguments: String = row.get(1);
+    let mut options = vec![
+        format!("SFUNC = {}", row.get::<_, String>(2)),
+        format!("STYPE = {}", row.get::<_, String>(3)),
+    ];
+    if let Some(finalfunc) = row.get::<_, Option<String>>(4) {
+        options.push(format!("FINALFUNC = {}", finalfunc));
+    }
+    if let Some(combinefunc) = row.get::<_, Option<String>>(5) {
+        options.push(format!("COMBINEFUNC = {}", combinefunc));
+    }
+    if let Some(initcond) = row.get::<_, Option<String>>(6) {
+        options.push(format!("INITCOND = {}", super::quote_literal(&initcond)));
+    }
+    if let Some(sortop) = row.get::<_, Option<String>>(7) {
+        // regoperator prints as name(left,right); SORTOP wants just the name
+        let op = sortop.split('(').next().unwrap_or(&sortop);
+        options.push(format!("SORTOP = {}", op));
+    }
+    Ok(format!(
+        "CREATE AGGREGATE {}({}) (\n    {}\n);",
+        name,
+        if arguments.is_empty() { "*" } else { &arguments },
+        options.join(",\n    ")
+    ))
+}
+
+// ── Views ──
+
+#[derive(Debug, Serialize)]
+pub struct ViewInfo {
+    pub schema: String,
+    pub name: String,
+    pub is_materialized: bool,
+    pub definition: String,
+    /// Always true for plain views; false for a matview created WITH NO DATA
+    pub is_populated: bool,
+    pub size: String,
+    pub row_estimate: i64,
+    pub owner: String,
+    /// Tables and views this view reads from
+    pub dependencies: Vec<String>,
+    /// Views that read from this view
+    pub dependents: Vec<String>,
+    /// Indexes, for materialized views
+    pub indexes: Vec<IndexInfo>,
+    /// When pgstudio last refreshed the matview; Postgres does not track this
+    pub last_refresh: Option<String>,
+}
+
+pub async fn get_views(client: &Arc<Client>, schema: &str) -> Result<Vec<ViewInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                c.relkind = 'm' as is_materialized,
+                pg_get_viewdef(c.oid, true) as definition,
+                c.relkind <> 'm' OR c.relispopulated as is_populated,
+                pg_size_pretty(pg_total_relation_size(c.oid)) as size,
+                GREATEST(c.reltuples::bigint, 0) as row_estimate,
+                pg_get_userbyid(c.relowner) as owner,
+                ARRAY(
+                    SELECT DISTINCT quote_ident(rn.nspname) || '.' || quote_ident(rc.relname)
+                    FROM pg_rewrite r
+                    JOIN pg_depend d ON d.classid = 'pg_rewrite'::regclass AND d.objid = r.oid
+                        AND d.refclassid = 'pg_class'::regclass
+                    JOIN pg_class rc ON rc.oid = d.refobjid
+                    JOIN pg_namespace rn ON rn.oid = rc.relnamespace
+                    WHERE r.ev_class = c.oid AND d.refobjid <> c.oid
+                    ORDER BY 1
+                ) as dependencies,
+                ARRAY(
+                    SELECT DISTINCT quote_ident(vn.nspname) || '.' || quote_ident(vc.relname)
+                    FROM pg_depend d
+                    JOIN pg_rewrite r ON d.classid = 'pg_rewrite'::regclass AND d.objid = r.oid
+                    JOIN pg_class vc ON vc.oid = r.ev_class
+                    JOIN pg_namespace vn ON vn.oid = vc.relnamespace
+                    WHERE d.refclassid = 'pg_class'::regclass AND d.refobjid = c.oid
+                      AND r.ev_class <> c.oid
+                    ORDER BY 1
+                ) as dependents
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relkind IN ('v', 'm')
+             ORDER BY c.relname",
+            &[&schema],
+        )
+        .await?;
+
+    let mut views = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let name: String = row.get(1);
+        let is_materialized: bool = row.get(2);
+        let indexes = if is_materialized {
+            get_indexes(client, schema, &name).await?
+        } else {
+            Vec::new()
+        };
+        views.push(ViewInfo {
+            schema: row.get(0),
+            name,
+            is_materialized,
+            definition: row.get(3),
+            is_populated: row.get(4),
+            size: row.get(5),
+            row_estimate: row.get(6),
+            owner: row.get(7),
+            dependencies: row.get(8),
+            dependents: row.get(9),
+            indexes,
+            last_refresh: None,
+        });
+    }
+    Ok(views)
+}
+
+// ── Sequences ──
+
+#[derive(Debug, Serialize)]
+pub struct SequenceInfo {
+    pub schema: String,
+    pub name: String,
+    pub data_type: String,
+    pub start_value: i64,
+    pub min_value: i64,
+    pub max_value: i64,
+    pub increment: i64,
+    pub cycle: bool,
+    pub cache_size: i64,
+    /// None until nextval has been called (or without privileges to read it)
+    pub last_value: Option<i64>,
+    /// "table.column" of an owning serial/identity column
+    pub owned_by: Option<String>,
+    /// How many more values can be handed out before the sequence is exhausted
+    pub remaining: i64,
+    pub percent_used: f64,
+}
+
+pub async fn get_sequences(client: &Arc<Client>, schema: &str) -> Result<Vec<SequenceInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                s.schemaname::text,
+                s.sequencename::text,
+                format_type(s.data_type, NULL) as data_type,
+                s.start_value,
+                s.min_value,
+                s.max_value,
+                s.increment_by,
+                s.cycle,
+                s.cache_size,
+                s.last_value,
+                (SELECT quote_ident(tc.relname) || '.' || quote_ident(a.attname)
+                 FROM pg_depend d
+                 JOIN pg_class tc ON tc.oid = d.refobjid
+                 JOIN pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+                 WHERE d.classid = 'pg_class'::regclass
+                   AND d.objid = (quote_ident(s.schemaname) || '.' || quote_ident(s.sequencename))::regclass
+                   AND d.refclassid = 'pg_class'::regclass
+                   AND d.deptype IN ('a', 'i')
+                 LIMIT 1) as owned_by
+             FROM pg_sequences s
+             WHERE s.schemaname = $1
+             ORDER BY s.sequencename",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let start_value: i64 = row.get(3);
+            let min_value: i64 = row.get(4);
+            let max_value: i64 = row.get(5);
+            let increment: i64 = row.get(6);
+            let last_value: Option<i64> = row.get(9);
+
+            // Work in i128: the distances can exceed i64 for bigint sequences
+            let (lo, hi, step) = (min_value as i128, max_value as i128, increment as i128);
+            let next = match last_value {
+                Some(last) => last as i128 + step,
+                None => start_value as i128,
+            };
+            let (left, span) = if step > 0 {
+                (hi - next, hi - lo)
+            } else {
+                (next - lo, hi - lo)
+            };
+            let remaining = if left < 0 { 0 } else { left / step.abs() + 1 };
+            let percent_used = if span > 0 {
+                (100.0 - (left.max(0) as f64 / span as f64) * 100.0).clamp(0.0, 100.0)
+            } else {
+                100.0
+            };
+
+            SequenceInfo {
+                schema: row.get(0),
+                name: row.get(1),
+                data_type: row.get(2),
+                start_value,
+                min_value,
+                max_value,
+                increment,
+                cycle: row.get(7),
+                cache_size: row.get(8),
+                last_value,
+                owned_by: row.get(10),
+                remaining: remaining.min(i64::MAX as i128) as i64,
+                percent_used,
+            }
+        })
+        .collect())
+}
+
+// ── Types ──
+
+#[derive(Debug, Serialize)]
+pub struct TypeAttribute {
+    pub name: String,
+    pub data_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeInfo {
+    pub schema: String,
+    pub name: String,
+    pub kind: String, // "enum", "domain", "composite" or "range"
+    pub description: Option<String>,
+    /// Enum labels in sort order
+    pub enum_labels: Vec<String>,
+    /// Domain base type
+    pub base_type: Option<String>,
+    pub not_null: bool,
+    pub default: Option<String>,
+    /// Domain CHECK constraints
+    pub check_constraints: Vec<String>,
+    /// Composite type attributes
+    pub attributes: Vec<TypeAttribute>,
+    /// Range subtype
+    pub subtype: Option<String>,
+}
+
+pub async fn get_types(client: &Arc<Client>, schema: &str) -> Result<Vec<TypeInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                t.typname::text,
+                CASE t.typtype
+                    WHEN 'e' THEN 'enum'
+                    WHEN 'd' THEN 'domain'
+                    WHEN 'c' THEN 'composite'
+                    ELSE 'range'
+                END as kind,
+                obj_description(t.oid, 'pg_type') as description,
+                ARRAY(SELECT e.enumlabel::text FROM pg_enum e
+                      WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder) as enum_labels,
+                CASE WHEN t.typtype = 'd' THEN format_type(t.typbasetype, t.typtypmod) END as base_type,
+                t.typnotnull,
+                t.typdefault,
+                ARRAY(SELECT pg_get_constraintdef(con.oid) FROM pg_constraint con
+                      WHERE con.contypid = t.oid ORDER BY con.conname) as check_constraints,
+                ARRAY(SELECT a.attname::text FROM pg_attribute a
+                      WHERE a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped
+                      ORDER BY a.attnum) as attribute_names,
+                ARRAY(SELECT format_type(a.atttypid, a.atttypmod) FROM pg_attribute a
+                      WHERE a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped
+                      ORDER BY a.attnum) as attribute_types,
+                (SELECT format_type(r.rngsubtype, NULL) FROM pg_range r
+                 WHERE r.rngtypid = t.oid) as subtype
+             FROM pg_type t
+             JOIN pg_namespace n ON n.oid = t.typnamespace
+             LEFT JOIN pg_class c ON c.oid = t.typrelid
+             WHERE n.nspname = $1
+               AND (t.typtype IN ('e', 'd', 'r') OR (t.typtype = 'c' AND c.relkind = 'c'))
+             ORDER BY t.typname",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let names: Vec<String> = row.get(9);
+            let types: Vec<String> = row.get(10);
+            TypeInfo {
+                schema: row.get(0),
+                name: row.get(1),
+                kind: row.get(2),
+                description: row.get(3),
+                enum_labels: row.get(4),
+                base_type: row.get(5),
+                not_null: row.get(6),
+                default: row.get(7),
+                check_constraints: row.get(8),
+                attributes: names
+                    .into_iter()
+                    .zip(types)
+                    .map(|(name, data_type)| TypeAttribute { name, data_type })
+                    .collect(),
+                subtype: row.get(11),
+            }
+        })
+        .collect())
+}
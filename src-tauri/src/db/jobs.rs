@@ -0,0 +1,206 @@
+use super::{execute_query_streaming, QueryLimits, QueryResult};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_postgres::{CancelToken, Client, NoTls};
+
+/// How long finished jobs (and their results) are kept by default
+const DEFAULT_RETENTION: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub connection_id: String,
+    pub session_id: Option<String>,
+    pub sql: String,
+    pub status: JobStatus,
+    pub started_at: String,
+    pub elapsed_ms: u128,
+    pub rows_received: usize,
+    pub error: Option<String>,
+}
+
+/// Called with a fresh snapshot whenever a job starts, makes progress or finishes
+pub type JobListener = Arc<dyn Fn(&JobInfo) + Send + Sync>;
+
+struct Job {
+    info: JobInfo,
+    started: Instant,
+    finished: Option<Instant>,
+    cancel_token: CancelToken,
+    result: Option<QueryResult>,
+}
+
+impl Job {
+    fn snapshot(&self) -> JobInfo {
+        let end = self.finished.unwrap_or_else(Instant::now);
+        JobInfo {
+            elapsed_ms: end.duration_since(self.started).as_millis(),
+            ..self.info.clone()
+        }
+    }
+}
+
+/// Runs queries in the background so long statements don't hold up the
+/// command that started them, and keeps finished results for a while.
+pub struct JobManager {
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+    retention: Mutex<Duration>,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            retention: Mutex::new(DEFAULT_RETENTION),
+        }
+    }
+
+    pub fn set_retention(&self, retention: Duration) {
+        *self.retention.lock().unwrap() = retention;
+        self.prune();
+    }
+
+    /// Start running `sql` on `client` and return the new job id right away.
+    pub fn submit(
+        &self,
+        client: Arc<Client>,
+        connection_id: &str,
+        session_id: Option<&str>,
+        sql: &str,
+        limits: QueryLimits,
+        listener: JobListener,
+    ) -> String {
+        self.prune();
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let job = Job {
+            info: JobInfo {
+                id: id.clone(),
+                connection_id: connection_id.to_string(),
+                session_id: session_id.map(str::to_string),
+                sql: sql.to_string(),
+                status: JobStatus::Running,
+                started_at: chrono::Utc::now().to_rfc3339(),
+                elapsed_ms: 0,
+                rows_received: 0,
+                error: None,
+            },
+            started: Instant::now(),
+            finished: None,
+            cancel_token: client.cancel_token(),
+            result: None,
+        };
+        listener(&job.snapshot());
+        self.jobs.lock().unwrap().insert(id.clone(), job);
+
+        let jobs = self.jobs.clone();
+        let job_id = id.clone();
+        let sql = sql.to_string();
+        tokio::spawn(async move {
+            let progress_jobs = jobs.clone();
+            let progress_listener = listener.clone();
+            let progress_id = job_id.clone();
+            let outcome = execute_query_streaming(&client, &sql, limits, move |rows| {
+                let snapshot = {
+                    let mut jobs = progress_jobs.lock().unwrap();
+                    let Some(job) = jobs.get_mut(&progress_id) else {
+                        return;
+                    };
+                    job.info.rows_received = rows;
+                    job.snapshot()
+                };
+                progress_listener(&snapshot);
+            })
+            .await;
+
+            let snapshot = {
+                let mut jobs = jobs.lock().unwrap();
+                let Some(job) = jobs.get_mut(&job_id) else {
+                    return;
+                };
+                job.finished = Some(Instant::now());
+                match outcome {
+                    Ok(result) => {
+                        job.info.status = JobStatus::Completed;
+                        job.info.rows_received = result.row_count;
+                        job.result = Some(result);
+                    }
+                    Err(e) => {
+                        // A cancelled job fails with the server's cancel error; keep it marked cancelled
+                        if job.info.status != JobStatus::Cancelled {
+                            job.info.status = JobStatus::Failed;
+                        }
+                        job.info.error = Some(e.to_string());
+                    }
+                }
+                job.snapshot()
+            };
+            listener(&snapshot);
+        });
+
+        id
+    }
+
+    pub fn list(&self, connection_id: Option<&str>) -> Vec<JobInfo> {
+        self.prune();
+        let jobs = self.jobs.lock().unwrap();
+        let mut list: Vec<JobInfo> = jobs
+            .values()
+            .filter(|job| connection_id.is_none_or(|id| job.info.connection_id == id))
+            .map(Job::snapshot)
+            .collect();
+        list.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        list
+    }
+
+    pub fn result(&self, job_id: &str) -> Result<QueryResult> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs
+            .get(job_id)
+            .ok_or_else(|| anyhow::anyhow!("No job with id: {}", job_id))?;
+        match (&job.result, job.info.status) {
+            (Some(result), _) => Ok(result.clone()),
+            (None, JobStatus::Running) => anyhow::bail!("Job is still running"),
+            (None, _) => anyhow::bail!(job
+                .info
+                .error
+                .clone()
+                .unwrap_or_else(|| "Job produced no result".into())),
+        }
+    }
+
+    /// Ask the server to cancel the statement the job is running.
+    pub async fn cancel(&self, job_id: &str) -> Result<()> {
+        let token = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = jobs
+                .get_mut(job_id)
+                .ok_or_else(|| anyhow::anyhow!("No job with id: {}", job_id))?;
+            if job.info.status != JobStatus::Running {
+                anyhow::bail!("Job is not running");
+            }
+            job.info.status = JobStatus::Cancelled;
+            job.cancel_token.clone()
+        };
+        token.cancel_query(NoTls).await?;
+        Ok(())
+    }
+
+    fn prune(&self) {
+        let retention = *self.retention.lock().unwrap();
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| job.finished.is_none_or(|at| at.elapsed() < retention));
+    }
+}
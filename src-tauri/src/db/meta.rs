@@ -0,0 +1,562 @@
+use super::{
+    execute_query_params, get_constraints, get_indexes, get_triggers, ColumnDef,
+    ConnectionConfig, QueryLimits, QueryResult,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio_postgres::Client;
+
+/// A psql backslash command such as `\d+ users`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaCommand {
+    pub name: String,
+    /// The command was given with a trailing `+`
+    pub verbose: bool,
+    pub args: Vec<String>,
+}
+
+/// Parse a single-line psql meta-command. Returns None for anything else.
+pub fn parse_meta_command(input: &str) -> Option<MetaCommand> {
+    let line = input.trim();
+    if !line.starts_with('\\') || line.contains('\n') {
+        return None;
+    }
+    let mut words = split_args(&line[1..]).into_iter();
+    let word = words.next()?;
+    let (name, verbose) = match word.strip_suffix('+') {
+        Some(name) => (name.to_string(), true),
+        None => (word, false),
+    };
+    Some(MetaCommand {
+        name,
+        verbose,
+        args: words.collect(),
+    })
+}
+
+/// Split on whitespace, keeping double-quoted sections (quotes included) together
+pub(crate) fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+/// Display toggles set with `\x` and `\timing`
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MetaSettings {
+    pub expanded: bool,
+    pub timing: bool,
+}
+
+/// Per-connection display settings changed by meta-commands
+pub struct MetaState {
+    settings: RwLock<HashMap<String, MetaSettings>>,
+}
+
+impl MetaState {
+    pub fn new() -> Self {
+        Self {
+            settings: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, connection_id: &str) -> MetaSettings {
+        let settings = self.settings.read().await;
+        settings.get(connection_id).copied().unwrap_or_default()
+    }
+
+    async fn update<F: FnOnce(&mut MetaSettings)>(&self, connection_id: &str, f: F) -> MetaSettings {
+        let mut settings = self.settings.write().await;
+        let entry = settings.entry(connection_id.to_string()).or_default();
+        f(entry);
+        *entry
+    }
+}
+
+const HELP: &[(&str, &str)] = &[
+    ("\\d[+] [NAME]", "list tables, views and sequences, or describe a relation"),
+    ("\\dt[+] [PATTERN]", "list tables"),
+    ("\\dv[+] [PATTERN]", "list views"),
+    ("\\dm[+] [PATTERN]", "list materialized views"),
+    ("\\di[+] [PATTERN]", "list indexes"),
+    ("\\ds[+] [PATTERN]", "list sequences"),
+    ("\\df[+] [PATTERN]", "list functions"),
+    ("\\dn[+] [PATTERN]", "list schemas"),
+    ("\\du [PATTERN]", "list roles"),
+    ("\\l[+] [PATTERN]", "list databases"),
+    ("\\x [on|off]", "toggle expanded output"),
+    ("\\timing [on|off]", "toggle timing of commands"),
+    ("\\conninfo", "display information about the current connection"),
+];
+
+/// Answer a meta-command with a result that renders like any other query result.
+pub async fn run_meta_command(
+    client: &Arc<Client>,
+    connection_id: &str,
+    config: &ConnectionConfig,
+    state: &MetaState,
+    command: &MetaCommand,
+) -> Result<QueryResult> {
+    if let Some(result) = run_catalog_command(client, command).await? {
+        return Ok(result);
+    }
+    match command.name.as_str() {
+        "x" => {
+            let on = toggle_value(command.args.first())?;
+            let settings = state
+                .update(connection_id, |s| s.expanded = on.unwrap_or(!s.expanded))
+                .await;
+            Ok(message_result(format!(
+                "Expanded display is {}.",
+                if settings.expanded { "on" } else { "off" }
+            )))
+        }
+        "timing" => {
+            let on = toggle_value(command.args.first())?;
+            let settings = state
+                .update(connection_id, |s| s.timing = on.unwrap_or(!s.timing))
+                .await;
+            Ok(message_result(format!(
+                "Timing is {}.",
+                if settings.timing { "on" } else { "off" }
+            )))
+        }
+        "conninfo" => Ok(message_result(format!(
+            "You are connected to database \"{}\" as user \"{}\" on host \"{}\" at port \"{}\".",
+            config.database, config.user, config.host, config.port
+        ))),
+        "?" => Ok(text_result(
+            &["Command", "Description"],
+            HELP.iter()
+                .map(|(c, d)| vec![Some(c.to_string()), Some(d.to_string())])
+                .collect(),
+            "Supported meta-commands".into(),
+        )),
+        other => anyhow::bail!("Unsupported meta-command: \\{}", other),
+    }
+}
+
+/// The meta-commands that only read the catalog: `\d` and its variants, `\l`.
+/// Returns None for anything else.
+pub async fn run_catalog_command(
+    client: &Arc<Client>,
+    command: &MetaCommand,
+) -> Result<Option<QueryResult>> {
+    let pattern = command.args.first().map(String::as_str);
+    let verbose = command.verbose;
+    let result = match command.name.as_str() {
+        "d" => match pattern {
+            Some(name) if !name.contains(['*', '?']) => describe_relation(client, name).await,
+            _ => list_relations(client, "rpvmSf", pattern, verbose).await,
+        },
+        "dt" => list_relations(client, "rp", pattern, verbose).await,
+        "dv" => list_relations(client, "v", pattern, verbose).await,
+        "dm" => list_relations(client, "m", pattern, verbose).await,
+        "di" => list_relations(client, "iI", pattern, verbose).await,
+        "ds" => list_relations(client, "S", pattern, verbose).await,
+        "df" => list_functions(client, pattern).await,
+        "dn" => list_schemas(client, pattern, verbose).await,
+        "du" | "dg" => list_roles(client, pattern).await,
+        "l" | "list" => list_databases(client, pattern, verbose).await,
+        _ => return Ok(None),
+    };
+    result.map(Some)
+}
+
+fn toggle_value(arg: Option<&String>) -> Result<Option<bool>> {
+    match arg.map(|a| a.to_ascii_lowercase()).as_deref() {
+        None => Ok(None),
+        Some("on") | Some("true") | Some("1") => Ok(Some(true)),
+        Some("off") | Some("false") | Some("0") => Ok(Some(false)),
+        Some(other) => anyhow::bail!("Unrecognized value \"{}\"; expected on or off", other),
+    }
+}
+
+fn computed_column(name: &str) -> ColumnDef {
+    ColumnDef {
+        name: name.to_string(),
+        data_type: "text".into(),
+        table_oid: None,
+        column_id: None,
+        source_schema: None,
+        source_table: None,
+        source_column: None,
+        is_nullable: None,
+        is_primary_key: false,
+        source_key_complete: false,
+    }
+}
+
+fn text_result(columns: &[&str], rows: Vec<Vec<Option<String>>>, command_tag: String) -> QueryResult {
+    let rows: Vec<Vec<serde_json::Value>> = rows
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|v| v.map_or(serde_json::Value::Null, serde_json::Value::String))
+                .collect()
+        })
+        .collect();
+    QueryResult {
+        columns: columns.iter().map(|c| computed_column(c)).collect(),
+        row_count: rows.len(),
+        rows,
+        execution_time_ms: 0,
+        command_tag,
+        truncated: false,
+        truncated_cells: Vec::new(),
+        footer: Vec::new(),
+    }
+}
+
+fn message_result(message: String) -> QueryResult {
+    text_result(&[], Vec::new(), message)
+}
+
+/// Apply `\x` and `\timing` to the result of an ordinary query. Expanded display
+/// turns each row into one (record, column, value) row per column.
+pub fn apply_display_settings(mut result: QueryResult, settings: MetaSettings) -> QueryResult {
+    if settings.expanded && !result.columns.is_empty() {
+        let width = result.columns.len();
+        let mut rows = Vec::with_capacity(result.rows.len() * width);
+        for (r, row) in result.rows.into_iter().enumerate() {
+            for (column, value) in result.columns.iter().zip(row) {
+                rows.push(vec![
+                    serde_json::Value::from(r + 1),
+                    serde_json::Value::String(column.name.clone()),
+                    value,
+                ]);
+            }
+        }
+        result.truncated_cells = result
+            .truncated_cells
+            .iter()
+            .map(|[r, c]| [r * width + c, 2])
+            .collect();
+        result.columns = vec![computed_column("record"), computed_column("column"), computed_column("value")];
+        result.columns[0].data_type = "integer".into();
+        result.rows = rows;
+    }
+    if settings.timing {
+        result.footer.push(format!("Time: {} ms", result.execution_time_ms));
+    }
+    result
+}
+
+/// Convert one part of a psql name pattern to an anchored regex. Unquoted
+/// names are folded to lower case; `*` and `?` are wildcards.
+fn pattern_regex(part: &str) -> String {
+    let mut regex = String::from("^(");
+    let mut quoted = false;
+    for c in part.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '*' if !quoted => regex.push_str(".*"),
+            '?' if !quoted => regex.push('.'),
+            c if "\\.^$|()[]{}+*?".contains(c) => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(if quoted { c } else { c.to_ascii_lowercase() }),
+        }
+    }
+    regex.push_str(")$");
+    regex
+}
+
+/// Split `schema.name` patterns into (schema regex, name regex)
+fn pattern_regexes(pattern: Option<&str>) -> (Option<String>, Option<String>) {
+    match pattern {
+        None => (None, None),
+        Some(p) => match p.rsplit_once('.') {
+            Some((schema, name)) => (
+                Some(pattern_regex(schema)),
+                (!name.is_empty()).then(|| pattern_regex(name)),
+            ),
+            None => (None, Some(pattern_regex(p))),
+        },
+    }
+}
+
+async fn run_catalog_query(client: &Arc<Client>, sql: &str, params: &[Option<String>], title: &str) -> Result<QueryResult> {
+    let mut result = execute_query_params(client, sql, params, QueryLimits::default()).await?;
+    result.command_tag = title.to_string();
+    Ok(result)
+}
+
+async fn list_relations(
+    client: &Arc<Client>,
+    kinds: &str,
+    pattern: Option<&str>,
+    verbose: bool,
+) -> Result<QueryResult> {
+    let (schema, name) = pattern_regexes(pattern);
+    let kinds: Vec<String> = kinds.chars().map(|k| format!("'{}'", k)).collect();
+    let extra = if verbose {
+        ",
+                pg_size_pretty(pg_total_relation_size(c.oid)) AS \"Size\",
+                obj_description(c.oid, 'pg_class') AS \"Description\""
+    } else {
+        ""
+    };
+    let sql = format!(
+        "SELECT
+                n.nspname AS \"Schema\",
+                c.relname AS \"Name\",
+                CASE c.relkind
+                    WHEN 'r' THEN 'table'
+                    WHEN 'p' THEN 'partitioned table'
+                    WHEN 'v' THEN 'view'
+                    WHEN 'm' THEN 'materialized view'
+                    WHEN 'i' THEN 'index'
+                    WHEN 'I' THEN 'partitioned index'
+                    WHEN 'S' THEN 'sequence'
+                    WHEN 'f' THEN 'foreign table'
+                END AS \"Type\",
+                pg_get_userbyid(c.relowner) AS \"Owner\"{}
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.relkind IN ({})
+               AND (($1::text IS NULL AND pg_table_is_visible(c.oid)
+                     AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+                     AND n.nspname !~ '^pg_toast')
+                    OR n.nspname ~ $1::text)
+               AND ($2::text IS NULL OR c.relname ~ $2::text)
+             ORDER BY 1, 2",
+        extra,
+        kinds.join(", ")
+    );
+    run_catalog_query(client, &sql, &[schema, name], "List of relations").await
+}
+
+async fn list_functions(client: &Arc<Client>, pattern: Option<&str>) -> Result<QueryResult> {
+    let (schema, name) = pattern_regexes(pattern);
+    run_catalog_query(
+        client,
+        "SELECT
+                n.nspname AS \"Schema\",
+                p.proname AS \"Name\",
+                pg_get_function_result(p.oid) AS \"Result data type\",
+                pg_get_function_arguments(p.oid) AS \"Argument data types\",
+                CASE p.prokind
+                    WHEN 'a' THEN 'agg'
+                    WHEN 'w' THEN 'window'
+                    WHEN 'p' THEN 'proc'
+                    ELSE 'func'
+                END AS \"Type\"
+             FROM pg_proc p
+             JOIN pg_namespace n ON n.oid = p.pronamespace
+             WHERE (($1::text IS NULL AND pg_function_is_visible(p.oid)
+                     AND n.nspname NOT IN ('pg_catalog', 'information_schema'))
+                    OR n.nspname ~ $1::text)
+               AND ($2::text IS NULL OR p.proname ~ $2::text)
+             ORDER BY 1, 2, 4",
+        &[schema, name],
+        "List of functions",
+    )
+    .await
+}
+
+async fn list_schemas(client: &Arc<Client>, pattern: Option<&str>, verbose: bool) -> Result<QueryResult> {
+    let extra = if verbose {
+        ",
+                obj_description(n.oid, 'pg_namespace') AS \"Description\""
+    } else {
+        ""
+    };
+    let sql = format!(
+        "SELECT
+                n.nspname AS \"Name\",
+                pg_get_userbyid(n.nspowner) AS \"Owner\"{}
+             FROM pg_namespace n
+             WHERE ($1::text IS NULL AND n.nspname !~ '^pg_' AND n.nspname <> 'information_schema')
+                OR n.nspname ~ $1::text
+             ORDER BY 1",
+        extra
+    );
+    run_catalog_query(client, &sql, &[pattern.map(pattern_regex)], "List of schemas").await
+}
+
+async fn list_roles(client: &Arc<Client>, pattern: Option<&str>) -> Result<QueryResult> {
+    run_catalog_query(
+        client,
+        "SELECT
+                r.rolname AS \"Role name\",
+                concat_ws(', ',
+                    CASE WHEN r.rolsuper THEN 'Superuser' END,
+                    CASE WHEN r.rolcreaterole THEN 'Create role' END,
+                    CASE WHEN r.rolcreatedb THEN 'Create DB' END,
+                    CASE WHEN NOT r.rolcanlogin THEN 'Cannot login' END,
+                    CASE WHEN r.rolreplication THEN 'Replication' END,
+                    CASE WHEN r.rolbypassrls THEN 'Bypass RLS' END
+                ) AS \"Attributes\"
+             FROM pg_roles r
+             WHERE ($1::text IS NULL AND r.rolname !~ '^pg_') OR r.rolname ~ $1::text
+             ORDER BY 1",
+        &[pattern.map(pattern_regex)],
+        "List of roles",
+    )
+    .await
+}
+
+async fn list_databases(client: &Arc<Client>, pattern: Option<&str>, verbose: bool) -> Result<QueryResult> {
+    let extra = if verbose {
+        ",
+                CASE WHEN has_database_privilege(d.oid, 'CONNECT')
+                    THEN pg_size_pretty(pg_database_size(d.oid))
+                    ELSE 'No Access'
+                END AS \"Size\""
+    } else {
+        ""
+    };
+    let sql = format!(
+        "SELECT
+                d.datname AS \"Name\",
+                pg_get_userbyid(d.datdba) AS \"Owner\",
+                pg_encoding_to_char(d.encoding) AS \"Encoding\",
+                d.datcollate AS \"Collate\",
+                d.datctype AS \"Ctype\"{}
+             FROM pg_database d
+             WHERE $1::text IS NULL OR d.datname ~ $1::text
+             ORDER BY 1",
+        extra
+    );
+    run_catalog_query(client, &sql, &[pattern.map(pattern_regex)], "List of databases").await
+}
+
+/// `\d name`: columns of a relation, with indexes, constraints and triggers as footer lines
+async fn describe_relation(client: &Arc<Client>, name: &str) -> Result<QueryResult> {
+    let row = client
+        .query_opt(
+            "SELECT c.oid, n.nspname, c.relname, c.relkind::text
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.oid = to_regclass($1)",
+            &[&name],
+        )
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Did not find any relation named \"{}\".", name))?;
+    let oid: u32 = row.get(0);
+    let schema: String = row.get(1);
+    let table: String = row.get(2);
+    let kind: String = row.get(3);
+
+    let kind_label = match kind.as_str() {
+        "r" => "Table",
+        "p" => "Partitioned table",
+        "v" => "View",
+        "m" => "Materialized view",
+        "i" | "I" => "Index",
+        "S" => "Sequence",
+        "f" => "Foreign table",
+        _ => "Relation",
+    };
+
+    // From pg_attribute rather than information_schema, which leaves out
+    // materialized views and indexes
+    let columns = client
+        .query(
+            "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), a.attnotnull,
+                    pg_get_expr(d.adbin, d.adrelid)
+             FROM pg_attribute a
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped
+             ORDER BY a.attnum",
+            &[&oid],
+        )
+        .await?;
+    let mut result = text_result(
+        &["Column", "Type", "Nullable", "Default"],
+        columns
+            .iter()
+            .map(|row| {
+                let not_null: bool = row.get(2);
+                vec![
+                    Some(row.get(0)),
+                    Some(row.get(1)),
+                    Some(if not_null { "not null".into() } else { String::new() }),
+                    row.get(3),
+                ]
+            })
+            .collect(),
+        format!("{} \"{}.{}\"", kind_label, schema, table),
+    );
+
+    let footer = &mut result.footer;
+    let indexes = get_indexes(client, &schema, &table).await?;
+    if !indexes.is_empty() {
+        footer.push("Indexes:".into());
+        for index in indexes {
+            let kind = if index.is_primary {
+                "PRIMARY KEY, "
+            } else if index.is_unique {
+                "UNIQUE, "
+            } else {
+                ""
+            };
+            let method = index
+                .definition
+                .split_once(" USING ")
+                .map_or(index.definition.as_str(), |(_, rest)| rest);
+            footer.push(format!("    \"{}\" {}{}", index.name, kind, method));
+        }
+    }
+
+    let constraints = get_constraints(client, &schema, &table).await?;
+    for (label, constraint_type) in [
+        ("Check constraints:", "CHECK"),
+        ("Foreign-key constraints:", "FOREIGN KEY"),
+    ] {
+        let matching: Vec<_> = constraints
+            .iter()
+            .filter(|c| c.constraint_type == constraint_type)
+            .collect();
+        if !matching.is_empty() {
+            footer.push(label.into());
+            for c in matching {
+                footer.push(format!("    \"{}\" {}", c.name, c.definition));
+            }
+        }
+    }
+
+    let triggers = get_triggers(client, &schema, &table).await?;
+    if !triggers.is_empty() {
+        footer.push("Triggers:".into());
+        for trigger in triggers {
+            footer.push(format!("    {}", trigger.definition));
+        }
+    }
+
+    if kind == "v" || kind == "m" {
+        let definition: Option<String> = client
+            .query_one("SELECT pg_get_viewdef(to_regclass($1), true)", &[&name])
+            .await?
+            .get(0);
+        if let Some(definition) = definition {
+            footer.push("View definition:".into());
+            footer.extend(definition.lines().map(|l| l.to_string()));
+        }
+    }
+
+    Ok(result)
+}
@@ -1,7 +1,38 @@
+mod advisor;
+mod catalog;
 mod connection;
+mod ddl;
+mod diff;
+mod extensions;
+mod graph;
+mod guard;
 mod introspection;
+mod jobs;
+mod meta;
+mod params;
+mod partitions;
 mod query;
+mod rls;
+mod roles;
+mod simple;
+mod snapshot;
+mod stats;
 
+pub use advisor::*;
+pub use catalog::*;
 pub use connection::*;
+pub use ddl::*;
+pub use diff::*;
+pub use extensions::*;
+pub use graph::*;
+pub use guard::*;
 pub use introspection::*;
+pub use jobs::*;
+pub use meta::*;
+pub use params::*;
+pub use partitions::*;
 pub use query::*;
+pub use rls::*;
+pub use roles::*;
+pub use snapshot::*;
+pub use stats::*;
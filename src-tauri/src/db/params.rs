@@ -0,0 +1,231 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use tokio_postgres::Client;
+
+enum Piece<'a> {
+    Sql(&'a str),
+    Param(&'a str),
+}
+
+fn is_ident_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_'
+}
+
+fn is_ident_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Split `sql` around `:name` and `${name}` placeholders. String literals,
+/// quoted identifiers, dollar-quoted bodies, comments and `::` casts are left alone.
+fn split_parameters(sql: &str) -> Vec<Piece<'_>> {
+    let bytes = sql.as_bytes();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' => {
+                // E'...' strings allow backslash escapes
+                let escapes = i > 0
+                    && matches!(bytes[i - 1], b'e' | b'E')
+                    && (i < 2 || !is_ident_char(bytes[i - 2]));
+                i += 1;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'\\' if escapes => i += 2,
+                        b'\'' if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+                        b'\'' => break,
+                        _ => i += 1,
+                    }
+                }
+                i += 1;
+            }
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                let name_start = i + 2;
+                let mut end = name_start;
+                while end < bytes.len() && is_ident_char(bytes[end]) {
+                    end += 1;
+                }
+                if end > name_start && bytes.get(end) == Some(&b'}') {
+                    pieces.push(Piece::Sql(&sql[start..i]));
+                    pieces.push(Piece::Param(&sql[name_start..end]));
+                    i = end + 1;
+                    start = i;
+                } else {
+                    i += 1;
+                }
+            }
+            b'$' => {
+                // Dollar quoting: $$...$$ or $tag$...$tag$ (but not $1)
+                let mut end = i + 1;
+                while end < bytes.len() && is_ident_char(bytes[end]) {
+                    end += 1;
+                }
+                let tag_ok = bytes.get(end) == Some(&b'$')
+                    && bytes.get(i + 1).is_none_or(|b| !b.is_ascii_digit());
+                if tag_ok {
+                    let tag = &sql[i..=end];
+                    i = match sql[end + 1..].find(tag) {
+                        Some(at) => end + 1 + at + tag.len(),
+                        None => bytes.len(),
+                    };
+                } else {
+                    i += 1;
+                }
+            }
+            b':' if bytes.get(i + 1) == Some(&b':') => i += 2,
+            b':' if bytes.get(i + 1).is_some_and(|b| is_ident_start(*b)) => {
+                let mut end = i + 1;
+                while end < bytes.len() && is_ident_char(bytes[end]) {
+                    end += 1;
+                }
+                pieces.push(Piece::Sql(&sql[start..i]));
+                pieces.push(Piece::Param(&sql[i + 1..end]));
+                i = end;
+                start = i;
+            }
+            _ => i += 1,
+        }
+    }
+    pieces.push(Piece::Sql(&sql[start.min(bytes.len())..]));
+    pieces
+}
+
+/// Names of the placeholders used in `sql`, in order of first use.
+pub fn find_named_parameters(sql: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for piece in split_parameters(sql) {
+        if let Piece::Param(name) = piece {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Rewrite placeholders as positional parameters. Each one becomes
+/// `($n::text::type)` so values are always bound as text and cast by the server.
+/// Returns the new SQL and the parameter names in `$n` order.
+pub fn bind_named_parameters(sql: &str, types: &HashMap<String, String>) -> (String, Vec<String>) {
+    let mut names: Vec<String> = Vec::new();
+    let mut out = String::with_capacity(sql.len());
+    for piece in split_parameters(sql) {
+        match piece {
+            Piece::Sql(text) => out.push_str(text),
+            Piece::Param(name) => {
+                let position = match names.iter().position(|n| n == name) {
+                    Some(p) => p + 1,
+                    None => {
+                        names.push(name.to_string());
+                        names.len()
+                    }
+                };
+                let data_type = types.get(name).map_or("text", String::as_str);
+                out.push_str(&format!("(${}::text::{})", position, data_type));
+            }
+        }
+    }
+    (out, names)
+}
+
+/// Quote a value the way libpq's PQescapeLiteral does: quotes are doubled, and
+/// so are backslashes, with an `E` prefix so that they read the same whatever
+/// `standard_conforming_strings` is set to.
+fn escape_literal(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('\'', "''");
+    if value.contains('\\') {
+        format!("E'{}'", escaped)
+    } else {
+        format!("'{}'", escaped)
+    }
+}
+
+/// Rewrite placeholders as literals, `('value'::text::type)`, for connections
+/// that use the simple query protocol and cannot bind parameters.
+pub fn inline_named_parameters(
+    sql: &str,
+    types: &HashMap<String, String>,
+    values: &HashMap<String, Option<String>>,
+) -> String {
+    let mut out = String::with_capacity(sql.len());
+    for piece in split_parameters(sql) {
+        match piece {
+            Piece::Sql(text) => out.push_str(text),
+            Piece::Param(name) => {
+                let literal = match values.get(name).cloned().flatten() {
+                    Some(value) => escape_literal(&value),
+                    None => "NULL".to_string(),
+                };
+                let data_type = types.get(name).map_or("text", String::as_str);
+                out.push_str(&format!("({}::text::{})", literal, data_type));
+            }
+        }
+    }
+    out
+}
+
+/// Make sure declared parameter types name real types before they go into SQL.
+pub async fn check_parameter_types(client: &Client, types: &HashMap<String, String>) -> Result<()> {
+    for (name, data_type) in types {
+        // to_regtype returns NULL for unknown names and errors on malformed ones
+        let known: Option<String> = client
+            .query_one("SELECT to_regtype($1)::text", &[data_type])
+            .await
+            .ok()
+            .and_then(|row| row.get(0));
+        if known.is_none() {
+            anyhow::bail!("Unknown type for parameter :{}: {}", name, data_type);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inlined_values_cannot_end_the_literal() {
+        let types = HashMap::from([("v".to_string(), "text".to_string())]);
+        let inline = |value: &str| {
+            let values = HashMap::from([("v".to_string(), Some(value.to_string()))]);
+            inline_named_parameters("SELECT :v", &types, &values)
+        };
+        assert_eq!(inline("it's"), "SELECT ('it''s'::text::text)");
+        assert_eq!(
+            inline(r"\'; DROP TABLE x; --"),
+            r"SELECT (E'\\''; DROP TABLE x; --'::text::text)"
+        );
+    }
+}
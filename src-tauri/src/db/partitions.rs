@@ -0,0 +1,344 @@
+use super::{quote_ident, quote_literal};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Serialize)]
+pub struct PartitionedTableInfo {
+    pub schema: String,
+    pub name: String,
+    /// range, list or hash
+    pub strategy: String,
+    /// As in PARTITION BY, e.g. "RANGE (created_at)"
+    pub partition_key: String,
+    /// Direct partitions
+    pub partition_count: i64,
+    pub default_partition: Option<String>,
+    /// Summed over every partition below the table
+    pub total_size: String,
+    pub total_size_bytes: i64,
+    pub row_estimate: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartitionNode {
+    pub schema: String,
+    pub name: String,
+    /// 0 for the root of the tree
+    pub level: i32,
+    /// FOR VALUES ... or DEFAULT; None for the root
+    pub bound: Option<String>,
+    /// Set when this partition is itself partitioned
+    pub partition_key: Option<String>,
+    /// Own size, or the size of everything below a partitioned node
+    pub size: String,
+    pub size_bytes: i64,
+    pub row_estimate: i64,
+    pub children: Vec<PartitionNode>,
+}
+
+/// Values are taken as literals, except MINVALUE and MAXVALUE in range bounds
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PartitionBound {
+    Range { from: Vec<String>, to: Vec<String> },
+    List { values: Vec<String> },
+    Hash { modulus: u32, remainder: u32 },
+    Default,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PartitionChange {
+    Create {
+        schema: String,
+        name: String,
+        bound: PartitionBound,
+        /// Sub-partition the new table, e.g. "LIST (region)"
+        partition_by: Option<String>,
+    },
+    Attach {
+        schema: String,
+        name: String,
+        bound: PartitionBound,
+    },
+    Detach {
+        schema: String,
+        name: String,
+        #[serde(default)]
+        concurrently: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartitionChangePreview {
+    pub ddl: String,
+    /// Locks, scans and conditions under which the statement fails
+    pub warnings: Vec<String>,
+}
+
+pub async fn get_partitioned_tables(
+    client: &Arc<Client>,
+    schema: &str,
+) -> Result<Vec<PartitionedTableInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                CASE p.partstrat WHEN 'r' THEN 'range' WHEN 'l' THEN 'list' WHEN 'h' THEN 'hash' END,
+                pg_get_partkeydef(c.oid),
+                (SELECT count(*) FROM pg_inherits i WHERE i.inhparent = c.oid),
+                (SELECT dc.relname FROM pg_class dc WHERE dc.oid = p.partdefid),
+                pg_size_pretty(tree.size),
+                tree.size,
+                tree.rows
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_partitioned_table p ON p.partrelid = c.oid
+             CROSS JOIN LATERAL (
+                 SELECT
+                     COALESCE(sum(pg_total_relation_size(t.relid)), 0)::bigint AS size,
+                     COALESCE(sum(GREATEST(tc.reltuples, 0)) FILTER (WHERE t.isleaf), 0)::bigint AS rows
+                 FROM pg_partition_tree(c.oid) t
+                 JOIN pg_class tc ON tc.oid = t.relid
+             ) tree
+             WHERE n.nspname = $1 AND NOT c.relispartition
+             ORDER BY c.relname",
+            &[&schema],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| PartitionedTableInfo {
+            schema: row.get(0),
+            name: row.get(1),
+            strategy: row.get(2),
+            partition_key: row.get(3),
+            partition_count: row.get(4),
+            default_partition: row.get(5),
+            total_size: row.get(6),
+            total_size_bytes: row.get(7),
+            row_estimate: row.get(8),
+        })
+        .collect())
+}
+
+/// The table and everything partitioned below it
+pub async fn get_partition_tree(
+    client: &Arc<Client>,
+    schema: &str,
+    table: &str,
+) -> Result<PartitionNode> {
+    let relation = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let rows = client
+        .query(
+            "SELECT
+                t.relid::oid,
+                t.parentrelid::oid,
+                t.level,
+                n.nspname,
+                c.relname,
+                CASE WHEN c.relispartition THEN pg_get_expr(c.relpartbound, c.oid) END,
+                CASE WHEN c.relkind = 'p' THEN pg_get_partkeydef(c.oid) END,
+                pg_size_pretty(sub.size),
+                sub.size,
+                sub.rows
+             FROM pg_partition_tree($1::text::regclass) t
+             JOIN pg_class c ON c.oid = t.relid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             CROSS JOIN LATERAL (
+                 SELECT
+                     COALESCE(sum(pg_total_relation_size(s.relid)), 0)::bigint AS size,
+                     COALESCE(sum(GREATEST(sc.reltuples, 0)) FILTER (WHERE s.isleaf), 0)::bigint AS rows
+                 FROM pg_partition_tree(c.oid) s
+                 JOIN pg_class sc ON sc.oid = s.relid
+             ) sub
+             ORDER BY t.level, n.nspname, c.relname",
+            &[&relation],
+        )
+        .await?;
+
+    // Rows come level by level; attach each to its parent from the deepest up
+    let mut flat: Vec<(u32, Option<u32>, PartitionNode)> = rows
+        .iter()
+        .map(|row| {
+            (
+                row.get(0),
+                row.get(1),
+                PartitionNode {
+                    schema: row.get(3),
+                    name: row.get(4),
+                    level: row.get(2),
+                    bound: row.get(5),
+                    partition_key: row.get(6),
+                    size: row.get(7),
+                    size_bytes: row.get(8),
+                    row_estimate: row.get(9),
+                    children: Vec::new(),
+                },
+            )
+        })
+        .collect();
+    while flat.len() > 1 {
+        let (_, parent, node) = flat.pop().expect("non-empty");
+        match flat.iter_mut().find(|(oid, _, _)| Some(*oid) == parent) {
+            Some((_, _, parent)) => parent.children.insert(0, node),
+            None => anyhow::bail!("Partition {} lost its parent", node.name),
+        }
+    }
+    flat.pop()
+        .map(|(_, _, root)| root)
+        .ok_or_else(|| anyhow::anyhow!("Table {}.{} not found", schema, table))
+}
+
+fn bound_value(value: &str, range: bool) -> String {
+    let trimmed = value.trim();
+    if range
+        && (trimmed.eq_ignore_ascii_case("MINVALUE") || trimmed.eq_ignore_ascii_case("MAXVALUE"))
+    {
+        trimmed.to_ascii_uppercase()
+    } else {
+        quote_literal(value)
+    }
+}
+
+pub fn partition_bound_sql(bound: &PartitionBound) -> String {
+    let list = |values: &[String], range: bool| {
+        values
+            .iter()
+            .map(|v| bound_value(v, range))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match bound {
+        PartitionBound::Range { from, to } => format!(
+            "FOR VALUES FROM ({}) TO ({})",
+            list(from, true),
+            list(to, true)
+        ),
+        PartitionBound::List { values } => format!("FOR VALUES IN ({})", list(values, false)),
+        PartitionBound::Hash { modulus, remainder } => format!(
+            "FOR VALUES WITH (MODULUS {}, REMAINDER {})",
+            modulus, remainder
+        ),
+        PartitionBound::Default => "DEFAULT".to_string(),
+    }
+}
+
+/// One statement for `change` on the partitioned table `schema.table`
+pub fn partition_ddl(schema: &str, table: &str, change: &PartitionChange) -> String {
+    let parent = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let qualified = |s: &str, n: &str| format!("{}.{}", quote_ident(s), quote_ident(n));
+    match change {
+        PartitionChange::Create {
+            schema,
+            name,
+            bound,
+            partition_by,
+        } => {
+            let mut sql = format!(
+                "CREATE TABLE {} PARTITION OF {}\n    {}",
+                qualified(schema, name),
+                parent,
+                partition_bound_sql(bound)
+            );
+            if let Some(key) = partition_by.as_deref().filter(|k| !k.trim().is_empty()) {
+                sql.push_str(&format!("\n    PARTITION BY {}", key.trim()));
+            }
+            sql
+        }
+        PartitionChange::Attach { schema, name, bound } => format!(
+            "ALTER TABLE {} ATTACH PARTITION {}\n    {}",
+            parent,
+            qualified(schema, name),
+            partition_bound_sql(bound)
+        ),
+        PartitionChange::Detach {
+            schema,
+            name,
+            concurrently,
+        } => format!(
+            "ALTER TABLE {} DETACH PARTITION {}{}",
+            parent,
+            qualified(schema, name),
+            if *concurrently { " CONCURRENTLY" } else { "" }
+        ),
+    }
+}
+
+pub async fn preview_partition_change(
+    client: &Arc<Client>,
+    schema: &str,
+    table: &str,
+    change: &PartitionChange,
+) -> Result<PartitionChangePreview> {
+    let parent = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let row = client
+        .query_one(
+            "SELECT
+                (SELECT dn.nspname || '.' || dc.relname
+                 FROM pg_class dc JOIN pg_namespace dn ON dn.oid = dc.relnamespace
+                 WHERE dc.oid = p.partdefid)
+             FROM pg_partitioned_table p
+             WHERE p.partrelid = $1::text::regclass",
+            &[&parent],
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("{}.{} is not a partitioned table", schema, table))?;
+    let default_partition: Option<String> = row.get(0);
+
+    let mut warnings = Vec::new();
+    match change {
+        PartitionChange::Create { bound, .. } | PartitionChange::Attach { bound, .. } => {
+            if let Some(default) = &default_partition {
+                if !matches!(bound, PartitionBound::Default) {
+                    warnings.push(format!(
+                        "The default partition {} is scanned for rows that belong to the new bound, and the statement fails if it finds any",
+                        default
+                    ));
+                }
+            }
+            if let PartitionChange::Attach { schema, name, .. } = change {
+                warnings.push(format!(
+                    "{}.{} is scanned to check that every row fits the bound, unless a CHECK constraint already implies it",
+                    schema, name
+                ));
+                warnings.push(format!(
+                    "Takes a SHARE UPDATE EXCLUSIVE lock on {}.{} and an ACCESS EXCLUSIVE lock on {}.{}",
+                    schema, table, schema, name
+                ));
+            } else {
+                warnings.push(format!(
+                    "Takes an ACCESS EXCLUSIVE lock on {}.{} while the partition is created",
+                    schema, table
+                ));
+            }
+        }
+        PartitionChange::Detach { concurrently, .. } => {
+            if *concurrently {
+                if default_partition.is_some() {
+                    warnings.push(
+                        "DETACH CONCURRENTLY is not allowed while the table has a default partition"
+                            .to_string(),
+                    );
+                }
+                warnings.push(
+                    "Runs outside a transaction and waits for queries using the table to finish; if it is interrupted, complete it with DETACH PARTITION ... FINALIZE"
+                        .to_string(),
+                );
+            } else {
+                warnings.push(format!(
+                    "Takes an ACCESS EXCLUSIVE lock on {}.{}; use CONCURRENTLY to avoid blocking queries",
+                    schema, table
+                ));
+            }
+        }
+    }
+    Ok(PartitionChangePreview {
+        ddl: partition_ddl(schema, table, change),
+        warnings,
+    })
+}
@@ -1,47 +1,172 @@
+use super::{simple::run_simple_query, ExecutionMode};
 use anyhow::Result;
+use futures_util::{pin_mut, TryStreamExt};
 use serde::Serialize;
+use sqlparser::ast::{SetExpr, Statement};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Instant;
-use tokio_postgres::types::Type;
+use std::time::{Duration, Instant};
+use tokio_postgres::types::{ToSql, Type};
 use tokio_postgres::Client;
 
-#[derive(Debug, Serialize)]
+pub const DEFAULT_ROW_LIMIT: u32 = 1000;
+pub const DEFAULT_CELL_BYTE_BUDGET: u32 = 16 * 1024;
+
+/// Minimum time between two progress callbacks while rows are streaming in
+pub(super) const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize)]
 pub struct QueryResult {
     pub columns: Vec<ColumnDef>,
     pub rows: Vec<Vec<serde_json::Value>>,
     pub row_count: usize,
     pub execution_time_ms: u128,
     pub command_tag: String,
+    /// More rows were available than the row limit allowed
+    pub truncated: bool,
+    /// [row, column] positions of cells cut down to the byte budget
+    pub truncated_cells: Vec<[usize; 2]>,
+    /// Extra lines shown under the grid, e.g. indexes for `\d table`
+    pub footer: Vec<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// Size limits applied to ad-hoc queries, and the protocol they are sent with.
+/// Zero disables a limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueryLimits {
+    /// Max rows fetched for a top-level SELECT that has no LIMIT of its own
+    pub row_limit: u32,
+    /// Max bytes kept for a single text or bytea cell
+    pub cell_byte_budget: u32,
+    pub execution_mode: ExecutionMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ColumnDef {
     pub name: String,
     pub data_type: String,
+    /// Source table OID when the column maps directly to a table column
+    pub table_oid: Option<u32>,
+    /// Attribute number of the column within its source table
+    pub column_id: Option<i16>,
+    pub source_schema: Option<String>,
+    pub source_table: Option<String>,
+    pub source_column: Option<String>,
+    /// Declared nullability of the source column; None for computed columns
+    pub is_nullable: Option<bool>,
+    pub is_primary_key: bool,
+    /// True when every primary key column of the source table is in the result,
+    /// i.e. rows can be addressed for editing
+    pub source_key_complete: bool,
 }
 
 pub async fn execute_query(client: &Arc<Client>, sql: &str) -> Result<QueryResult> {
+    execute_query_limited(client, sql, QueryLimits::default()).await
+}
+
+pub async fn execute_query_limited(
+    client: &Arc<Client>,
+    sql: &str,
+    limits: QueryLimits,
+) -> Result<QueryResult> {
+    execute_query_streaming(client, sql, limits, |_| {}).await
+}
+
+/// Like `execute_query_limited`, but reports the number of rows received so far
+/// to `on_progress` while the result is streaming in.
+pub async fn execute_query_streaming<F>(
+    client: &Arc<Client>,
+    sql: &str,
+    limits: QueryLimits,
+    on_progress: F,
+) -> Result<QueryResult>
+where
+    F: FnMut(usize),
+{
+    run_query(client, sql, &[], limits, on_progress).await
+}
+
+/// Run a query with text parameters for `$1`, `$2`, ... (None binds NULL).
+pub async fn execute_query_params(
+    client: &Arc<Client>,
+    sql: &str,
+    params: &[Option<String>],
+    limits: QueryLimits,
+) -> Result<QueryResult> {
+    let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+    run_query(client, sql, &params, limits, |_| {}).await
+}
+
+async fn run_query<F>(
+    client: &Arc<Client>,
+    sql: &str,
+    params: &[&(dyn ToSql + Sync)],
+    limits: QueryLimits,
+    mut on_progress: F,
+) -> Result<QueryResult>
+where
+    F: FnMut(usize),
+{
+    if limits.execution_mode == ExecutionMode::Simple {
+        if !params.is_empty() {
+            anyhow::bail!("Query parameters are not supported with the simple query protocol");
+        }
+        return run_simple_query(client, sql, limits, on_progress).await;
+    }
+
     let start = Instant::now();
+    let (effective_sql, row_limit) = limited_sql(sql, limits);
 
-    let stmt = client.prepare(sql).await?;
-    let rows = client.query(&stmt, &[]).await?;
+    let stmt = client.prepare(&effective_sql).await?;
+    let stream = client.query_raw(&stmt, params.iter().copied()).await?;
+    pin_mut!(stream);
+
+    let mut rows = Vec::new();
+    let mut last_report = Instant::now();
+    while let Some(row) = stream.try_next().await? {
+        rows.push(row);
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            on_progress(rows.len());
+            last_report = Instant::now();
+        }
+    }
     let execution_time_ms = start.elapsed().as_millis();
 
-    let columns: Vec<ColumnDef> = stmt
+    let truncated = matches!(row_limit, Some(limit) if rows.len() > limit);
+    if let Some(limit) = row_limit {
+        rows.truncate(limit);
+    }
+
+    let mut columns: Vec<ColumnDef> = stmt
         .columns()
         .iter()
         .map(|col| ColumnDef {
             name: col.name().to_string(),
             data_type: pg_type_to_string(col.type_()),
+            table_oid: col.table_oid(),
+            column_id: col.column_id(),
+            source_schema: None,
+            source_table: None,
+            source_column: None,
+            is_nullable: None,
+            is_primary_key: false,
+            source_key_complete: false,
         })
         .collect();
 
     let mut result_rows = Vec::with_capacity(rows.len());
+    let mut truncated_cells = Vec::new();
+    let byte_budget = limits.cell_byte_budget as usize;
 
-    for row in &rows {
+    for (r, row) in rows.iter().enumerate() {
         let mut values = Vec::with_capacity(columns.len());
         for (i, col) in stmt.columns().iter().enumerate() {
-            let value = pg_value_to_json(&row, i, col.type_());
+            let (value, cut) = pg_value_to_json(row, i, col.type_(), byte_budget);
+            if cut {
+                truncated_cells.push([r, i]);
+            }
             values.push(value);
         }
         result_rows.push(values);
@@ -49,16 +174,234 @@ pub async fn execute_query(client: &Arc<Client>, sql: &str) -> Result<QueryResul
 
     let row_count = result_rows.len();
 
+    // Provenance is best-effort: a failed catalog lookup must not fail the query
+    let _ = resolve_column_sources(client, &mut columns).await;
+
     Ok(QueryResult {
         columns,
         rows: result_rows,
         row_count,
         execution_time_ms,
         command_tag: format!("SELECT {}", row_count),
+        truncated,
+        truncated_cells,
+        footer: Vec::new(),
     })
 }
 
-fn pg_type_to_string(pg_type: &Type) -> String {
+/// Wrap `sql` in a LIMIT when the row limit applies to it. Returns the SQL to
+/// run and the limit, if any.
+pub(super) fn limited_sql(sql: &str, limits: QueryLimits) -> (String, Option<usize>) {
+    let row_limit = (limits.row_limit > 0 && row_limit_applies(sql)).then_some(limits.row_limit as usize);
+    let effective_sql = match row_limit {
+        // Fetch one extra row so we can tell whether the result was cut off
+        Some(limit) => format!(
+            "SELECT * FROM (\n{}\n) AS pgstudio_limited LIMIT {}",
+            strip_terminator(sql),
+            limit + 1
+        ),
+        None => sql.to_string(),
+    };
+    (effective_sql, row_limit)
+}
+
+/// Whether `sql` is a single read-only SELECT without its own LIMIT/FETCH,
+/// i.e. one that can safely be wrapped to cap the number of rows fetched.
+pub fn row_limit_applies(sql: &str) -> bool {
+    if strip_terminator(sql).contains(';') {
+        return false;
+    }
+    let Ok(statements) = Parser::parse_sql(&PostgreSqlDialect {}, sql) else {
+        return false;
+    };
+    match statements.as_slice() {
+        [Statement::Query(query)] => {
+            query.limit.is_none()
+                && query.fetch.is_none()
+                && query.locks.is_empty()
+                && is_plain_select(&query.body)
+                && query
+                    .with
+                    .iter()
+                    .flat_map(|with| &with.cte_tables)
+                    .all(|cte| is_plain_select(&cte.query.body))
+        }
+        _ => false,
+    }
+}
+
+fn is_plain_select(body: &SetExpr) -> bool {
+    match body {
+        // SELECT ... INTO creates a table and cannot be wrapped
+        SetExpr::Select(select) => select.into.is_none(),
+        SetExpr::Query(query) => is_plain_select(&query.body),
+        SetExpr::SetOperation { left, right, .. } => is_plain_select(left) && is_plain_select(right),
+        SetExpr::Values(_) | SetExpr::Table(_) => true,
+        SetExpr::Insert(_) | SetExpr::Update(_) => false,
+    }
+}
+
+fn strip_terminator(sql: &str) -> &str {
+    sql.trim().trim_end_matches(';').trim_end()
+}
+
+/// Load the full value of a single cell, addressed by the row's primary key.
+/// Used to fetch cells that were truncated by the byte budget.
+pub async fn fetch_cell(
+    client: &Arc<Client>,
+    schema: &str,
+    table: &str,
+    column: &str,
+    key: &HashMap<String, serde_json::Value>,
+) -> Result<serde_json::Value> {
+    if key.is_empty() {
+        anyhow::bail!("A row key is required to fetch a cell");
+    }
+    let qualified = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let key_names: Vec<&String> = key.keys().collect();
+
+    // Cast the text parameters to each key column's own type so indexes can be used
+    let type_rows = client
+        .query(
+            "SELECT a.attname, format_type(a.atttypid, a.atttypmod)
+             FROM pg_attribute a
+             WHERE a.attrelid = $1::text::regclass AND a.attname = ANY($2)
+               AND a.attnum > 0 AND NOT a.attisdropped",
+            &[&qualified, &key_names],
+        )
+        .await?;
+    let key_types: HashMap<String, String> = type_rows
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+    let mut conditions = Vec::with_capacity(key.len());
+    let mut params: Vec<Option<String>> = Vec::with_capacity(key.len());
+    for name in &key_names {
+        let type_name = key_types
+            .get(name.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Column {} not found on {}", name, qualified))?;
+        match &key[name.as_str()] {
+            serde_json::Value::Null => {
+                conditions.push(format!("{} IS NULL", quote_ident(name)));
+            }
+            value => {
+                params.push(Some(json_to_text(value)));
+                conditions.push(format!(
+                    "{} = ${}::text::{}",
+                    quote_ident(name),
+                    params.len(),
+                    type_name
+                ));
+            }
+        }
+    }
+
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {}",
+        quote_ident(column),
+        qualified,
+        conditions.join(" AND ")
+    );
+    let param_refs: Vec<&(dyn ToSql + Sync)> =
+        params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+    let rows = client.query(&sql, &param_refs).await?;
+    match rows.as_slice() {
+        [row] => Ok(pg_value_to_json(row, 0, row.columns()[0].type_(), 0).0),
+        [] => anyhow::bail!("Row not found"),
+        _ => anyhow::bail!("Row key matched {} rows", rows.len()),
+    }
+}
+
+fn json_to_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn quote_ident(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+pub fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// The server's message for a failed statement, rather than the bare "db error"
+pub fn db_error_message(e: &tokio_postgres::Error) -> String {
+    e.as_db_error()
+        .map(|db| db.message().to_string())
+        .unwrap_or_else(|| e.to_string())
+}
+
+/// Fill in schema/table/column names, nullability and primary key coverage
+/// for result columns that come straight from a table.
+async fn resolve_column_sources(client: &Client, columns: &mut [ColumnDef]) -> Result<()> {
+    let mut oids: Vec<u32> = columns.iter().filter_map(|c| c.table_oid).collect();
+    oids.sort_unstable();
+    oids.dedup();
+    if oids.is_empty() {
+        return Ok(());
+    }
+
+    let rows = client
+        .query(
+            "SELECT
+                c.oid,
+                n.nspname,
+                c.relname,
+                a.attnum,
+                a.attname,
+                NOT a.attnotnull as is_nullable,
+                COALESCE(a.attnum = ANY(pk.conkey), false) as is_pk,
+                COALESCE(pk.conkey, ARRAY[]::int2[]) as pk_columns
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+             LEFT JOIN pg_constraint pk ON pk.conrelid = c.oid AND pk.contype = 'p'
+             WHERE c.oid = ANY($1)",
+            &[&oids],
+        )
+        .await?;
+
+    let mut attributes: HashMap<(u32, i16), &tokio_postgres::Row> = HashMap::new();
+    let mut primary_keys: HashMap<u32, Vec<i16>> = HashMap::new();
+    for row in &rows {
+        let oid: u32 = row.get(0);
+        attributes.insert((oid, row.get(3)), row);
+        primary_keys.entry(oid).or_insert_with(|| row.get(7));
+    }
+
+    let mut present: HashMap<u32, HashSet<i16>> = HashMap::new();
+    for col in columns.iter() {
+        if let (Some(oid), Some(attnum)) = (col.table_oid, col.column_id) {
+            present.entry(oid).or_default().insert(attnum);
+        }
+    }
+
+    for col in columns.iter_mut() {
+        let (Some(oid), Some(attnum)) = (col.table_oid, col.column_id) else {
+            continue;
+        };
+        let Some(row) = attributes.get(&(oid, attnum)) else {
+            continue;
+        };
+        col.source_schema = Some(row.get(1));
+        col.source_table = Some(row.get(2));
+        col.source_column = Some(row.get(4));
+        col.is_nullable = Some(row.get(5));
+        col.is_primary_key = row.get(6);
+        col.source_key_complete = match (primary_keys.get(&oid), present.get(&oid)) {
+            (Some(pk), Some(cols)) => !pk.is_empty() && pk.iter().all(|k| cols.contains(k)),
+            _ => false,
+        };
+    }
+
+    Ok(())
+}
+
+pub(super) fn pg_type_to_string(pg_type: &Type) -> String {
     match *pg_type {
         Type::BOOL => "boolean".into(),
         Type::INT2 => "smallint".into(),
@@ -82,13 +425,30 @@ fn pg_type_to_string(pg_type: &Type) -> String {
     }
 }
 
+/// Cut `text` down to at most `byte_budget` bytes (0 = unlimited) on a char
+/// boundary. Returns whether anything was cut.
+pub(super) fn truncate_text(text: &mut String, byte_budget: usize) -> bool {
+    let cut = byte_budget > 0 && text.len() > byte_budget;
+    if cut {
+        let mut end = byte_budget;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    cut
+}
+
+/// Convert a cell to JSON. Text and bytea values longer than `byte_budget`
+/// bytes (0 = unlimited) are cut down; the returned flag reports whether that happened.
 fn pg_value_to_json(
     row: &tokio_postgres::Row,
     idx: usize,
     pg_type: &Type,
-) -> serde_json::Value {
+    byte_budget: usize,
+) -> (serde_json::Value, bool) {
     // Try to extract based on type, fallback to text representation
-    match *pg_type {
+    let value = match *pg_type {
         Type::BOOL => row
             .try_get::<_, Option<bool>>(idx)
             .ok()
@@ -132,13 +492,27 @@ fn pg_value_to_json(
             .ok()
             .flatten()
             .unwrap_or(serde_json::Value::Null),
+        Type::BYTEA => {
+            return match row.try_get::<_, Option<&[u8]>>(idx).ok().flatten() {
+                Some(bytes) => {
+                    let cut = byte_budget > 0 && bytes.len() > byte_budget;
+                    let bytes = if cut { &bytes[..byte_budget] } else { bytes };
+                    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                    (serde_json::Value::String(format!("\\x{}", hex)), cut)
+                }
+                None => (serde_json::Value::Null, false),
+            };
+        }
         _ => {
             // Fallback: try to get as string
-            row.try_get::<_, Option<String>>(idx)
-                .ok()
-                .flatten()
-                .map(serde_json::Value::String)
-                .unwrap_or(serde_json::Value::Null)
+            return match row.try_get::<_, Option<String>>(idx).ok().flatten() {
+                Some(mut text) => {
+                    let cut = truncate_text(&mut text, byte_budget);
+                    (serde_json::Value::String(text), cut)
+                }
+                None => (serde_json::Value::Null, false),
+            };
         }
-    }
+    };
+    (value, false)
 }
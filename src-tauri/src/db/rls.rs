@@ -0,0 +1,287 @@
+use super::{
+    execute_query_params, quote_ident, ExecutionMode, PolicyInfo, QueryLimits, QueryResult,
+};
+use anyhow::Result;
+use serde::Serialize;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Serialize)]
+pub struct RlsTableReport {
+    pub schema: String,
+    pub table: String,
+    /// SELECT, INSERT, UPDATE or DELETE, as the query uses the table
+    pub command: String,
+    pub rls_enabled: bool,
+    pub rls_forced: bool,
+    /// Why policies were skipped for this role, if they were
+    pub bypass_reason: Option<String>,
+    /// Policies that apply to the role and command
+    pub policies: Vec<PolicyInfo>,
+    /// RLS is in force but no policy applies, so no rows are visible or writable
+    pub default_deny: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RlsTestResult {
+    pub role: String,
+    pub result: Option<QueryResult>,
+    /// The error the query raised as the role, e.g. a permission or WITH CHECK failure
+    pub error: Option<String>,
+    pub tables: Vec<RlsTableReport>,
+}
+
+/// Whether `sql` starts by ending or splitting the surrounding transaction.
+/// Input that cannot be tokenized counts, so it is refused.
+fn is_transaction_control(sql: &str) -> bool {
+    let Ok(tokens) = Tokenizer::new(&PostgreSqlDialect {}, sql).tokenize() else {
+        return true;
+    };
+    let mut words = tokens.iter().filter_map(|t| match t {
+        Token::Word(w) => Some(w.keyword),
+        _ => None,
+    });
+    match words.next() {
+        Some(
+            Keyword::COMMIT
+            | Keyword::ROLLBACK
+            | Keyword::ABORT
+            | Keyword::END
+            | Keyword::SAVEPOINT
+            | Keyword::RELEASE,
+        ) => true,
+        Some(Keyword::PREPARE) => words.next() == Some(Keyword::TRANSACTION),
+        _ => false,
+    }
+}
+
+/// Run `sql` as `role` with `settings` applied via `SET LOCAL`, inside a
+/// transaction that is always rolled back. `client` must not be shared: the
+/// role and settings hold for the whole transaction. The query is always sent
+/// with the extended protocol, which takes a single statement, and statements
+/// that would end the transaction early are refused.
+pub async fn run_as_role(
+    client: &Arc<Client>,
+    role: &str,
+    settings: &HashMap<String, String>,
+    sql: &str,
+    limits: QueryLimits,
+) -> Result<RlsTestResult> {
+    if is_transaction_control(sql) {
+        anyhow::bail!("Transaction control statements cannot be tested as another role");
+    }
+    let limits = QueryLimits {
+        execution_mode: ExecutionMode::Extended,
+        ..limits
+    };
+    client.batch_execute("BEGIN").await?;
+    let outcome = run_in_transaction(client, role, settings, sql, limits).await;
+    client.batch_execute("ROLLBACK").await?;
+    let (relations, query) = outcome?;
+
+    let mut tables = Vec::with_capacity(relations.len());
+    for (schema, table, command) in relations {
+        tables.push(table_report(client, role, schema, table, command).await?);
+    }
+    let (result, error) = match query {
+        Ok(result) => (Some(result), None),
+        Err(e) => (None, Some(e)),
+    };
+    Ok(RlsTestResult {
+        role: role.to_string(),
+        result,
+        error,
+        tables,
+    })
+}
+
+type Relations = Vec<(String, String, String)>;
+
+async fn run_in_transaction(
+    client: &Arc<Client>,
+    role: &str,
+    settings: &HashMap<String, String>,
+    sql: &str,
+    limits: QueryLimits,
+) -> Result<(Relations, std::result::Result<QueryResult, String>)> {
+    client
+        .batch_execute(&format!("SET LOCAL ROLE {}", quote_ident(role)))
+        .await
+        .map_err(|e| anyhow::anyhow!(super::db_error_message(&e)))?;
+    for (name, value) in settings {
+        client
+            .execute("SELECT set_config($1, $2, true)", &[name, value])
+            .await
+            .map_err(|e| anyhow::anyhow!(super::db_error_message(&e)))?;
+    }
+
+    // The plan names every table the query touches, after view expansion.
+    // Statements that cannot be explained are run without a table report.
+    client.batch_execute("SAVEPOINT rls_explain").await?;
+    let relations = match client
+        .query_one(&format!("EXPLAIN (VERBOSE, FORMAT JSON) {}", sql), &[])
+        .await
+    {
+        Ok(row) => {
+            let plan: serde_json::Value = row.get(0);
+            let mut relations = Vec::new();
+            collect_relations(&plan, &mut relations);
+            relations
+        }
+        Err(_) => {
+            client.batch_execute("ROLLBACK TO SAVEPOINT rls_explain").await?;
+            Vec::new()
+        }
+    };
+
+    let query = execute_query_params(client, sql, &[], limits)
+        .await
+        .map_err(|e| match e.downcast_ref::<tokio_postgres::Error>() {
+            Some(pg) => super::db_error_message(pg),
+            None => e.to_string(),
+        });
+    Ok((relations, query))
+}
+
+fn collect_relations(node: &serde_json::Value, out: &mut Relations) {
+    match node {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_relations(item, out);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            if let (Some(schema), Some(table)) = (
+                map.get("Schema").and_then(|v| v.as_str()),
+                map.get("Relation Name").and_then(|v| v.as_str()),
+            ) {
+                let command = map
+                    .get("Operation")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Select")
+                    .to_ascii_uppercase();
+                match out.iter_mut().find(|(s, t, _)| s == schema && t == table) {
+                    // A table that is both written and read reports the write
+                    Some(existing) if command != "SELECT" => existing.2 = command,
+                    Some(_) => {}
+                    None => out.push((schema.to_string(), table.to_string(), command)),
+                }
+            }
+            for value in map.values() {
+                collect_relations(value, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+async fn table_report(
+    client: &Client,
+    role: &str,
+    schema: String,
+    table: String,
+    command: String,
+) -> Result<RlsTableReport> {
+    let row = client
+        .query_one(
+            "SELECT
+                c.relrowsecurity,
+                c.relforcerowsecurity,
+                r.rolsuper,
+                r.rolbypassrls,
+                pg_has_role(r.oid, c.relowner, 'USAGE')
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             CROSS JOIN pg_roles r
+             WHERE n.nspname = $1 AND c.relname = $2 AND r.rolname = $3",
+            &[&schema, &table, &role],
+        )
+        .await?;
+    let rls_enabled: bool = row.get(0);
+    let rls_forced: bool = row.get(1);
+    let bypass_reason = if !rls_enabled {
+        Some("row level security is disabled on the table")
+    } else if row.get(2) {
+        Some("the role is a superuser")
+    } else if row.get(3) {
+        Some("the role has BYPASSRLS")
+    } else if row.get(4) && !rls_forced {
+        Some("the role owns the table and row level security is not forced")
+    } else {
+        None
+    };
+
+    // Reads need SELECT policies; UPDATE and DELETE also read the rows they change
+    let commands: Vec<String> = match command.as_str() {
+        "INSERT" => vec!["*".into(), "a".into()],
+        "UPDATE" => vec!["*".into(), "w".into(), "r".into()],
+        "DELETE" => vec!["*".into(), "d".into(), "r".into()],
+        _ => vec!["*".into(), "r".into()],
+    };
+    let rows = client
+        .query(
+            "SELECT
+                pol.polname,
+                CASE pol.polcmd
+                    WHEN 'r' THEN 'SELECT'
+                    WHEN 'a' THEN 'INSERT'
+                    WHEN 'w' THEN 'UPDATE'
+                    WHEN 'd' THEN 'DELETE'
+                    WHEN '*' THEN 'ALL'
+                    ELSE pol.polcmd::text
+                END,
+                pol.polpermissive,
+                COALESCE(
+                    (SELECT array_agg(r.rolname)
+                     FROM unnest(pol.polroles) AS role_oid
+                     JOIN pg_roles r ON r.oid = role_oid),
+                    ARRAY['PUBLIC']::text[]
+                ),
+                pg_get_expr(pol.polqual, pol.polrelid, true),
+                pg_get_expr(pol.polwithcheck, pol.polrelid, true)
+             FROM pg_policy pol
+             JOIN pg_class c ON c.oid = pol.polrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2
+               AND pol.polcmd::text = ANY($4)
+               AND EXISTS (
+                   SELECT 1 FROM unnest(pol.polroles) AS role_oid
+                   WHERE role_oid = 0 OR pg_has_role($3::name, role_oid, 'MEMBER')
+               )
+             ORDER BY pol.polname",
+            &[&schema, &table, &role, &commands],
+        )
+        .await?;
+    let policies: Vec<PolicyInfo> = rows
+        .iter()
+        .map(|row| PolicyInfo {
+            name: row.get(0),
+            command: row.get(1),
+            permissive: row.get(2),
+            roles: row.get(3),
+            using_expr: row.get(4),
+            check_expr: row.get(5),
+        })
+        .collect();
+
+    // Restrictive policies only narrow what permissive ones allow, and a write
+    // needs a permissive policy for its own command
+    let default_deny = bypass_reason.is_none()
+        && !policies
+            .iter()
+            .any(|p| p.permissive && (p.command == "ALL" || p.command == command));
+    Ok(RlsTableReport {
+        schema,
+        table,
+        command,
+        rls_enabled,
+        rls_forced,
+        bypass_reason: bypass_reason.map(str::to_string),
+        policies,
+        default_deny,
+    })
+}
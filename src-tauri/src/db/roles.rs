@@ -0,0 +1,435 @@
+use super::quote_ident;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Serialize)]
+pub struct RoleInfo {
+    pub oid: u32,
+    pub name: String,
+    pub can_login: bool,
+    pub superuser: bool,
+    pub create_db: bool,
+    pub create_role: bool,
+    pub inherit: bool,
+    pub replication: bool,
+    pub bypass_rls: bool,
+    /// -1 means no limit
+    pub connection_limit: i32,
+    pub valid_until: Option<String>,
+    pub member_of: Vec<String>,
+    pub description: Option<String>,
+    /// Predefined roles such as pg_read_all_data
+    pub is_system: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleMembership {
+    pub role: String,
+    pub member: String,
+    pub admin_option: bool,
+    pub grantor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivilegeObjectKind {
+    Schema,
+    Table,
+    Sequence,
+    Function,
+}
+
+impl PrivilegeObjectKind {
+    /// Privileges that can be granted on this kind of object, in psql's order
+    pub fn privileges(self) -> &'static [&'static str] {
+        match self {
+            Self::Table => &["SELECT", "INSERT", "UPDATE", "DELETE", "TRUNCATE", "REFERENCES", "TRIGGER"],
+            Self::Sequence => &["USAGE", "SELECT", "UPDATE"],
+            Self::Schema => &["USAGE", "CREATE"],
+            Self::Function => &["EXECUTE"],
+        }
+    }
+
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::Table => "TABLE",
+            Self::Sequence => "SEQUENCE",
+            Self::Schema => "SCHEMA",
+            Self::Function => "FUNCTION",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrivilegeState {
+    pub privilege: String,
+    /// The role can use the privilege, directly, through PUBLIC, membership or ownership
+    pub effective: bool,
+    /// Granted to the role itself in the object's ACL
+    pub direct: bool,
+    pub grantable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ObjectPrivileges {
+    pub kind: PrivilegeObjectKind,
+    pub schema: String,
+    pub name: String,
+    /// Identity arguments, for functions
+    pub arguments: Option<String>,
+    pub owner: String,
+    pub acl: Vec<AclItem>,
+    pub privileges: Vec<PrivilegeState>,
+}
+
+/// One parsed `aclitem`, e.g. `alice=arw*/postgres`
+#[derive(Debug, Clone, Serialize)]
+pub struct AclItem {
+    /// Empty for PUBLIC
+    pub grantee: String,
+    pub grantor: String,
+    pub privileges: Vec<String>,
+    pub grantable: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrivilegeChange {
+    pub grant: bool,
+    pub kind: PrivilegeObjectKind,
+    pub schema: String,
+    /// Ignored for schemas
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Option<String>,
+    pub privileges: Vec<String>,
+    pub role: String,
+    #[serde(default)]
+    pub with_grant_option: bool,
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+pub async fn get_roles(client: &Arc<Client>) -> Result<Vec<RoleInfo>> {
+    let rows = client
+        .query(
+            "SELECT
+                r.oid,
+                r.rolname,
+                r.rolcanlogin,
+                r.rolsuper,
+                r.rolcreatedb,
+                r.rolcreaterole,
+                r.rolinherit,
+                r.rolreplication,
+                r.rolbypassrls,
+                r.rolconnlimit,
+                r.rolvaliduntil::text,
+                ARRAY(
+                    SELECT g.rolname
+                    FROM pg_auth_members m
+                    JOIN pg_roles g ON g.oid = m.roleid
+                    WHERE m.member = r.oid
+                    ORDER BY g.rolname
+                ) as member_of,
+                shobj_description(r.oid, 'pg_authid') as description,
+                r.rolname ~ '^pg_' as is_system
+             FROM pg_roles r
+             ORDER BY is_system, r.rolname",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| RoleInfo {
+            oid: row.get(0),
+            name: row.get(1),
+            can_login: row.get(2),
+            superuser: row.get(3),
+            create_db: row.get(4),
+            create_role: row.get(5),
+            inherit: row.get(6),
+            replication: row.get(7),
+            bypass_rls: row.get(8),
+            connection_limit: row.get(9),
+            valid_until: row.get(10),
+            member_of: row.get(11),
+            description: row.get(12),
+            is_system: row.get(13),
+        })
+        .collect())
+}
+
+pub async fn get_role_memberships(client: &Arc<Client>) -> Result<Vec<RoleMembership>> {
+    let rows = client
+        .query(
+            "SELECT r.rolname, m.rolname, am.admin_option, g.rolname
+             FROM pg_auth_members am
+             JOIN pg_roles r ON r.oid = am.roleid
+             JOIN pg_roles m ON m.oid = am.member
+             LEFT JOIN pg_roles g ON g.oid = am.grantor
+             ORDER BY r.rolname, m.rolname",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| RoleMembership {
+            role: row.get(0),
+            member: row.get(1),
+            admin_option: row.get(2),
+            grantor: row.get(3),
+        })
+        .collect())
+}
+
+fn acl_privilege(c: char) -> Option<&'static str> {
+    Some(match c {
+        'r' => "SELECT",
+        'w' => "UPDATE",
+        'a' => "INSERT",
+        'd' => "DELETE",
+        'D' => "TRUNCATE",
+        'x' => "REFERENCES",
+        't' => "TRIGGER",
+        'X' => "EXECUTE",
+        'U' => "USAGE",
+        'C' => "CREATE",
+        'c' => "CONNECT",
+        'T' => "TEMPORARY",
+        'm' => "MAINTAIN",
+        's' => "SET",
+        'A' => "ALTER SYSTEM",
+        _ => return None,
+    })
+}
+
+/// Read a role name from the start of `s`, which may be double-quoted
+fn acl_role(s: &str) -> (String, &str) {
+    let Some(rest) = s.strip_prefix('"') else {
+        let end = s.find(['=', '/']).unwrap_or(s.len());
+        return (s[..end].to_string(), &s[end..]);
+    };
+    let mut name = String::new();
+    let mut chars = rest.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '"' {
+            if matches!(chars.peek(), Some((_, '"'))) {
+                chars.next();
+                name.push('"');
+            } else {
+                return (name, &rest[i + 1..]);
+            }
+        } else {
+            name.push(c);
+        }
+    }
+    (name, "")
+}
+
+/// Parse the text form of an aclitem: `grantee=privileges/grantor`
+pub fn parse_acl_item(item: &str) -> Option<AclItem> {
+    let (grantee, rest) = acl_role(item);
+    let rest = rest.strip_prefix('=')?;
+    let (privs, grantor) = rest.split_once('/')?;
+    let (grantor, _) = acl_role(grantor);
+
+    let mut privileges = Vec::new();
+    let mut grantable = Vec::new();
+    let mut chars = privs.chars().peekable();
+    while let Some(c) = chars.next() {
+        let privilege = acl_privilege(c)?.to_string();
+        if chars.next_if_eq(&'*').is_some() {
+            grantable.push(privilege.clone());
+        }
+        privileges.push(privilege);
+    }
+    Some(AclItem {
+        grantee,
+        grantor,
+        privileges,
+        grantable,
+    })
+}
+
+/// Effective and directly granted privileges of `role` on every table, view,
+/// sequence and function in `schema`, and on the schema itself. Objects without
+/// an ACL get the built-in default, so owners show their implicit grants.
+pub async fn get_privilege_matrix(
+    client: &Arc<Client>,
+    role: &str,
+    schema: &str,
+) -> Result<Vec<ObjectPrivileges>> {
+    let checks = |kind: PrivilegeObjectKind, func: &str, oid: &str| {
+        let calls: Vec<String> = kind
+            .privileges()
+            .iter()
+            .map(|p| format!("{}($1::name, {}, '{}')", func, oid, p))
+            .collect();
+        format!("ARRAY[{}]", calls.join(", "))
+    };
+    let sql = format!(
+        "SELECT 'schema', n.nspname, n.nspname, NULL::text, pg_get_userbyid(n.nspowner),
+                COALESCE(n.nspacl, acldefault('n', n.nspowner))::text[], {schema_checks}
+         FROM pg_namespace n
+         WHERE n.nspname = $2
+         UNION ALL
+         SELECT 'table', n.nspname, c.relname, NULL, pg_get_userbyid(c.relowner),
+                COALESCE(c.relacl, acldefault('r', c.relowner))::text[], {table_checks}
+         FROM pg_class c
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE n.nspname = $2 AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+         UNION ALL
+         SELECT 'sequence', n.nspname, c.relname, NULL, pg_get_userbyid(c.relowner),
+                COALESCE(c.relacl, acldefault('s', c.relowner))::text[], {sequence_checks}
+         FROM pg_class c
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE n.nspname = $2 AND c.relkind = 'S'
+         UNION ALL
+         SELECT 'function', n.nspname, p.proname, pg_get_function_identity_arguments(p.oid),
+                pg_get_userbyid(p.proowner),
+                COALESCE(p.proacl, acldefault('f', p.proowner))::text[], {function_checks}
+         FROM pg_proc p
+         JOIN pg_namespace n ON n.oid = p.pronamespace
+         WHERE n.nspname = $2 AND p.prokind <> 'a'
+         ORDER BY 3, 4",
+        schema_checks = checks(PrivilegeObjectKind::Schema, "has_schema_privilege", "n.oid"),
+        table_checks = checks(PrivilegeObjectKind::Table, "has_table_privilege", "c.oid"),
+        sequence_checks = checks(PrivilegeObjectKind::Sequence, "has_sequence_privilege", "c.oid"),
+        function_checks = checks(PrivilegeObjectKind::Function, "has_function_privilege", "p.oid"),
+    );
+    let rows = client.query(&sql, &[&role, &schema]).await?;
+
+    let mut objects = Vec::with_capacity(rows.len());
+    for row in rows {
+        let kind = match row.get::<_, &str>(0) {
+            "schema" => PrivilegeObjectKind::Schema,
+            "table" => PrivilegeObjectKind::Table,
+            "sequence" => PrivilegeObjectKind::Sequence,
+            _ => PrivilegeObjectKind::Function,
+        };
+        let acl: Vec<AclItem> = row
+            .get::<_, Vec<String>>(5)
+            .iter()
+            .filter_map(|item| parse_acl_item(item))
+            .collect();
+        let effective: Vec<bool> = row.get(6);
+        let own = acl.iter().find(|item| item.grantee == role);
+        let privileges = kind
+            .privileges()
+            .iter()
+            .zip(effective)
+            .map(|(p, effective)| PrivilegeState {
+                privilege: p.to_string(),
+                effective,
+                direct: own.is_some_and(|item| item.privileges.iter().any(|g| g == p)),
+                grantable: own.is_some_and(|item| item.grantable.iter().any(|g| g == p)),
+            })
+            .collect();
+        objects.push(ObjectPrivileges {
+            kind,
+            schema: row.get(1),
+            name: row.get(2),
+            arguments: row.get(3),
+            owner: row.get(4),
+            acl,
+            privileges,
+        });
+    }
+    // Schema first, then tables, sequences and functions, each by name
+    objects.sort_by_key(|o| o.kind);
+    Ok(objects)
+}
+
+/// The canonical signature of the function a change names, e.g.
+/// `s.f(integer, text)`. The argument list is resolved by the server rather
+/// than copied into the statement.
+async fn function_signature(client: &Arc<Client>, change: &PrivilegeChange) -> Result<String> {
+    let name = format!(
+        "{}.{}({})",
+        quote_ident(&change.schema),
+        quote_ident(&change.name),
+        change.arguments.as_deref().unwrap_or("")
+    );
+    let row = client
+        .query_one("SELECT to_regprocedure($1)::oid::regprocedure::text", &[&name])
+        .await
+        .map_err(|e| anyhow::anyhow!(super::db_error_message(&e)))?;
+    row.get::<_, Option<String>>(0)
+        .ok_or_else(|| anyhow::anyhow!("Function not found: {}", name))
+}
+
+/// GRANT or REVOKE statement for one change. Privileges are checked against
+/// the ones the object kind supports and function signatures are resolved on
+/// the server, so nothing unquoted reaches the SQL.
+pub async fn privilege_statement(client: &Arc<Client>, change: &PrivilegeChange) -> Result<String> {
+    let allowed = change.kind.privileges();
+    let mut privileges = Vec::new();
+    for p in &change.privileges {
+        let p = p.trim().to_ascii_uppercase();
+        if p == "ALL" || p == "ALL PRIVILEGES" {
+            privileges = vec!["ALL PRIVILEGES".to_string()];
+            break;
+        }
+        if !allowed.contains(&p.as_str()) {
+            anyhow::bail!("{} is not a privilege on a {}", p, change.kind.keyword().to_lowercase());
+        }
+        privileges.push(p);
+    }
+    if privileges.is_empty() {
+        anyhow::bail!("No privileges given");
+    }
+
+    let object = match change.kind {
+        PrivilegeObjectKind::Schema => quote_ident(&change.schema),
+        PrivilegeObjectKind::Function => function_signature(client, change).await?,
+        _ => format!("{}.{}", quote_ident(&change.schema), quote_ident(&change.name)),
+    };
+    let role = if change.role.eq_ignore_ascii_case("public") {
+        "PUBLIC".to_string()
+    } else {
+        quote_ident(&change.role)
+    };
+
+    let privileges = privileges.join(", ");
+    let keyword = change.kind.keyword();
+    Ok(if change.grant {
+        format!(
+            "GRANT {} ON {} {} TO {}{}",
+            privileges,
+            keyword,
+            object,
+            role,
+            if change.with_grant_option { " WITH GRANT OPTION" } else { "" }
+        )
+    } else {
+        format!(
+            "REVOKE {}{} ON {} {} FROM {}{}",
+            if change.with_grant_option { "GRANT OPTION FOR " } else { "" },
+            privileges,
+            keyword,
+            object,
+            role,
+            if change.cascade { " CASCADE" } else { "" }
+        )
+    })
+}
+
+/// Run the statements for `changes` as one implicit transaction
+pub async fn apply_privilege_changes(client: &Arc<Client>, changes: &[PrivilegeChange]) -> Result<Vec<String>> {
+    let mut statements = Vec::with_capacity(changes.len());
+    for change in changes {
+        statements.push(privilege_statement(client, change).await?);
+    }
+    if !statements.is_empty() {
+        client
+            .batch_execute(&statements.join(";\n"))
+            .await
+            .map_err(|e| anyhow::anyhow!(super::db_error_message(&e)))?;
+    }
+    Ok(statements)
+}
@@ -0,0 +1,273 @@
+use super::query::{limited_sql, pg_type_to_string, truncate_text, PROGRESS_INTERVAL};
+use super::{quote_literal, ColumnDef, QueryLimits, QueryResult};
+use anyhow::Result;
+use futures_util::{pin_mut, TryStreamExt};
+use sqlparser::ast::{Expr, Ident, SelectItem, SetExpr, Statement, TableFactor};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio_postgres::types::Type;
+use tokio_postgres::{Client, SimpleQueryMessage};
+
+/// Run `sql` over the simple query protocol. Several statements may be sent at
+/// once; the last result set is returned. Values arrive as text, so column
+/// types are only known when the query reads plain columns from one table.
+pub(super) async fn run_simple_query<F>(
+    client: &Arc<Client>,
+    sql: &str,
+    limits: QueryLimits,
+    mut on_progress: F,
+) -> Result<QueryResult>
+where
+    F: FnMut(usize),
+{
+    let start = Instant::now();
+    let (effective_sql, row_limit) = limited_sql(sql, limits);
+
+    let stream = client.simple_query_raw(&effective_sql).await?;
+    pin_mut!(stream);
+
+    let mut names: Vec<String> = Vec::new();
+    let mut rows: Vec<Vec<Option<String>>> = Vec::new();
+    let mut last_report = Instant::now();
+    while let Some(message) = stream.try_next().await? {
+        match message {
+            SimpleQueryMessage::RowDescription(columns) => {
+                names = columns.iter().map(|c| c.name().to_string()).collect();
+                rows.clear();
+            }
+            SimpleQueryMessage::Row(row) => {
+                rows.push((0..row.len()).map(|i| row.get(i).map(str::to_string)).collect());
+                if last_report.elapsed() >= PROGRESS_INTERVAL {
+                    on_progress(rows.len());
+                    last_report = Instant::now();
+                }
+            }
+            _ => {}
+        }
+    }
+    let execution_time_ms = start.elapsed().as_millis();
+
+    let truncated = matches!(row_limit, Some(limit) if rows.len() > limit);
+    if let Some(limit) = row_limit {
+        rows.truncate(limit);
+    }
+
+    let mut columns: Vec<ColumnDef> = names
+        .into_iter()
+        .map(|name| ColumnDef {
+            name,
+            data_type: "text".into(),
+            table_oid: None,
+            column_id: None,
+            source_schema: None,
+            source_table: None,
+            source_column: None,
+            is_nullable: None,
+            is_primary_key: false,
+            source_key_complete: false,
+        })
+        .collect();
+    // Best-effort, like provenance on the extended protocol
+    let _ = resolve_simple_columns(client, sql, &mut columns).await;
+
+    let mut result_rows = Vec::with_capacity(rows.len());
+    let mut truncated_cells = Vec::new();
+    let byte_budget = limits.cell_byte_budget as usize;
+    for (r, row) in rows.into_iter().enumerate() {
+        let mut values = Vec::with_capacity(columns.len());
+        for (i, (text, col)) in row.into_iter().zip(&columns).enumerate() {
+            let (value, cut) = text_to_json(text, &col.data_type, byte_budget);
+            if cut {
+                truncated_cells.push([r, i]);
+            }
+            values.push(value);
+        }
+        result_rows.push(values);
+    }
+
+    let row_count = result_rows.len();
+    Ok(QueryResult {
+        columns,
+        rows: result_rows,
+        row_count,
+        execution_time_ms,
+        command_tag: format!("SELECT {}", row_count),
+        truncated,
+        truncated_cells,
+        footer: Vec::new(),
+    })
+}
+
+/// Convert a text-format value to JSON the way the extended protocol path
+/// would for the same type.
+fn text_to_json(text: Option<String>, data_type: &str, byte_budget: usize) -> (serde_json::Value, bool) {
+    let Some(mut text) = text else {
+        return (serde_json::Value::Null, false);
+    };
+    let value = match data_type {
+        "boolean" => serde_json::Value::Bool(text == "t"),
+        "smallint" | "integer" | "bigint" => match text.parse::<i64>() {
+            Ok(v) => serde_json::Value::Number(v.into()),
+            Err(_) => serde_json::Value::String(text),
+        },
+        "real" | "double precision" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        "json" | "jsonb" => serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)),
+        "bytea" => {
+            // Hex output: `\x` then two characters per byte
+            let budget = if byte_budget > 0 { 2 + byte_budget * 2 } else { 0 };
+            let cut = truncate_text(&mut text, budget);
+            return (serde_json::Value::String(text), cut);
+        }
+        _ => {
+            let cut = truncate_text(&mut text, byte_budget);
+            return (serde_json::Value::String(text), cut);
+        }
+    };
+    (value, false)
+}
+
+fn folded(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_lowercase(),
+    }
+}
+
+/// For `SELECT <columns and *> FROM <one table>`, the table name and a map from
+/// output column name to source column (None for computed columns).
+/// `*` maps every column to itself.
+fn single_table_projection(sql: &str) -> Option<(String, HashMap<String, Option<String>>, bool)> {
+    let statements = Parser::parse_sql(&PostgreSqlDialect {}, sql).ok()?;
+    let [Statement::Query(query)] = statements.as_slice() else {
+        return None;
+    };
+    if query.with.is_some() {
+        return None;
+    }
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return None;
+    };
+    let [from] = select.from.as_slice() else {
+        return None;
+    };
+    if !from.joins.is_empty() {
+        return None;
+    }
+    let TableFactor::Table { name, .. } = &from.relation else {
+        return None;
+    };
+
+    let mut sources = HashMap::new();
+    let mut wildcard = false;
+    let mut unnamed_computed = false;
+    for item in &select.projection {
+        let (expr, alias) = match item {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                wildcard = true;
+                continue;
+            }
+            SelectItem::UnnamedExpr(expr) => (expr, None),
+            SelectItem::ExprWithAlias { expr, alias } => (expr, Some(alias)),
+        };
+        let column = match expr {
+            Expr::Identifier(ident) => Some(ident),
+            Expr::CompoundIdentifier(parts) => parts.last(),
+            _ => None,
+        };
+        match (column, alias) {
+            (Some(column), alias) => {
+                sources.insert(folded(alias.unwrap_or(column)), Some(folded(column)));
+            }
+            (None, Some(alias)) => {
+                sources.insert(folded(alias), None);
+            }
+            // The server picks the name, which could shadow a column
+            (None, None) => unnamed_computed = true,
+        }
+    }
+    if wildcard && unnamed_computed {
+        return None;
+    }
+    Some((name.to_string(), sources, wildcard))
+}
+
+/// Fill in types and provenance for single-table queries from the catalog.
+/// The lookup itself also goes over the simple protocol.
+async fn resolve_simple_columns(client: &Client, sql: &str, columns: &mut [ColumnDef]) -> Result<()> {
+    let Some((table, sources, wildcard)) = single_table_projection(sql) else {
+        return Ok(());
+    };
+    let lookup = format!(
+        "SELECT
+            a.attname,
+            a.atttypid,
+            t.typname,
+            a.attnum,
+            c.oid,
+            n.nspname,
+            c.relname,
+            NOT a.attnotnull,
+            COALESCE(a.attnum = ANY(pk.conkey), false),
+            COALESCE(array_length(pk.conkey, 1), 0)
+         FROM pg_class c
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+         JOIN pg_type t ON t.oid = a.atttypid
+         LEFT JOIN pg_constraint pk ON pk.conrelid = c.oid AND pk.contype = 'p'
+         WHERE c.oid = to_regclass({})",
+        quote_literal(&table)
+    );
+
+    let mut attributes: HashMap<String, Vec<String>> = HashMap::new();
+    for message in client.simple_query(&lookup).await? {
+        if let SimpleQueryMessage::Row(row) = message {
+            let values: Vec<String> = (0..row.len())
+                .map(|i| row.get(i).unwrap_or_default().to_string())
+                .collect();
+            attributes.insert(values[0].clone(), values);
+        }
+    }
+
+    let mut pk_present: HashSet<String> = HashSet::new();
+    let mut pk_size = 0usize;
+    for col in columns.iter_mut() {
+        let source = match sources.get(&col.name) {
+            Some(Some(source)) => source,
+            None if wildcard => &col.name,
+            _ => continue,
+        };
+        let Some(attr) = attributes.get(source) else {
+            continue;
+        };
+        col.data_type = attr[1]
+            .parse()
+            .ok()
+            .and_then(Type::from_oid)
+            .map(|t| pg_type_to_string(&t))
+            .unwrap_or_else(|| attr[2].clone());
+        col.column_id = attr[3].parse().ok();
+        col.table_oid = attr[4].parse().ok();
+        col.source_schema = Some(attr[5].clone());
+        col.source_table = Some(attr[6].clone());
+        col.source_column = Some(attr[0].clone());
+        col.is_nullable = Some(attr[7] == "t");
+        col.is_primary_key = attr[8] == "t";
+        pk_size = attr[9].parse().unwrap_or(0);
+        if col.is_primary_key {
+            pk_present.insert(attr[0].clone());
+        }
+    }
+    let key_complete = pk_size > 0 && pk_present.len() == pk_size;
+    for col in columns.iter_mut().filter(|c| c.table_oid.is_some()) {
+        col.source_key_complete = key_complete;
+    }
+    Ok(())
+}
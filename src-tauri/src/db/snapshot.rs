@@ -0,0 +1,642 @@
+use super::ddl::{create_policy_sql, create_sequence_sql, type_ddl};
+use super::{get_function_definition, quote_ident};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+/// The parts of a catalog that schema comparison looks at. Every object is
+/// kept with the definition Postgres prints for it, so two snapshots taken
+/// from servers with the same search_path compare textually.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    pub schemas: Vec<String>,
+    pub tables: Vec<TableSnapshot>,
+    pub views: Vec<ViewSnapshot>,
+    pub sequences: Vec<SequenceSnapshot>,
+    pub functions: Vec<FunctionSnapshot>,
+    pub types: Vec<TypeSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableSnapshot {
+    pub schema: String,
+    pub name: String,
+    /// `PARTITION BY` clause of a partitioned table
+    pub partition_key: Option<String>,
+    /// Parent of a partition, as it is written in SQL
+    pub partition_of: Option<String>,
+    pub partition_bound: Option<String>,
+    /// Columns defined on the table itself; inherited ones belong to the parent
+    pub columns: Vec<ColumnSnapshot>,
+    pub constraints: Vec<ConstraintSnapshot>,
+    pub indexes: Vec<NamedDefinition>,
+    pub triggers: Vec<NamedDefinition>,
+    pub policies: Vec<NamedDefinition>,
+    pub rls_enabled: bool,
+    /// `grantee:PRIVILEGE`, with a trailing `*` for WITH GRANT OPTION. The
+    /// owner's own privileges are left out.
+    pub grants: Vec<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSnapshot {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    /// "ALWAYS" or "BY DEFAULT"
+    pub identity: Option<String>,
+    /// Expression of a generated column
+    pub generated: Option<String>,
+    /// "STORED" or "VIRTUAL"
+    pub generated_kind: Option<String>,
+    pub collation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConstraintSnapshot {
+    pub name: String,
+    /// "p", "u", "f", "c" or "x", as in pg_constraint.contype
+    pub kind: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedDefinition {
+    pub name: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewSnapshot {
+    pub schema: String,
+    pub name: String,
+    pub materialized: bool,
+    /// Storage parameters, e.g. `security_barrier=true` or `check_option=local`
+    #[serde(default)]
+    pub options: Vec<String>,
+    pub definition: String,
+    /// Tables and views the query reads, as `schema.name`
+    pub depends_on: Vec<String>,
+    pub indexes: Vec<NamedDefinition>,
+    pub grants: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SequenceSnapshot {
+    pub schema: String,
+    pub name: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionSnapshot {
+    pub schema: String,
+    pub name: String,
+    /// FUNCTION, PROCEDURE or AGGREGATE
+    pub kind: String,
+    pub identity_arguments: String,
+    pub return_type: Option<String>,
+    pub definition: String,
+    pub grants: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeSnapshot {
+    pub schema: String,
+    pub name: String,
+    /// enum, domain, composite or range
+    pub kind: String,
+    pub definition: String,
+    pub enum_labels: Vec<String>,
+}
+
+impl SchemaSnapshot {
+    /// SHA-256 of the serialized snapshot; equal catalogs hash equally
+    pub fn hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        Sha256::digest(&json).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.tables.len() + self.views.len() + self.sequences.len() + self.functions.len() + self.types.len()
+    }
+}
+
+impl TableSnapshot {
+    pub fn key(&self) -> String {
+        format!("{}.{}", self.schema, self.name)
+    }
+}
+
+impl ViewSnapshot {
+    pub fn key(&self) -> String {
+        format!("{}.{}", self.schema, self.name)
+    }
+}
+
+impl FunctionSnapshot {
+    pub fn key(&self) -> String {
+        format!("{}.{}({})", self.schema, self.name, self.identity_arguments)
+    }
+}
+
+/// Objects created by an extension are the extension's business
+fn not_from_extension(oid: &str) -> String {
+    format!(
+        "NOT EXISTS (SELECT 1 FROM pg_depend ext WHERE ext.objid = {} AND ext.deptype = 'e')",
+        oid
+    )
+}
+
+/// Snapshot `schemas`, or every user schema when the list is empty. Most kinds
+/// of object are read with one catalog query across all schemas; types and
+/// aggregates are rendered one at a time, so their cost grows with their count.
+pub async fn take_snapshot(client: &Arc<Client>, schemas: &[String]) -> Result<SchemaSnapshot> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT n.nspname
+                 FROM pg_namespace n
+                 WHERE n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'
+                   AND (cardinality($1::text[]) = 0 OR n.nspname = ANY($1))
+                   AND {}
+                 ORDER BY n.nspname",
+                not_from_extension("n.oid")
+            ),
+            &[&schemas],
+        )
+        .await?;
+    let schemas: Vec<String> = rows.iter().map(|r| r.get(0)).collect();
+
+    let tables = snapshot_tables(client, &schemas).await?;
+    let views = snapshot_views(client, &schemas).await?;
+    let sequences = snapshot_sequences(client, &schemas).await?;
+    let functions = snapshot_functions(client, &schemas).await?;
+    let types = snapshot_types(client, &schemas).await?;
+    Ok(SchemaSnapshot {
+        schemas,
+        tables,
+        views,
+        sequences,
+        functions,
+        types,
+    })
+}
+
+/// aclexplode over an object's ACL (or the default when it has none),
+/// skipping the owner
+fn grants_expr(acl: &str, kind: &str, owner: &str) -> String {
+    format!(
+        "ARRAY(
+            SELECT COALESCE(gr.rolname, 'PUBLIC') || ':' || g.privilege_type
+                || CASE WHEN g.is_grantable THEN '*' ELSE '' END
+            FROM aclexplode(COALESCE({acl}, acldefault('{kind}', {owner}))) g
+            LEFT JOIN pg_roles gr ON gr.oid = g.grantee
+            WHERE g.grantee <> {owner}
+            ORDER BY 1
+        )"
+    )
+}
+
+async fn snapshot_tables(client: &Client, schemas: &[String]) -> Result<Vec<TableSnapshot>> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT
+                    n.nspname,
+                    c.relname,
+                    CASE WHEN c.relkind = 'p' THEN pg_get_partkeydef(c.oid) END,
+                    CASE WHEN c.relispartition THEN
+                        (SELECT i.inhparent::regclass::text FROM pg_inherits i WHERE i.inhrelid = c.oid)
+                    END,
+                    pg_get_expr(c.relpartbound, c.oid),
+                    c.relrowsecurity,
+                    {grants},
+                    obj_description(c.oid, 'pg_class')
+                 FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE c.relkind IN ('r', 'p') AND n.nspname = ANY($1) AND {ext}
+                 ORDER BY n.nspname, c.relname",
+                grants = grants_expr("c.relacl", "r", "c.relowner"),
+                ext = not_from_extension("c.oid"),
+            ),
+            &[&schemas],
+        )
+        .await?;
+    let mut tables: Vec<TableSnapshot> = rows
+        .iter()
+        .map(|row| TableSnapshot {
+            schema: row.get(0),
+            name: row.get(1),
+            partition_key: row.get(2),
+            partition_of: row.get(3),
+            partition_bound: row.get(4),
+            columns: Vec::new(),
+            constraints: Vec::new(),
+            indexes: Vec::new(),
+            triggers: Vec::new(),
+            policies: Vec::new(),
+            rls_enabled: row.get(5),
+            grants: row.get(6),
+            comment: row.get(7),
+        })
+        .collect();
+    let index: HashMap<(String, String), usize> = tables
+        .iter()
+        .enumerate()
+        .map(|(i, t)| ((t.schema.clone(), t.name.clone()), i))
+        .collect();
+    let table_of = |row: &tokio_postgres::Row| index.get(&(row.get(0), row.get(1))).copied();
+
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                a.attname,
+                format_type(a.atttypid, a.atttypmod),
+                NOT a.attnotnull,
+                CASE WHEN a.attgenerated = '' THEN pg_get_expr(d.adbin, d.adrelid) END,
+                CASE a.attidentity WHEN 'a' THEN 'ALWAYS' WHEN 'd' THEN 'BY DEFAULT' END,
+                CASE WHEN a.attgenerated <> '' THEN pg_get_expr(d.adbin, d.adrelid) END,
+                CASE a.attgenerated WHEN 's' THEN 'STORED' WHEN 'v' THEN 'VIRTUAL' END,
+                CASE WHEN a.attcollation <> t.typcollation
+                    THEN quote_ident(cn.nspname) || '.' || quote_ident(co.collname)
+                END
+             FROM pg_attribute a
+             JOIN pg_class c ON c.oid = a.attrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_type t ON t.oid = a.atttypid
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             LEFT JOIN pg_collation co ON co.oid = a.attcollation
+             LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+             WHERE c.relkind IN ('r', 'p') AND n.nspname = ANY($1)
+               AND a.attnum > 0 AND NOT a.attisdropped AND a.attislocal
+             ORDER BY n.nspname, c.relname, a.attnum",
+            &[&schemas],
+        )
+        .await?;
+    for row in &rows {
+        if let Some(i) = table_of(row) {
+            tables[i].columns.push(ColumnSnapshot {
+                name: row.get(2),
+                data_type: row.get(3),
+                nullable: row.get(4),
+                default: row.get(5),
+                identity: row.get(6),
+                generated: row.get(7),
+                generated_kind: row.get(8),
+                collation: row.get(9),
+            });
+        }
+    }
+
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                con.conname,
+                con.contype::text,
+                pg_get_constraintdef(con.oid, true)
+             FROM pg_constraint con
+             JOIN pg_class c ON c.oid = con.conrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE con.contype IN ('p', 'u', 'f', 'c', 'x')
+               AND con.conislocal AND con.conparentid = 0
+               AND n.nspname = ANY($1)
+             ORDER BY n.nspname, c.relname, con.conname",
+            &[&schemas],
+        )
+        .await?;
+    for row in &rows {
+        if let Some(i) = table_of(row) {
+            tables[i].constraints.push(ConstraintSnapshot {
+                name: row.get(2),
+                kind: row.get(3),
+                definition: row.get(4),
+            });
+        }
+    }
+
+    for (schema, table, index_name, definition) in snapshot_indexes(client, schemas).await? {
+        if let Some(&i) = index.get(&(schema, table)) {
+            tables[i].indexes.push(NamedDefinition {
+                name: index_name,
+                definition,
+            });
+        }
+    }
+
+    let rows = client
+        .query(
+            "SELECT n.nspname, c.relname, t.tgname, pg_get_triggerdef(t.oid, true)
+             FROM pg_trigger t
+             JOIN pg_class c ON c.oid = t.tgrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE NOT t.tgisinternal AND t.tgparentid = 0 AND n.nspname = ANY($1)
+             ORDER BY n.nspname, c.relname, t.tgname",
+            &[&schemas],
+        )
+        .await?;
+    for row in &rows {
+        if let Some(i) = table_of(row) {
+            tables[i].triggers.push(NamedDefinition {
+                name: row.get(2),
+                definition: row.get(3),
+            });
+        }
+    }
+
+    let rows = client
+        .query(
+            "SELECT
+                n.nspname,
+                c.relname,
+                pol.polname,
+                pol.polpermissive,
+                CASE pol.polcmd
+                    WHEN 'r' THEN 'SELECT'
+                    WHEN 'a' THEN 'INSERT'
+                    WHEN 'w' THEN 'UPDATE'
+                    WHEN 'd' THEN 'DELETE'
+                    ELSE 'ALL'
+                END,
+                COALESCE(
+                    (SELECT array_agg(quote_ident(r.rolname) ORDER BY r.rolname)
+                     FROM unnest(pol.polroles) AS role_oid
+                     JOIN pg_roles r ON r.oid = role_oid),
+                    ARRAY['PUBLIC']::text[]
+                ),
+                pg_get_expr(pol.polqual, pol.polrelid, true),
+                pg_get_expr(pol.polwithcheck, pol.polrelid, true)
+             FROM pg_policy pol
+             JOIN pg_class c ON c.oid = pol.polrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = ANY($1)
+             ORDER BY n.nspname, c.relname, pol.polname",
+            &[&schemas],
+        )
+        .await?;
+    for row in &rows {
+        if let Some(i) = table_of(row) {
+            let table = format!("{}.{}", quote_ident(row.get(0)), quote_ident(row.get(1)));
+            let name: String = row.get(2);
+            let definition = create_policy_sql(
+                &name,
+                &table,
+                row.get(3),
+                row.get(4),
+                &row.get::<_, Vec<String>>(5),
+                row.get(6),
+                row.get(7),
+            );
+            tables[i].policies.push(NamedDefinition { name, definition });
+        }
+    }
+    Ok(tables)
+}
+
+/// Indexes on tables and materialized views that are not created by a
+/// constraint or attached to a partitioned parent's index
+async fn snapshot_indexes(
+    client: &Client,
+    schemas: &[String],
+) -> Result<Vec<(String, String, String, String)>> {
+    let rows = client
+        .query(
+            "SELECT n.nspname, c.relname, ic.relname,
+                CASE WHEN c.relkind = 'p'
+                    THEN regexp_replace(pg_get_indexdef(i.indexrelid), ' ON ONLY ', ' ON ')
+                    ELSE pg_get_indexdef(i.indexrelid)
+                END
+             FROM pg_index i
+             JOIN pg_class c ON c.oid = i.indrelid
+             JOIN pg_class ic ON ic.oid = i.indexrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = ANY($1)
+               AND NOT EXISTS (
+                   SELECT 1 FROM pg_constraint con
+                   WHERE con.conrelid = i.indrelid
+                     AND con.conindid = i.indexrelid
+                     AND con.contype IN ('p', 'u', 'x')
+               )
+               AND NOT EXISTS (SELECT 1 FROM pg_inherits inh WHERE inh.inhrelid = i.indexrelid)
+             ORDER BY n.nspname, c.relname, ic.relname",
+            &[&schemas],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect())
+}
+
+async fn snapshot_views(client: &Client, schemas: &[String]) -> Result<Vec<ViewSnapshot>> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT
+                    n.nspname,
+                    c.relname,
+                    c.relkind = 'm',
+                    pg_get_viewdef(c.oid, true),
+                    ARRAY(
+                        SELECT DISTINCT rn.nspname || '.' || r.relname
+                        FROM pg_rewrite rw
+                        JOIN pg_depend d ON d.classid = 'pg_rewrite'::regclass AND d.objid = rw.oid
+                        JOIN pg_class r ON r.oid = d.refobjid
+                        JOIN pg_namespace rn ON rn.oid = r.relnamespace
+                        WHERE rw.ev_class = c.oid
+                          AND d.refclassid = 'pg_class'::regclass
+                          AND d.refobjid <> c.oid
+                        ORDER BY 1
+                    ),
+                    {grants},
+                    COALESCE(c.reloptions, ARRAY[]::text[])
+                 FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE c.relkind IN ('v', 'm') AND n.nspname = ANY($1) AND {ext}
+                 ORDER BY n.nspname, c.relname",
+                grants = grants_expr("c.relacl", "r", "c.relowner"),
+                ext = not_from_extension("c.oid"),
+            ),
+            &[&schemas],
+        )
+        .await?;
+    let mut views: Vec<ViewSnapshot> = rows
+        .iter()
+        .map(|row| {
+            let definition: String = row.get(3);
+            ViewSnapshot {
+                schema: row.get(0),
+                name: row.get(1),
+                materialized: row.get(2),
+                options: row.get(6),
+                definition: definition.trim().trim_end_matches(';').to_string(),
+                depends_on: row.get(4),
+                indexes: Vec::new(),
+                grants: row.get(5),
+            }
+        })
+        .collect();
+    for (schema, view, index_name, definition) in snapshot_indexes(client, schemas).await? {
+        if let Some(v) = views.iter_mut().find(|v| v.schema == schema && v.name == view) {
+            v.indexes.push(NamedDefinition {
+                name: index_name,
+                definition,
+            });
+        }
+    }
+    Ok(views)
+}
+
+/// Standalone and serial sequences; identity sequences belong to their column
+async fn snapshot_sequences(client: &Client, schemas: &[String]) -> Result<Vec<SequenceSnapshot>> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT
+                    n.nspname,
+                    c.relname,
+                    format_type(s.seqtypid, NULL),
+                    s.seqstart,
+                    s.seqincrement,
+                    s.seqmin,
+                    s.seqmax,
+                    s.seqcache,
+                    s.seqcycle
+                 FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 JOIN pg_sequence s ON s.seqrelid = c.oid
+                 WHERE n.nspname = ANY($1) AND {ext}
+                   AND NOT EXISTS (
+                       SELECT 1 FROM pg_depend d
+                       WHERE d.classid = 'pg_class'::regclass AND d.objid = c.oid AND d.deptype = 'i'
+                   )
+                 ORDER BY n.nspname, c.relname",
+                ext = not_from_extension("c.oid"),
+            ),
+            &[&schemas],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let schema: String = row.get(0);
+            let name: String = row.get(1);
+            let definition = create_sequence_sql(
+                &format!("{}.{}", quote_ident(&schema), quote_ident(&name)),
+                row.get(2),
+                row.get(3),
+                row.get(4),
+                row.get(5),
+                row.get(6),
+                row.get(7),
+                row.get(8),
+            );
+            SequenceSnapshot {
+                schema,
+                name,
+                definition,
+            }
+        })
+        .collect())
+}
+
+async fn snapshot_functions(client: &Arc<Client>, schemas: &[String]) -> Result<Vec<FunctionSnapshot>> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT
+                    p.oid,
+                    n.nspname,
+                    p.proname,
+                    CASE p.prokind
+                        WHEN 'p' THEN 'PROCEDURE'
+                        WHEN 'a' THEN 'AGGREGATE'
+                        ELSE 'FUNCTION'
+                    END,
+                    pg_get_function_identity_arguments(p.oid),
+                    pg_get_function_result(p.oid),
+                    CASE WHEN p.prokind <> 'a' THEN pg_get_functiondef(p.oid) END,
+                    {grants}
+                 FROM pg_proc p
+                 JOIN pg_namespace n ON n.oid = p.pronamespace
+                 WHERE n.nspname = ANY($1) AND {ext}
+                 ORDER BY n.nspname, p.proname, 5",
+                grants = grants_expr("p.proacl", "f", "p.proowner"),
+                ext = not_from_extension("p.oid"),
+            ),
+            &[&schemas],
+        )
+        .await?;
+    let mut functions = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let definition = match row.get::<_, Option<String>>(6) {
+            Some(definition) => definition,
+            None => get_function_definition(client, row.get(0)).await?,
+        };
+        functions.push(FunctionSnapshot {
+            schema: row.get(1),
+            name: row.get(2),
+            kind: row.get(3),
+            identity_arguments: row.get(4),
+            return_type: row.get(5),
+            definition: definition.trim_end().trim_end_matches(';').to_string(),
+            grants: row.get(7),
+        });
+    }
+    Ok(functions)
+}
+
+async fn snapshot_types(client: &Client, schemas: &[String]) -> Result<Vec<TypeSnapshot>> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT
+                    n.nspname,
+                    t.typname,
+                    CASE t.typtype
+                        WHEN 'e' THEN 'enum'
+                        WHEN 'd' THEN 'domain'
+                        WHEN 'c' THEN 'composite'
+                        ELSE 'range'
+                    END,
+                    ARRAY(SELECT e.enumlabel FROM pg_enum e WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder)
+                 FROM pg_type t
+                 JOIN pg_namespace n ON n.oid = t.typnamespace
+                 LEFT JOIN pg_class c ON c.oid = t.typrelid
+                 WHERE n.nspname = ANY($1) AND {ext}
+                   AND (t.typtype IN ('e', 'd', 'r') OR (t.typtype = 'c' AND c.relkind = 'c'))
+                 ORDER BY n.nspname, t.typname",
+                ext = not_from_extension("t.oid"),
+            ),
+            &[&schemas],
+        )
+        .await?;
+    let mut types = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let schema: String = row.get(0);
+        let name: String = row.get(1);
+        // Only the CREATE statement; ownership is not compared
+        let definition = type_ddl(client, &schema, &name)
+            .await?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        types.push(TypeSnapshot {
+            schema,
+            name,
+            kind: row.get(2),
+            definition,
+            enum_labels: row.get(3),
+        });
+    }
+    Ok(types)
+}
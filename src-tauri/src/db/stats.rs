@@ -0,0 +1,336 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio_postgres::Client;
+
+#[derive(Debug, Serialize)]
+pub struct TableStats {
+    pub schema: String,
+    pub name: String,
+    pub seq_scan: i64,
+    pub seq_tup_read: i64,
+    pub idx_scan: i64,
+    pub idx_tup_fetch: i64,
+    pub n_live_tup: i64,
+    pub n_dead_tup: i64,
+    /// Dead tuples as a share of all tuples, 0..1
+    pub dead_tuple_ratio: f64,
+    pub n_mod_since_analyze: i64,
+    pub last_vacuum: Option<DateTime<Utc>>,
+    pub last_autovacuum: Option<DateTime<Utc>>,
+    pub last_analyze: Option<DateTime<Utc>>,
+    pub last_autoanalyze: Option<DateTime<Utc>>,
+    pub vacuum_count: i64,
+    pub autovacuum_count: i64,
+    pub analyze_count: i64,
+    pub autoanalyze_count: i64,
+    /// Dead tuples are past the point where autovacuum picks the table up
+    pub vacuum_due: bool,
+    pub table_size_bytes: i64,
+    pub index_size_bytes: i64,
+    pub toast_size_bytes: i64,
+    pub total_size: String,
+    /// Heap space beyond what the live rows need at the table's fillfactor,
+    /// estimated from pg_stats; None when the table has no statistics
+    pub estimated_bloat_bytes: Option<i64>,
+    pub estimated_bloat_ratio: Option<f64>,
+    /// The same estimate summed over the table's btree indexes
+    pub estimated_index_bloat_bytes: Option<i64>,
+    /// Shares of block reads served from shared buffers, 0..1; None before any read
+    pub heap_hit_ratio: Option<f64>,
+    pub index_hit_ratio: Option<f64>,
+    pub toast_hit_ratio: Option<f64>,
+    /// Transactions since the table (or its TOAST table) was last frozen
+    pub xid_age: i64,
+    /// `xid_age` as a share of autovacuum_freeze_max_age, at which an
+    /// anti-wraparound vacuum is forced
+    pub wraparound_ratio: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableHealth {
+    pub name: String,
+    /// Higher is worse; 0 means nothing stood out
+    pub score: f64,
+    pub issues: Vec<String>,
+    pub stats: TableStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaHealthSummary {
+    pub schema: String,
+    pub table_count: usize,
+    pub total_dead_tuples: i64,
+    pub total_estimated_bloat_bytes: i64,
+    pub tables_due_for_vacuum: usize,
+    /// Tables with at least one issue, worst first
+    pub worst_tables: Vec<TableHealth>,
+}
+
+/// Statistics of the tables and materialized views of `schema`, or of one of them
+pub async fn get_table_stats(
+    client: &Arc<Client>,
+    schema: &str,
+    table: Option<&str>,
+) -> Result<Vec<TableStats>> {
+    let rows = client
+        .query(
+            "WITH rel AS (
+                SELECT c.oid, n.nspname, c.relname, c.reltuples, c.relpages, c.reltoastrelid,
+                       c.relfrozenxid, c.reloptions
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE c.relkind IN ('r', 'm') AND n.nspname = $1
+                  AND ($2::text IS NULL OR c.relname = $2)
+             ),
+             -- Expected heap pages from the average row width in pg_stats
+             heap AS (
+                SELECT
+                    rel.oid,
+                    bool_and(s.avg_width IS NOT NULL) AS has_stats,
+                    -- Tuple header with null bitmap, plus data, aligned to 8 bytes
+                    ceil((23 + CASE WHEN max(COALESCE(s.null_frac, 0)) > 0 THEN (7 + count(*)) / 8 ELSE 0 END
+                          + sum((1 - COALESCE(s.null_frac, 0)) * COALESCE(s.avg_width, 0))) / 8) * 8
+                        + 4 AS tuple_bytes
+                FROM rel
+                JOIN pg_attribute a ON a.attrelid = rel.oid AND a.attnum > 0 AND NOT a.attisdropped
+                LEFT JOIN pg_stats s ON s.schemaname = rel.nspname AND s.tablename = rel.relname
+                    AND s.attname = a.attname AND NOT s.inherited
+                GROUP BY rel.oid
+             ),
+             -- The same for full btree indexes on plain columns, at the default
+             -- fillfactor of 90, with one entry per table row
+             idx AS (
+                SELECT
+                    i.indrelid AS oid,
+                    sum(GREATEST(ic.relpages - ceil(GREATEST(rel.reltuples, 0) * w.entry_bytes
+                        / ((current_setting('block_size')::numeric - 40) * 0.9)), 0)
+                        * current_setting('block_size')::numeric)::bigint AS bloat_bytes
+                FROM pg_index i
+                JOIN rel ON rel.oid = i.indrelid
+                JOIN pg_class ic ON ic.oid = i.indexrelid
+                JOIN pg_am am ON am.oid = ic.relam AND am.amname = 'btree'
+                CROSS JOIN LATERAL (
+                    SELECT ceil((8 + sum(s.avg_width)) / 8.0) * 8 + 4 AS entry_bytes,
+                           count(s.avg_width) = count(*) AS complete
+                    FROM unnest(i.indkey::int2[]) k(attnum)
+                    LEFT JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum
+                    LEFT JOIN pg_stats s ON s.schemaname = rel.nspname AND s.tablename = rel.relname
+                        AND s.attname = a.attname AND NOT s.inherited
+                ) w
+                WHERE w.complete AND ic.relpages > 0 AND i.indpred IS NULL
+                GROUP BY i.indrelid
+             )
+             SELECT
+                rel.nspname,
+                rel.relname,
+                COALESCE(st.seq_scan, 0),
+                COALESCE(st.seq_tup_read, 0),
+                COALESCE(st.idx_scan, 0),
+                COALESCE(st.idx_tup_fetch, 0),
+                COALESCE(st.n_live_tup, 0),
+                COALESCE(st.n_dead_tup, 0),
+                COALESCE(st.n_mod_since_analyze, 0),
+                st.last_vacuum,
+                st.last_autovacuum,
+                st.last_analyze,
+                st.last_autoanalyze,
+                COALESCE(st.vacuum_count, 0),
+                COALESCE(st.autovacuum_count, 0),
+                COALESCE(st.analyze_count, 0),
+                COALESCE(st.autoanalyze_count, 0),
+                COALESCE(st.n_dead_tup, 0) > current_setting('autovacuum_vacuum_threshold')::float8
+                    + current_setting('autovacuum_vacuum_scale_factor')::float8 * GREATEST(rel.reltuples, 0),
+                pg_relation_size(rel.oid),
+                pg_indexes_size(rel.oid),
+                COALESCE(pg_total_relation_size(NULLIF(rel.reltoastrelid, 0)), 0),
+                pg_size_pretty(pg_total_relation_size(rel.oid)),
+                CASE WHEN heap.has_stats AND rel.reltuples > 0 THEN
+                    (GREATEST(rel.relpages - ceil(rel.reltuples * heap.tuple_bytes
+                        / ((current_setting('block_size')::numeric - 24)
+                           * COALESCE(substring(array_to_string(rel.reloptions, ' ') FROM 'fillfactor=([0-9]+)')::numeric, 100)
+                           / 100)), 0)
+                     * current_setting('block_size')::numeric)::bigint
+                END,
+                idx.bloat_bytes,
+                io.heap_blks_hit::float8 / NULLIF(io.heap_blks_hit + io.heap_blks_read, 0),
+                io.idx_blks_hit::float8 / NULLIF(io.idx_blks_hit + io.idx_blks_read, 0),
+                io.toast_blks_hit::float8 / NULLIF(io.toast_blks_hit + io.toast_blks_read, 0),
+                GREATEST(age(rel.relfrozenxid), COALESCE(age(t.relfrozenxid), 0))::bigint,
+                current_setting('autovacuum_freeze_max_age')::float8
+             FROM rel
+             LEFT JOIN pg_stat_all_tables st ON st.relid = rel.oid
+             LEFT JOIN pg_statio_all_tables io ON io.relid = rel.oid
+             LEFT JOIN pg_class t ON t.oid = rel.reltoastrelid
+             LEFT JOIN heap ON heap.oid = rel.oid
+             LEFT JOIN idx ON idx.oid = rel.oid
+             ORDER BY rel.relname",
+            &[&schema, &table],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let live: i64 = row.get(6);
+            let dead: i64 = row.get(7);
+            let table_size: i64 = row.get(18);
+            let bloat: Option<i64> = row.get(22);
+            let xid_age: i64 = row.get(27);
+            let freeze_max_age: f64 = row.get(28);
+            TableStats {
+                schema: row.get(0),
+                name: row.get(1),
+                seq_scan: row.get(2),
+                seq_tup_read: row.get(3),
+                idx_scan: row.get(4),
+                idx_tup_fetch: row.get(5),
+                n_live_tup: live,
+                n_dead_tup: dead,
+                dead_tuple_ratio: if live + dead > 0 {
+                    dead as f64 / (live + dead) as f64
+                } else {
+                    0.0
+                },
+                n_mod_since_analyze: row.get(8),
+                last_vacuum: row.get(9),
+                last_autovacuum: row.get(10),
+                last_analyze: row.get(11),
+                last_autoanalyze: row.get(12),
+                vacuum_count: row.get(13),
+                autovacuum_count: row.get(14),
+                analyze_count: row.get(15),
+                autoanalyze_count: row.get(16),
+                vacuum_due: row.get(17),
+                table_size_bytes: table_size,
+                index_size_bytes: row.get(19),
+                toast_size_bytes: row.get(20),
+                total_size: row.get(21),
+                estimated_bloat_bytes: bloat,
+                estimated_bloat_ratio: bloat
+                    .filter(|_| table_size > 0)
+                    .map(|b| b as f64 / table_size as f64),
+                estimated_index_bloat_bytes: row.get(23),
+                heap_hit_ratio: row.get(24),
+                index_hit_ratio: row.get(25),
+                toast_hit_ratio: row.get(26),
+                xid_age,
+                wraparound_ratio: xid_age as f64 / freeze_max_age,
+            }
+        })
+        .collect())
+}
+
+const MB: i64 = 1024 * 1024;
+
+/// Score a table by what needs attention; each issue adds to the score
+fn assess(stats: &TableStats) -> (f64, Vec<String>) {
+    let mut score = 0.0;
+    let mut issues = Vec::new();
+
+    if stats.n_dead_tup > 1000 && stats.dead_tuple_ratio > 0.1 {
+        score += stats.dead_tuple_ratio * 10.0;
+        issues.push(format!(
+            "{:.0}% dead tuples ({})",
+            stats.dead_tuple_ratio * 100.0,
+            stats.n_dead_tup
+        ));
+    }
+    if stats.vacuum_due {
+        score += 2.0;
+        issues.push("Due for autovacuum".to_string());
+    }
+    if let (Some(bytes), Some(ratio)) = (stats.estimated_bloat_bytes, stats.estimated_bloat_ratio) {
+        if bytes > 10 * MB && ratio > 0.3 {
+            score += ratio * 10.0;
+            issues.push(format!(
+                "About {:.0}% table bloat ({} MB)",
+                ratio * 100.0,
+                bytes / MB
+            ));
+        }
+    }
+    if let Some(bytes) = stats.estimated_index_bloat_bytes {
+        if bytes > 10 * MB && stats.index_size_bytes > 0 {
+            let ratio = bytes as f64 / stats.index_size_bytes as f64;
+            if ratio > 0.3 {
+                score += ratio * 5.0;
+                issues.push(format!(
+                    "About {:.0}% index bloat ({} MB)",
+                    ratio * 100.0,
+                    bytes / MB
+                ));
+            }
+        }
+    }
+    if let Some(ratio) = stats.heap_hit_ratio {
+        if ratio < 0.9 && stats.table_size_bytes > 10 * MB {
+            score += (0.9 - ratio) * 10.0;
+            issues.push(format!("Heap cache hit ratio {:.1}%", ratio * 100.0));
+        }
+    }
+    if stats.seq_scan > stats.idx_scan && stats.n_live_tup > 10_000 && stats.seq_tup_read > 1_000_000 {
+        score += 3.0;
+        issues.push(format!(
+            "{} sequential scans vs {} index scans",
+            stats.seq_scan, stats.idx_scan
+        ));
+    }
+    if stats.wraparound_ratio > 0.5 {
+        score += stats.wraparound_ratio * 20.0;
+        issues.push(format!(
+            "XID age {} is {:.0}% of autovacuum_freeze_max_age",
+            stats.xid_age,
+            stats.wraparound_ratio * 100.0
+        ));
+    }
+    if stats.last_analyze.is_none() && stats.last_autoanalyze.is_none() && stats.n_live_tup > 0 {
+        score += 1.0;
+        issues.push("Never analyzed".to_string());
+    } else if stats.n_live_tup > 0 && stats.n_mod_since_analyze > stats.n_live_tup / 2 {
+        score += 1.0;
+        issues.push(format!(
+            "{} rows changed since the last analyze",
+            stats.n_mod_since_analyze
+        ));
+    }
+    (score, issues)
+}
+
+/// Totals for `schema` and its `limit` worst tables
+pub async fn get_schema_health_summary(
+    client: &Arc<Client>,
+    schema: &str,
+    limit: usize,
+) -> Result<SchemaHealthSummary> {
+    let stats = get_table_stats(client, schema, None).await?;
+    let table_count = stats.len();
+    let total_dead_tuples = stats.iter().map(|s| s.n_dead_tup).sum();
+    let total_estimated_bloat_bytes = stats.iter().filter_map(|s| s.estimated_bloat_bytes).sum();
+    let tables_due_for_vacuum = stats.iter().filter(|s| s.vacuum_due).count();
+
+    let mut worst: Vec<TableHealth> = stats
+        .into_iter()
+        .filter_map(|stats| {
+            let (score, issues) = assess(&stats);
+            (!issues.is_empty()).then(|| TableHealth {
+                name: stats.name.clone(),
+                score,
+                issues,
+                stats,
+            })
+        })
+        .collect();
+    worst.sort_by(|a, b| b.score.total_cmp(&a.score));
+    worst.truncate(limit);
+
+    Ok(SchemaHealthSummary {
+        schema: schema.to_string(),
+        table_count,
+        total_dead_tuples,
+        total_estimated_bloat_bytes,
+        tables_due_for_vacuum,
+        worst_tables: worst,
+    })
+}
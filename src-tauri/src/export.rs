@@ -0,0 +1,789 @@
+use crate::db::{self, ConnectionManager};
+use anyhow::Result;
+use futures_util::{pin_mut, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, State};
+use tokio::sync::Mutex;
+use tokio_postgres::types::Type;
+use tokio_postgres::{CancelToken, NoTls, SimpleQueryMessage};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const DEFAULT_INSERT_BATCH: usize = 100;
+/// Worksheet row limit, including the header row
+const XLSX_MAX_ROWS: u32 = 1_048_576;
+/// Longest string Excel accepts in a cell
+const XLSX_MAX_STRING_CHARS: usize = 32_767;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Json,
+    Jsonl,
+    Markdown,
+    SqlInsert,
+    Xlsx,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// CSV field delimiter, defaults to ','
+    #[serde(default)]
+    pub delimiter: Option<char>,
+    /// CSV quote character, defaults to '"'
+    #[serde(default)]
+    pub quote: Option<char>,
+    /// Text written for NULL in CSV, TSV and Markdown, defaults to empty
+    #[serde(default)]
+    pub null_value: Option<String>,
+    /// Write a header row (CSV, TSV, XLSX), defaults to true
+    #[serde(default)]
+    pub header: Option<bool>,
+    /// Target table for SQL INSERT statements, optionally schema-qualified
+    #[serde(default)]
+    pub table_name: Option<String>,
+    /// Rows per INSERT statement
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportResult {
+    pub file_path: String,
+    pub rows_written: u64,
+    pub size_bytes: u64,
+    pub elapsed_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    pub file_path: String,
+    pub rows_written: u64,
+}
+
+/// How a column's text value is rendered in typed formats (JSON, SQL, XLSX)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Number,
+    Bool,
+    Json,
+    Text,
+}
+
+impl ColumnKind {
+    fn of(pg_type: &Type) -> Self {
+        match *pg_type {
+            Type::INT2 | Type::INT4 | Type::INT8 | Type::OID | Type::FLOAT4 | Type::FLOAT8
+            | Type::NUMERIC => ColumnKind::Number,
+            Type::BOOL => ColumnKind::Bool,
+            Type::JSON | Type::JSONB => ColumnKind::Json,
+            _ => ColumnKind::Text,
+        }
+    }
+}
+
+/// A numeric value that can be written as-is (not NaN or Infinity)
+fn is_finite_number(text: &str) -> bool {
+    text.parse::<f64>().is_ok_and(|v| v.is_finite())
+}
+
+struct Column {
+    name: String,
+    kind: ColumnKind,
+}
+
+/// Receives the rows of an export one at a time and writes them in some format.
+trait RowSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<()>;
+    fn row(&mut self, values: &[Option<&str>]) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+struct DelimitedSink {
+    writer: csv::Writer<BufWriter<File>>,
+    null_value: String,
+    header: bool,
+}
+
+impl RowSink for DelimitedSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<()> {
+        if self.header {
+            self.writer.write_record(columns.iter().map(|c| &c.name))?;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Option<&str>]) -> Result<()> {
+        let null = self.null_value.as_str();
+        self.writer
+            .write_record(values.iter().map(|v| v.unwrap_or(null)))?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// JSON array of objects, or one object per line (JSON Lines)
+struct JsonSink {
+    out: BufWriter<File>,
+    keys: Vec<String>,
+    kinds: Vec<ColumnKind>,
+    lines: bool,
+    rows: u64,
+}
+
+impl RowSink for JsonSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<()> {
+        self.keys = columns
+            .iter()
+            .map(|c| serde_json::to_string(&c.name))
+            .collect::<Result<_, _>>()?;
+        self.kinds = columns.iter().map(|c| c.kind).collect();
+        if !self.lines {
+            self.out.write_all(b"[")?;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Option<&str>]) -> Result<()> {
+        if !self.lines {
+            self.out
+                .write_all(if self.rows == 0 { b"\n" } else { b",\n" })?;
+        }
+        // Objects are written by hand to keep the column order of the result
+        self.out.write_all(b"{")?;
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.out.write_all(b",")?;
+            }
+            write!(self.out, "{}:", self.keys[i])?;
+            match (value, self.kinds[i]) {
+                (None, _) => self.out.write_all(b"null")?,
+                (Some(v), ColumnKind::Number) if is_finite_number(v) => {
+                    self.out.write_all(v.as_bytes())?
+                }
+                (Some(v), ColumnKind::Bool) => {
+                    self.out
+                        .write_all(if *v == "t" { b"true" } else { b"false" })?
+                }
+                (Some(v), ColumnKind::Json) => self.out.write_all(v.as_bytes())?,
+                (Some(v), _) => serde_json::to_writer(&mut self.out, v)?,
+            }
+        }
+        self.out.write_all(b"}")?;
+        if self.lines {
+            self.out.write_all(b"\n")?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if !self.lines {
+            self.out
+                .write_all(if self.rows == 0 { b"]\n" } else { b"\n]\n" })?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct MarkdownSink {
+    out: BufWriter<File>,
+    null_value: String,
+}
+
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+impl RowSink for MarkdownSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<()> {
+        let names: Vec<String> = columns.iter().map(|c| markdown_escape(&c.name)).collect();
+        writeln!(self.out, "| {} |", names.join(" | "))?;
+        writeln!(self.out, "|{}", " --- |".repeat(columns.len()))?;
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Option<&str>]) -> Result<()> {
+        let cells: Vec<String> = values
+            .iter()
+            .map(|v| markdown_escape(v.unwrap_or(&self.null_value)))
+            .collect();
+        writeln!(self.out, "| {} |", cells.join(" | "))?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Multi-row INSERT statements, `batch_size` rows each
+struct SqlInsertSink {
+    out: BufWriter<File>,
+    table: String,
+    column_list: String,
+    kinds: Vec<ColumnKind>,
+    batch_size: usize,
+    in_batch: usize,
+}
+
+impl RowSink for SqlInsertSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<()> {
+        self.column_list = columns
+            .iter()
+            .map(|c| db::quote_ident(&c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.kinds = columns.iter().map(|c| c.kind).collect();
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Option<&str>]) -> Result<()> {
+        if self.in_batch == 0 {
+            write!(
+                self.out,
+                "INSERT INTO {} ({}) VALUES\n(",
+                self.table, self.column_list
+            )?;
+        } else {
+            self.out.write_all(b",\n(")?;
+        }
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.out.write_all(b", ")?;
+            }
+            let literal = match (value, self.kinds[i]) {
+                (None, _) => "NULL".to_string(),
+                (Some(v), ColumnKind::Number) if is_finite_number(v) => v.to_string(),
+                (Some(v), ColumnKind::Bool) => {
+                    if *v == "t" { "TRUE" } else { "FALSE" }.to_string()
+                }
+                (Some(v), _) => db::quote_literal(v),
+            };
+            self.out.write_all(literal.as_bytes())?;
+        }
+        self.out.write_all(b")")?;
+
+        self.in_batch += 1;
+        if self.in_batch == self.batch_size {
+            self.out.write_all(b";\n")?;
+            self.in_batch = 0;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.in_batch > 0 {
+            self.out.write_all(b";\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct XlsxSink {
+    workbook: rust_xlsxwriter::Workbook,
+    path: String,
+    columns: Vec<String>,
+    kinds: Vec<ColumnKind>,
+    header: bool,
+    next_row: u32,
+}
+
+impl RowSink for XlsxSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<()> {
+        self.workbook.add_worksheet_with_constant_memory();
+        self.columns = columns.iter().map(|c| c.name.clone()).collect();
+        self.kinds = columns.iter().map(|c| c.kind).collect();
+        if self.header {
+            let sheet = self.workbook.worksheet_from_index(0)?;
+            for (i, column) in columns.iter().enumerate() {
+                sheet.write_string(0, i as u16, &column.name)?;
+            }
+            self.next_row = 1;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Option<&str>]) -> Result<()> {
+        if self.next_row >= XLSX_MAX_ROWS {
+            anyhow::bail!(
+                "XLSX worksheets are limited to {} rows; use CSV for larger exports",
+                XLSX_MAX_ROWS
+            );
+        }
+        let row = self.next_row;
+        let sheet = self.workbook.worksheet_from_index(0)?;
+        for (i, value) in values.iter().enumerate() {
+            let Some(v) = value else { continue };
+            let col = i as u16;
+            match self.kinds[i] {
+                ColumnKind::Number if is_finite_number(v) => {
+                    sheet.write_number(row, col, v.parse::<f64>()?)?;
+                }
+                ColumnKind::Bool => {
+                    sheet.write_boolean(row, col, *v == "t")?;
+                }
+                _ => {
+                    // Excel rejects longer strings, and cutting them would lose data
+                    if v.chars().nth(XLSX_MAX_STRING_CHARS).is_some() {
+                        anyhow::bail!(
+                            "Row {}, column \"{}\" is longer than the {} characters an XLSX cell can hold; use CSV to export it",
+                            row + 1,
+                            self.columns[i],
+                            XLSX_MAX_STRING_CHARS
+                        );
+                    }
+                    sheet.write_string(row, col, *v)?;
+                }
+            }
+        }
+        self.next_row += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.workbook.save(&self.path)?;
+        Ok(())
+    }
+}
+
+fn csv_byte(c: char, what: &str) -> Result<u8> {
+    if !c.is_ascii() {
+        anyhow::bail!("CSV {} must be an ASCII character", what);
+    }
+    Ok(c as u8)
+}
+
+fn open_sink(path: &str, options: &ExportOptions) -> Result<Box<dyn RowSink + Send>> {
+    let null_value = options.null_value.clone().unwrap_or_default();
+    let header = options.header.unwrap_or(true);
+
+    let sink: Box<dyn RowSink + Send> = match options.format {
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            let delimiter = match options.format {
+                ExportFormat::Tsv => b'\t',
+                _ => csv_byte(options.delimiter.unwrap_or(','), "delimiter")?,
+            };
+            let writer = csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .quote(csv_byte(options.quote.unwrap_or('"'), "quote")?)
+                .from_writer(BufWriter::new(File::create(path)?));
+            Box::new(DelimitedSink {
+                writer,
+                null_value,
+                header,
+            })
+        }
+        ExportFormat::Json | ExportFormat::Jsonl => Box::new(JsonSink {
+            out: BufWriter::new(File::create(path)?),
+            keys: Vec::new(),
+            kinds: Vec::new(),
+            lines: options.format == ExportFormat::Jsonl,
+            rows: 0,
+        }),
+        ExportFormat::Markdown => Box::new(MarkdownSink {
+            out: BufWriter::new(File::create(path)?),
+            null_value,
+        }),
+        ExportFormat::SqlInsert => {
+            let table_name = options
+                .table_name
+                .as_deref()
+                .filter(|t| !t.trim().is_empty())
+                .ok_or_else(|| anyhow::anyhow!("SQL INSERT export needs a table name"))?;
+            let table = table_name
+                .split('.')
+                .map(|part| db::quote_ident(part.trim()))
+                .collect::<Vec<_>>()
+                .join(".");
+            Box::new(SqlInsertSink {
+                out: BufWriter::new(File::create(path)?),
+                table,
+                column_list: String::new(),
+                kinds: Vec::new(),
+                batch_size: options.batch_size.unwrap_or(DEFAULT_INSERT_BATCH).max(1),
+                in_batch: 0,
+            })
+        }
+        ExportFormat::Xlsx => Box::new(XlsxSink {
+            workbook: rust_xlsxwriter::Workbook::new(),
+            path: path.to_string(),
+            columns: Vec::new(),
+            kinds: Vec::new(),
+            header,
+            next_row: 0,
+        }),
+    };
+    Ok(sink)
+}
+
+/// Stream the rows of a single query into `sink`. Rows are read in text form over
+/// the simple query protocol so every type exports exactly as the server prints it;
+/// column types come from preparing the statement first.
+async fn stream_query<F>(
+    client: &tokio_postgres::Client,
+    sql: &str,
+    sink: &mut (dyn RowSink + Send),
+    mut on_progress: F,
+) -> Result<u64>
+where
+    F: FnMut(u64),
+{
+    let sql = sql.trim().trim_end_matches(';');
+    let statement = client.prepare(sql).await?;
+    if statement.columns().is_empty() {
+        anyhow::bail!("Only queries that return rows can be exported");
+    }
+    let columns: Vec<Column> = statement
+        .columns()
+        .iter()
+        .map(|c| Column {
+            name: c.name().to_string(),
+            kind: ColumnKind::of(c.type_()),
+        })
+        .collect();
+    sink.begin(&columns)?;
+
+    let stream = client.simple_query_raw(sql).await?;
+    pin_mut!(stream);
+
+    let mut rows_written = 0u64;
+    let mut last_progress = Instant::now();
+    while let Some(message) = stream.try_next().await? {
+        if let SimpleQueryMessage::Row(row) = message {
+            let values = (0..row.len())
+                .map(|i| row.try_get(i))
+                .collect::<Result<Vec<Option<&str>>, _>>()?;
+            sink.row(&values)?;
+            rows_written += 1;
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                on_progress(rows_written);
+                last_progress = Instant::now();
+            }
+        }
+    }
+    Ok(rows_written)
+}
+
+/// Exports have no confirmation step, so on a protected connection statements
+/// the guard flags are refused; they can still be run from the editor.
+async fn reject_destructive(
+    manager: &ConnectionManager,
+    connection_id: &str,
+    sql: &str,
+) -> Result<(), String> {
+    let config = manager
+        .get_config(connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if config.protected && !db::classify_statements(sql).is_empty() {
+        return Err(
+            "This statement needs confirmation on a protected connection and cannot be exported"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// Run a query on the backend and write its rows straight to `output_path`,
+/// without building the result in memory. Emits `export-progress` events.
+#[tauri::command]
+pub async fn export_query(
+    connection_id: String,
+    sql: String,
+    output_path: String,
+    options: ExportOptions,
+    app: tauri::AppHandle,
+    manager: State<'_, ConnectionManager>,
+) -> Result<ExportResult, String> {
+    let started = Instant::now();
+    reject_destructive(&manager, &connection_id, &sql).await?;
+    // A separate connection keeps a long export from holding up the editor's queries
+    let client = manager
+        .open_dedicated(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut sink = open_sink(&output_path, &options).map_err(|e| e.to_string())?;
+    let rows_written = stream_query(&client, &sql, sink.as_mut(), |rows_written| {
+        let _ = app.emit(
+            "export-progress",
+            ExportProgress {
+                file_path: output_path.clone(),
+                rows_written,
+            },
+        );
+    })
+    .await;
+
+    let finished = rows_written.and_then(|rows| sink.finish().map(|_| rows));
+    let rows_written = match finished {
+        Ok(rows) => rows,
+        Err(e) => {
+            let _ = std::fs::remove_file(&output_path);
+            return Err(e.to_string());
+        }
+    };
+
+    let size_bytes = std::fs::metadata(&output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    Ok(ExportResult {
+        file_path: output_path,
+        rows_written,
+        size_bytes,
+        elapsed_ms: started.elapsed().as_millis(),
+    })
+}
+
+// ── COPY export ──
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyFormat {
+    Csv,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CopyExportResult {
+    pub file_path: String,
+    pub bytes_received: u64,
+    pub size_bytes: u64,
+    pub elapsed_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CopyExportProgress {
+    pub export_id: String,
+    pub bytes_received: u64,
+    pub bytes_per_sec: u64,
+    pub elapsed_ms: u128,
+}
+
+/// Cancel tokens of COPY exports that are still running, keyed by export id
+pub struct RunningExports {
+    tokens: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl RunningExports {
+    pub fn new() -> Self {
+        Self {
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+enum CopyWriter {
+    Plain(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl CopyWriter {
+    fn create(path: &str, compression: Compression) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match compression {
+            Compression::None => CopyWriter::Plain(file),
+            Compression::Gzip => CopyWriter::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => CopyWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Write any compression trailer and flush the file
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            CopyWriter::Plain(file) => file,
+            CopyWriter::Gzip(encoder) => encoder.finish()?,
+            CopyWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for CopyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CopyWriter::Plain(w) => w.write(buf),
+            CopyWriter::Gzip(w) => w.write(buf),
+            CopyWriter::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CopyWriter::Plain(w) => w.flush(),
+            CopyWriter::Gzip(w) => w.flush(),
+            CopyWriter::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+async fn copy_to_file<F>(
+    client: &tokio_postgres::Client,
+    copy_sql: &str,
+    output_path: &str,
+    compression: Compression,
+    mut on_progress: F,
+) -> Result<u64>
+where
+    F: FnMut(u64),
+{
+    let mut writer = CopyWriter::create(output_path, compression)?;
+    let stream = client.copy_out(copy_sql).await?;
+    pin_mut!(stream);
+
+    let mut bytes_received = 0u64;
+    let mut last_progress = Instant::now();
+    while let Some(chunk) = stream.try_next().await? {
+        writer.write_all(&chunk)?;
+        bytes_received += chunk.len() as u64;
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            on_progress(bytes_received);
+            last_progress = Instant::now();
+        }
+    }
+    writer.finish()?;
+    Ok(bytes_received)
+}
+
+/// Export a whole table, or the rows of a single query, with `COPY ... TO STDOUT`,
+/// streaming the server's output straight to disk. Give either `schema` and `table`
+/// or `sql`. Emits `copy-export-progress` events and can be stopped with `cancel_export`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_export(
+    export_id: String,
+    connection_id: String,
+    schema: Option<String>,
+    table: Option<String>,
+    sql: Option<String>,
+    output_path: String,
+    format: CopyFormat,
+    header: Option<bool>,
+    compression: Option<Compression>,
+    app: tauri::AppHandle,
+    manager: State<'_, ConnectionManager>,
+    running: State<'_, RunningExports>,
+) -> Result<CopyExportResult, String> {
+    let started = Instant::now();
+    let client = manager
+        .open_dedicated(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let source = match (schema, table, sql) {
+        // COPY of a relation only works on plain tables; a query covers views too
+        (Some(schema), Some(table), None) => format!(
+            "(SELECT * FROM {}.{})",
+            db::quote_ident(&schema),
+            db::quote_ident(&table)
+        ),
+        (None, None, Some(sql)) => {
+            let sql = sql.trim().trim_end_matches(';');
+            reject_destructive(&manager, &connection_id, sql).await?;
+            // Preparing checks that this is a single statement that returns rows
+            let statement = client.prepare(sql).await.map_err(|e| e.to_string())?;
+            if statement.columns().is_empty() {
+                return Err("Only queries that return rows can be exported".into());
+            }
+            format!("({})", sql)
+        }
+        _ => return Err("Specify either a schema and table, or a query".into()),
+    };
+    let options = match format {
+        CopyFormat::Csv => format!("FORMAT csv, HEADER {}", header.unwrap_or(true)),
+        CopyFormat::Text => "FORMAT text".to_string(),
+    };
+    let copy_sql = format!("COPY {} TO STDOUT ({})", source, options);
+
+    running
+        .tokens
+        .lock()
+        .await
+        .insert(export_id.clone(), client.cancel_token());
+    let outcome = copy_to_file(
+        &client,
+        &copy_sql,
+        &output_path,
+        compression.unwrap_or_default(),
+        |bytes_received| {
+            let elapsed = started.elapsed();
+            let _ = app.emit(
+                "copy-export-progress",
+                CopyExportProgress {
+                    export_id: export_id.clone(),
+                    bytes_received,
+                    bytes_per_sec: (bytes_received as f64 / elapsed.as_secs_f64()) as u64,
+                    elapsed_ms: elapsed.as_millis(),
+                },
+            );
+        },
+    )
+    .await;
+    // cancel_export takes the token out, so a missing entry means the user cancelled
+    let cancelled = running.tokens.lock().await.remove(&export_id).is_none();
+
+    let bytes_received = match outcome {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = std::fs::remove_file(&output_path);
+            return Err(if cancelled {
+                "Export cancelled".to_string()
+            } else {
+                e.to_string()
+            });
+        }
+    };
+
+    let size_bytes = std::fs::metadata(&output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    Ok(CopyExportResult {
+        file_path: output_path,
+        bytes_received,
+        size_bytes,
+        elapsed_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[tauri::command]
+pub async fn cancel_export(
+    export_id: String,
+    running: State<'_, RunningExports>,
+) -> Result<(), String> {
+    let token = running
+        .tokens
+        .lock()
+        .await
+        .remove(&export_id)
+        .ok_or_else(|| format!("No running export with id: {}", export_id))?;
+    token.cancel_query(NoTls).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,804 @@
+use crate::db::{self, ConnectionManager};
+use anyhow::Result;
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::Instant;
+use tauri::{Emitter, State};
+use tokio_postgres::Client;
+
+const DEFAULT_SAMPLE_ROWS: usize = 100;
+/// JSON records scanned up front to discover the set of keys
+const JSON_KEY_DISCOVERY_ROWS: usize = 1000;
+/// Rows sent per COPY; a rejected row only replays its own batch
+const BATCH_ROWS: usize = 5000;
+const COPY_CHUNK_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Csv,
+    Tsv,
+    Jsonl,
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    /// CSV field delimiter, defaults to ','
+    #[serde(default)]
+    pub delimiter: Option<char>,
+    /// CSV quote character, defaults to '"'
+    #[serde(default)]
+    pub quote: Option<char>,
+    /// CSV/TSV: the first line holds column names, defaults to true
+    #[serde(default)]
+    pub has_header: Option<bool>,
+    /// CSV/TSV text that stands for NULL, defaults to empty
+    #[serde(default)]
+    pub null_value: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InferredColumn {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportPreview {
+    pub columns: Vec<InferredColumn>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+/// A file column and the table column it is loaded into
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColumnMapping {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewColumn {
+    pub name: String,
+    pub data_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportTarget {
+    pub schema: String,
+    pub table: String,
+    pub mapping: Vec<ColumnMapping>,
+    /// Create the table with these columns before loading
+    #[serde(default)]
+    pub create_columns: Option<Vec<NewColumn>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowError {
+    /// Line in the source file (record number for JSON arrays)
+    pub line: u64,
+    pub column: Option<String>,
+    pub value: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResult {
+    pub rows_imported: u64,
+    pub rows_rejected: u64,
+    pub errors: Vec<RowError>,
+    pub dry_run: bool,
+    pub committed: bool,
+    pub elapsed_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportProgress {
+    pub import_id: String,
+    pub rows_processed: u64,
+    pub rows_rejected: u64,
+}
+
+// ── Reading ──
+
+struct SourceRow {
+    line: u64,
+    values: Vec<Option<String>>,
+}
+
+enum RowReader {
+    Delimited {
+        reader: csv::Reader<File>,
+        null_value: String,
+    },
+    JsonLines {
+        lines: std::io::Lines<BufReader<File>>,
+        line: u64,
+        buffered: VecDeque<(u64, serde_json::Map<String, Value>)>,
+    },
+    JsonArray {
+        items: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+    },
+}
+
+/// Reads records from an import file as rows of optional text values,
+/// aligned with `columns`.
+struct SourceReader {
+    columns: Vec<String>,
+    rows: RowReader,
+}
+
+fn csv_byte(c: char, what: &str) -> Result<u8> {
+    if !c.is_ascii() {
+        anyhow::bail!("CSV {} must be an ASCII character", what);
+    }
+    Ok(c as u8)
+}
+
+fn json_text(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        other => Some(other.to_string()),
+    }
+}
+
+fn json_object(value: Value, line: u64) -> Result<serde_json::Map<String, Value>> {
+    match value {
+        Value::Object(map) => Ok(map),
+        _ => anyhow::bail!("Line {}: expected a JSON object", line),
+    }
+}
+
+fn add_keys(columns: &mut Vec<String>, object: &serde_json::Map<String, Value>) {
+    for key in object.keys() {
+        if !columns.contains(key) {
+            columns.push(key.clone());
+        }
+    }
+}
+
+impl SourceReader {
+    fn open(path: &str, options: &ImportOptions) -> Result<Self> {
+        match options.format {
+            ImportFormat::Csv | ImportFormat::Tsv => {
+                let delimiter = match options.format {
+                    ImportFormat::Tsv => b'\t',
+                    _ => csv_byte(options.delimiter.unwrap_or(','), "delimiter")?,
+                };
+                let has_header = options.has_header.unwrap_or(true);
+                let mut reader = csv::ReaderBuilder::new()
+                    .delimiter(delimiter)
+                    .quote(csv_byte(options.quote.unwrap_or('"'), "quote")?)
+                    .has_headers(has_header)
+                    .flexible(true)
+                    .from_path(path)?;
+                // Without a header the first record only tells us how many columns there are
+                let first = reader.headers()?;
+                let columns = if has_header {
+                    first.iter().map(|h| h.trim().to_string()).collect()
+                } else {
+                    (1..=first.len()).map(|i| format!("column_{}", i)).collect()
+                };
+                Ok(Self {
+                    columns,
+                    rows: RowReader::Delimited {
+                        reader,
+                        null_value: options.null_value.clone().unwrap_or_default(),
+                    },
+                })
+            }
+            ImportFormat::Jsonl => {
+                let mut lines = BufReader::new(File::open(path)?).lines();
+                let mut columns = Vec::new();
+                let mut buffered = VecDeque::new();
+                let mut line = 0;
+                while buffered.len() < JSON_KEY_DISCOVERY_ROWS {
+                    let Some(text) = lines.next() else { break };
+                    line += 1;
+                    let text = text?;
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    let object = json_object(serde_json::from_str(&text)?, line)?;
+                    add_keys(&mut columns, &object);
+                    buffered.push_back((line, object));
+                }
+                Ok(Self {
+                    columns,
+                    rows: RowReader::JsonLines {
+                        lines,
+                        line,
+                        buffered,
+                    },
+                })
+            }
+            ImportFormat::Json => {
+                let Value::Array(items) = serde_json::from_reader(BufReader::new(File::open(path)?))?
+                else {
+                    anyhow::bail!("Expected a JSON array of objects");
+                };
+                let mut columns = Vec::new();
+                for item in items.iter().take(JSON_KEY_DISCOVERY_ROWS) {
+                    if let Value::Object(object) = item {
+                        add_keys(&mut columns, object);
+                    }
+                }
+                Ok(Self {
+                    columns,
+                    rows: RowReader::JsonArray {
+                        items: items.into_iter().enumerate(),
+                    },
+                })
+            }
+        }
+    }
+
+    fn next_row(&mut self) -> Option<Result<SourceRow>> {
+        let columns = &self.columns;
+        let from_object = |line: u64, mut object: serde_json::Map<String, Value>| SourceRow {
+            line,
+            values: columns
+                .iter()
+                .map(|c| object.remove(c).and_then(json_text))
+                .collect(),
+        };
+
+        match &mut self.rows {
+            RowReader::Delimited { reader, null_value } => {
+                let mut record = csv::StringRecord::new();
+                match reader.read_record(&mut record) {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    Err(e) => return Some(Err(e.into())),
+                }
+                let line = record.position().map_or(0, |p| p.line());
+                let values = (0..columns.len())
+                    .map(|i| {
+                        record
+                            .get(i)
+                            .filter(|v| *v != null_value.as_str())
+                            .map(str::to_string)
+                    })
+                    .collect();
+                Some(Ok(SourceRow { line, values }))
+            }
+            RowReader::JsonLines {
+                lines,
+                line,
+                buffered,
+            } => {
+                if let Some((line, object)) = buffered.pop_front() {
+                    return Some(Ok(from_object(line, object)));
+                }
+                loop {
+                    let text = match lines.next()? {
+                        Ok(text) => text,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+                    *line += 1;
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    let object = serde_json::from_str(&text)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|value| json_object(value, *line));
+                    return Some(object.map(|object| from_object(*line, object)));
+                }
+            }
+            RowReader::JsonArray { items } => {
+                let (index, item) = items.next()?;
+                let line = index as u64 + 1;
+                Some(json_object(item, line).map(|object| from_object(line, object)))
+            }
+        }
+    }
+
+    /// Positions of the mapped source columns
+    fn mapped_indices(&self, mapping: &[ColumnMapping]) -> Result<Vec<usize>> {
+        mapping
+            .iter()
+            .map(|m| {
+                self.columns
+                    .iter()
+                    .position(|c| *c == m.source)
+                    .ok_or_else(|| anyhow::anyhow!("Column not found in file: {}", m.source))
+            })
+            .collect()
+    }
+}
+
+// ── Type inference ──
+
+/// Candidate types a column's sample values still fit, narrowest first
+struct TypeGuess {
+    boolean: bool,
+    integer: bool,
+    bigint: bool,
+    numeric: bool,
+    date: bool,
+    timestamp: bool,
+    timestamptz: bool,
+    uuid: bool,
+    jsonb: bool,
+    seen: bool,
+    nullable: bool,
+}
+
+impl TypeGuess {
+    fn new() -> Self {
+        Self {
+            boolean: true,
+            integer: true,
+            bigint: true,
+            numeric: true,
+            date: true,
+            timestamp: true,
+            timestamptz: true,
+            uuid: true,
+            jsonb: true,
+            seen: false,
+            nullable: false,
+        }
+    }
+
+    fn observe(&mut self, value: Option<&str>) {
+        let Some(v) = value.map(str::trim) else {
+            self.nullable = true;
+            return;
+        };
+        self.seen = true;
+        self.boolean &= matches!(
+            v.to_ascii_lowercase().as_str(),
+            "true" | "false" | "t" | "f"
+        );
+        self.integer &= v.parse::<i32>().is_ok();
+        self.bigint &= v.parse::<i64>().is_ok();
+        self.numeric &= v.parse::<f64>().is_ok()
+            && v.bytes()
+                .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'));
+        self.date &= chrono::NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok();
+        self.timestamp &= ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .any(|f| chrono::NaiveDateTime::parse_from_str(v, f).is_ok());
+        self.timestamptz &= chrono::DateTime::parse_from_rfc3339(v).is_ok()
+            || chrono::DateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f%#z").is_ok();
+        self.uuid &= v.len() == 36 && uuid::Uuid::parse_str(v).is_ok();
+        self.jsonb &= (v.starts_with('{') || v.starts_with('['))
+            && serde_json::from_str::<Value>(v).is_ok();
+    }
+
+    fn data_type(&self) -> &'static str {
+        if !self.seen {
+            return "text";
+        }
+        [
+            (self.boolean, "boolean"),
+            (self.integer, "integer"),
+            (self.bigint, "bigint"),
+            (self.numeric, "numeric"),
+            (self.date, "date"),
+            (self.timestamp, "timestamp"),
+            (self.timestamptz, "timestamptz"),
+            (self.uuid, "uuid"),
+            (self.jsonb, "jsonb"),
+        ]
+        .iter()
+        .find(|(fits, _)| *fits)
+        .map_or("text", |(_, name)| name)
+    }
+}
+
+/// Read the first rows of a file and guess a Postgres type for each column.
+#[tauri::command]
+pub async fn preview_import(
+    file_path: String,
+    options: ImportOptions,
+    sample_rows: Option<usize>,
+) -> Result<ImportPreview, String> {
+    let mut reader = SourceReader::open(&file_path, &options).map_err(|e| e.to_string())?;
+    let mut guesses: Vec<TypeGuess> = reader.columns.iter().map(|_| TypeGuess::new()).collect();
+    let mut rows = Vec::new();
+    while rows.len() < sample_rows.unwrap_or(DEFAULT_SAMPLE_ROWS) {
+        let Some(row) = reader.next_row() else { break };
+        let row = row.map_err(|e| e.to_string())?;
+        for (guess, value) in guesses.iter_mut().zip(&row.values) {
+            guess.observe(value.as_deref());
+        }
+        rows.push(row.values);
+    }
+
+    let columns = reader
+        .columns
+        .iter()
+        .zip(&guesses)
+        .map(|(name, guess)| InferredColumn {
+            name: name.clone(),
+            data_type: guess.data_type().to_string(),
+            nullable: guess.nullable,
+        })
+        .collect();
+    Ok(ImportPreview { columns, rows })
+}
+
+fn qualified_name(schema: &str, table: &str) -> String {
+    format!("{}.{}", db::quote_ident(schema), db::quote_ident(table))
+}
+
+fn create_table_sql(schema: &str, table: &str, columns: &[NewColumn]) -> String {
+    let defs: Vec<String> = columns
+        .iter()
+        .map(|c| format!("    {} {}", db::quote_ident(&c.name), c.data_type))
+        .collect();
+    format!(
+        "CREATE TABLE {} (\n{}\n);",
+        qualified_name(schema, table),
+        defs.join(",\n")
+    )
+}
+
+/// CREATE TABLE statement for importing into a new table, for review before running.
+#[tauri::command]
+pub async fn generate_import_table(
+    schema: String,
+    table: String,
+    columns: Vec<NewColumn>,
+) -> Result<String, String> {
+    Ok(create_table_sql(&schema, &table, &columns))
+}
+
+// ── Validation ──
+
+struct TargetColumn {
+    data_type: String,
+    not_null: bool,
+}
+
+/// Types of the mapped target columns, from the new table definition or the catalog
+async fn target_columns(client: &Client, target: &ImportTarget) -> Result<Vec<TargetColumn>> {
+    let mut result = Vec::new();
+    if let Some(ref new_columns) = target.create_columns {
+        for m in &target.mapping {
+            let column = new_columns
+                .iter()
+                .find(|c| c.name == m.target)
+                .ok_or_else(|| anyhow::anyhow!("Column not in new table: {}", m.target))?;
+            let known: Option<String> = client
+                .query_one("SELECT to_regtype($1)::text", &[&column.data_type])
+                .await
+                .ok()
+                .and_then(|row| row.get(0));
+            if known.is_none() {
+                anyhow::bail!("Unknown type for column {}: {}", column.name, column.data_type);
+            }
+            result.push(TargetColumn {
+                data_type: column.data_type.clone(),
+                not_null: false,
+            });
+        }
+        return Ok(result);
+    }
+
+    // Not `db::get_columns`: its information_schema types ("ARRAY", "USER-DEFINED",
+    // "character varying" without the length) cannot be cast to, and the sample
+    // is validated with `$1::text::<type>`. format_type gives the exact type.
+    let rows = client
+        .query(
+            "SELECT a.attname, format_type(a.atttypid, a.atttypmod), a.attnotnull
+             FROM pg_attribute a
+             WHERE a.attrelid = to_regclass($1) AND a.attnum > 0 AND NOT a.attisdropped",
+            &[&qualified_name(&target.schema, &target.table)],
+        )
+        .await?;
+    if rows.is_empty() {
+        anyhow::bail!("Table not found: {}.{}", target.schema, target.table);
+    }
+    for m in &target.mapping {
+        let row = rows
+            .iter()
+            .find(|r| r.get::<_, String>(0) == m.target)
+            .ok_or_else(|| anyhow::anyhow!("Column not found in table: {}", m.target))?;
+        result.push(TargetColumn {
+            data_type: row.get(1),
+            not_null: row.get(2),
+        });
+    }
+    Ok(result)
+}
+
+/// Check the first rows of the file against the target column types on the server.
+#[tauri::command]
+pub async fn validate_import(
+    connection_id: String,
+    file_path: String,
+    options: ImportOptions,
+    target: ImportTarget,
+    sample_rows: Option<usize>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<RowError>, String> {
+    let client = manager
+        .get_client(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let columns = target_columns(&client, &target)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut reader = SourceReader::open(&file_path, &options).map_err(|e| e.to_string())?;
+    let indices = reader
+        .mapped_indices(&target.mapping)
+        .map_err(|e| e.to_string())?;
+    let mut sample = Vec::new();
+    while sample.len() < sample_rows.unwrap_or(DEFAULT_SAMPLE_ROWS) {
+        let Some(row) = reader.next_row() else { break };
+        sample.push(row.map_err(|e| e.to_string())?);
+    }
+
+    let mut errors = Vec::new();
+    for ((m, column), &index) in target.mapping.iter().zip(&columns).zip(&indices) {
+        let values: Vec<(u64, &str)> = sample
+            .iter()
+            .filter_map(|row| row.values[index].as_deref().map(|v| (row.line, v)))
+            .collect();
+        if column.not_null {
+            for row in sample.iter().filter(|row| row.values[index].is_none()) {
+                errors.push(RowError {
+                    line: row.line,
+                    column: Some(m.target.clone()),
+                    value: None,
+                    message: "null value in a NOT NULL column".into(),
+                });
+            }
+        }
+
+        // Cast the whole column at once, and only look at single values when that fails
+        let texts: Vec<&str> = values.iter().map(|(_, v)| *v).collect();
+        let all_valid = client
+            .query(
+                &format!(
+                    "SELECT v::{} FROM unnest($1::text[]) AS v",
+                    column.data_type
+                ),
+                &[&texts],
+            )
+            .await
+            .is_ok();
+        if all_valid {
+            continue;
+        }
+        let cast = format!("SELECT $1::text::{}", column.data_type);
+        for (line, value) in values {
+            if let Err(e) = client.query(&cast, &[&value]).await {
+                errors.push(RowError {
+                    line,
+                    column: Some(m.target.clone()),
+                    value: Some(value.to_string()),
+                    message: db::db_error_message(&e),
+                });
+            }
+        }
+    }
+    errors.sort_by_key(|e| e.line);
+    Ok(errors)
+}
+
+// ── Loading ──
+
+/// Append a row as a COPY CSV line. Values are always quoted so that an
+/// unquoted empty field can stand for NULL.
+fn write_copy_row(buf: &mut Vec<u8>, values: &[Option<String>], indices: &[usize]) {
+    for (n, &i) in indices.iter().enumerate() {
+        if n > 0 {
+            buf.push(b',');
+        }
+        if let Some(ref value) = values[i] {
+            buf.push(b'"');
+            buf.extend_from_slice(value.replace('"', "\"\"").as_bytes());
+            buf.push(b'"');
+        }
+    }
+    buf.push(b'\n');
+}
+
+async fn copy_batch(
+    client: &Client,
+    copy_sql: &str,
+    batch: &[SourceRow],
+    indices: &[usize],
+) -> Result<u64, tokio_postgres::Error> {
+    let sink = client.copy_in(copy_sql).await?;
+    pin_mut!(sink);
+    let mut buf = Vec::with_capacity(COPY_CHUNK_BYTES);
+    for row in batch {
+        write_copy_row(&mut buf, &row.values, indices);
+        if buf.len() >= COPY_CHUNK_BYTES {
+            sink.send(Bytes::from(std::mem::take(&mut buf))).await?;
+        }
+    }
+    if !buf.is_empty() {
+        sink.send(Bytes::from(buf)).await?;
+    }
+    sink.finish().await
+}
+
+/// The row of a batch a failed COPY complained about, from the error's
+/// context line, e.g. `COPY items, line 3, column price: "abc"`.
+fn failed_copy_line(e: &tokio_postgres::Error) -> Option<(usize, Option<String>)> {
+    let context = e.as_db_error()?.where_()?;
+    let rest = &context[context.find(", line ")? + ", line ".len()..];
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    let line: usize = digits.parse().ok()?;
+    let column = rest
+        .find(", column ")
+        .map(|at| &rest[at + ", column ".len()..])
+        .map(|c| c.split(':').next().unwrap_or(c).to_string());
+    Some((line.checked_sub(1)?, column))
+}
+
+async fn load_rows<F>(
+    client: &Client,
+    reader: &mut SourceReader,
+    target: &ImportTarget,
+    max_errors: usize,
+    result: &mut ImportResult,
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(u64, u64),
+{
+    let indices = reader.mapped_indices(&target.mapping)?;
+    let target_list: Vec<String> = target
+        .mapping
+        .iter()
+        .map(|m| db::quote_ident(&m.target))
+        .collect();
+    let copy_sql = format!(
+        "COPY {} ({}) FROM STDIN (FORMAT csv)",
+        qualified_name(&target.schema, &target.table),
+        target_list.join(", ")
+    );
+
+    let mut processed = 0u64;
+    loop {
+        let mut batch = Vec::with_capacity(BATCH_ROWS);
+        while batch.len() < BATCH_ROWS {
+            match reader.next_row() {
+                Some(row) => batch.push(row?),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            return Ok(());
+        }
+        processed += batch.len() as u64;
+
+        // Each batch runs under a savepoint; a bad row is dropped and the batch replayed
+        loop {
+            client.batch_execute("SAVEPOINT pgstudio_import").await?;
+            match copy_batch(client, &copy_sql, &batch, &indices).await {
+                Ok(rows) => {
+                    client.batch_execute("RELEASE SAVEPOINT pgstudio_import").await?;
+                    result.rows_imported += rows;
+                    break;
+                }
+                Err(e) => {
+                    client
+                        .batch_execute("ROLLBACK TO SAVEPOINT pgstudio_import")
+                        .await?;
+                    let Some((index, column)) =
+                        failed_copy_line(&e).filter(|(index, _)| *index < batch.len())
+                    else {
+                        anyhow::bail!(db::db_error_message(&e));
+                    };
+                    let row = batch.remove(index);
+                    let value = column.as_ref().and_then(|c| {
+                        let n = target.mapping.iter().position(|m| m.target == *c)?;
+                        row.values[indices[n]].clone()
+                    });
+                    result.rows_rejected += 1;
+                    result.errors.push(RowError {
+                        line: row.line,
+                        column,
+                        value,
+                        message: db::db_error_message(&e),
+                    });
+                    if result.errors.len() > max_errors {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        on_progress(processed, result.rows_rejected);
+    }
+}
+
+/// Load a file into a table with `COPY ... FROM STDIN`, all in one transaction.
+/// Up to `max_errors` bad rows (default 0) are skipped and reported; past that the
+/// whole import is rolled back. A dry run does all the work and then rolls back.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_import(
+    import_id: String,
+    connection_id: String,
+    file_path: String,
+    options: ImportOptions,
+    target: ImportTarget,
+    dry_run: Option<bool>,
+    max_errors: Option<usize>,
+    app: tauri::AppHandle,
+    manager: State<'_, ConnectionManager>,
+) -> Result<ImportResult, String> {
+    let started = Instant::now();
+    let dry_run = dry_run.unwrap_or(false);
+    let max_errors = max_errors.unwrap_or(0);
+
+    let mut reader = SourceReader::open(&file_path, &options).map_err(|e| e.to_string())?;
+    let client = manager
+        .open_dedicated(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    client
+        .batch_execute("BEGIN")
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(ref columns) = target.create_columns {
+        client
+            .batch_execute(&create_table_sql(&target.schema, &target.table, columns))
+            .await
+            .map_err(|e| db::db_error_message(&e))?;
+    }
+
+    let mut result = ImportResult {
+        rows_imported: 0,
+        rows_rejected: 0,
+        errors: Vec::new(),
+        dry_run,
+        committed: false,
+        elapsed_ms: 0,
+    };
+    let loaded = load_rows(
+        &client,
+        &mut reader,
+        &target,
+        max_errors,
+        &mut result,
+        |rows_processed, rows_rejected| {
+            let _ = app.emit(
+                "import-progress",
+                ImportProgress {
+                    import_id: import_id.clone(),
+                    rows_processed,
+                    rows_rejected,
+                },
+            );
+        },
+    )
+    .await;
+
+    if let Err(e) = loaded {
+        let _ = client.batch_execute("ROLLBACK").await;
+        return Err(e.to_string());
+    }
+    if dry_run || result.errors.len() > max_errors {
+        client
+            .batch_execute("ROLLBACK")
+            .await
+            .map_err(|e| e.to_string())?;
+    } else {
+        client
+            .batch_execute("COMMIT")
+            .await
+            .map_err(|e| db::db_error_message(&e))?;
+        result.committed = true;
+    }
+    result.elapsed_ms = started.elapsed().as_millis();
+    Ok(result)
+}
@@ -3,6 +3,9 @@ mod db;
 mod storage;
 mod commands;
 mod migration;
+mod export;
+mod import;
+mod script;
 
 use tauri::Manager;
 
@@ -13,6 +16,10 @@ pub fn run() {
         .plugin(tauri_plugin_store::Builder::default().build())
         .plugin(tauri_plugin_dialog::init())
         .manage(db::ConnectionManager::new())
+        .manage(db::PendingConfirmations::new())
+        .manage(db::JobManager::new())
+        .manage(db::MetaState::new())
+        .manage(export::RunningExports::new())
         .manage(ai::AIService::new())
         .setup(|app| {
             let app_handle = app.handle().clone();
@@ -50,6 +57,14 @@ pub fn run() {
             commands::connect,
             commands::disconnect,
             commands::execute_query,
+            commands::fetch_all_rows,
+            commands::fetch_cell,
+            commands::submit_query,
+            commands::list_jobs,
+            commands::get_job_result,
+            commands::cancel_job,
+            commands::set_job_retention,
+            commands::close_session,
             commands::get_databases,
             commands::switch_database,
             commands::get_schemas,
@@ -60,8 +75,40 @@ pub fn run() {
             commands::get_triggers,
             commands::get_rules,
             commands::get_policies,
+            commands::test_rls_as_role,
+            commands::get_functions,
+            commands::get_function_definition,
+            commands::get_views,
+            commands::refresh_materialized_view,
+            commands::get_sequences,
+            commands::get_types,
+            commands::get_extensions,
+            commands::get_available_extensions,
+            commands::get_extension_objects,
+            commands::preview_extension_change,
+            commands::apply_extension_change,
+            commands::get_partitioned_tables,
+            commands::get_partition_tree,
+            commands::preview_partition_change,
+            commands::apply_partition_change,
+            commands::get_roles,
+            commands::get_role_memberships,
+            commands::get_privilege_matrix,
+            commands::generate_privilege_statements,
+            commands::apply_privilege_changes,
+            commands::generate_ddl,
+            commands::compare_schemas,
+            commands::take_schema_snapshot,
+            commands::list_schema_snapshots,
+            commands::diff_schema_snapshots,
+            commands::get_relationship_graph,
+            commands::export_relationship_graph,
+            commands::get_table_stats,
+            commands::get_schema_health_summary,
+            commands::get_index_advice,
             commands::get_table_data,
             commands::get_full_schema,
+            commands::refresh_schema_catalog,
             commands::save_connection,
             commands::list_connections,
             commands::delete_connection,
@@ -71,6 +118,8 @@ pub fn run() {
             commands::save_query,
             commands::get_saved_queries,
             commands::delete_saved_query,
+            commands::detect_query_parameters,
+            commands::run_saved_query,
             commands::ai_configure,
             commands::ai_status,
             commands::ai_get_config,
@@ -85,6 +134,14 @@ pub fn run() {
             migration::pg_dump_to_file,
             migration::pg_restore_from_file,
             migration::pg_transfer,
+            export::export_query,
+            export::copy_export,
+            export::cancel_export,
+            import::preview_import,
+            import::generate_import_table,
+            import::validate_import,
+            import::run_import,
+            script::run_sql_script,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,3 +1,5 @@
+use crate::db::ConnectionManager;
+use crate::script::{run_script, ScriptOptions};
 use crate::storage::LocalDb;
 use serde::{Deserialize, Serialize};
 use std::process::Command;
@@ -188,6 +190,7 @@ pub async fn pg_restore_from_file(
     clean: bool,
     schema_only: bool,
     local_db: State<'_, LocalDb>,
+    manager: State<'_, ConnectionManager>,
 ) -> Result<RestoreResult, String> {
     let info = get_conn_info(&local_db, &connection_id).await?;
 
@@ -203,11 +206,7 @@ pub async fn pg_restore_from_file(
     };
 
     if is_plain_sql {
-        // For plain SQL, read file and execute via psql or direct connection
-        let sql = std::fs::read_to_string(&file_path)
-            .map_err(|e| format!("Failed to read SQL file: {}", e))?;
-
-        // Use psql for plain SQL files
+        // Use psql for plain SQL files when it is installed
         let psql_path = find_pg_binary("psql");
         if let Some(psql) = psql_path {
             let mut cmd = Command::new(&psql);
@@ -234,12 +233,31 @@ pub async fn pg_restore_from_file(
             });
         }
 
-        // Fallback: use the existing connection manager to execute SQL
-        // This is less ideal but works without psql
-        drop(sql); // We'll use psql path above; if no psql, return error
+        // Without psql, run the file with the built-in script runner
+        let protected = manager
+            .get_config(&connection_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .protected;
+        let client = std::sync::Arc::new(
+            manager
+                .open_dedicated(&connection_id)
+                .await
+                .map_err(|e| e.to_string())?,
+        );
+        let options = ScriptOptions {
+            on_error_stop: true,
+            ..Default::default()
+        };
+        let result = run_script(&client, std::path::Path::new(&file_path), &options, protected)
+            .await
+            .map_err(|e| e.to_string())?;
         return Ok(RestoreResult {
-            success: false,
-            error: Some("psql not found on system. Plain SQL restore requires psql.".to_string()),
+            success: result.success,
+            error: result
+                .errors
+                .first()
+                .map(|e| format!("{}:{}: {}", e.file, e.line, e.message)),
         });
     }
 
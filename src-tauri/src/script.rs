@@ -0,0 +1,812 @@
+use crate::db::{self, ConnectionManager};
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::State;
+use tokio_postgres::Client;
+
+/// psql refuses to nest `\i` deeper than this, which also stops include cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScriptOptions {
+    /// Stop at the first error instead of carrying on (psql's ON_ERROR_STOP)
+    #[serde(default)]
+    pub on_error_stop: bool,
+    /// Wrap the whole script in BEGIN/COMMIT; any error rolls everything back
+    #[serde(default)]
+    pub single_transaction: bool,
+    /// Initial values for `:name` substitution, like `psql -v name=value`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptResult {
+    pub success: bool,
+    pub statements_executed: usize,
+    pub rows_copied: u64,
+    /// Lines written by `\echo`, `COPY ... TO STDOUT` and catalog meta-commands such as `\d`
+    pub output: Vec<String>,
+    pub errors: Vec<ScriptError>,
+    pub elapsed_ms: u128,
+}
+
+enum Item {
+    Statement { sql: String, line: usize },
+    Meta { name: String, rest: String, line: usize },
+}
+
+fn is_ident_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_'
+}
+
+fn is_ident_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// End (exclusive) of the quoted string, identifier, comment or dollar-quoted
+/// body starting at `i`, or None if nothing of the sort starts there.
+fn quoted_end(bytes: &[u8], i: usize) -> Option<usize> {
+    let prev = i.checked_sub(1).map(|p| bytes[p]);
+    match bytes[i] {
+        b'\'' => {
+            // E'...' strings allow backslash escapes
+            let escapes = matches!(prev, Some(b'e' | b'E'))
+                && (i < 2 || !is_ident_char(bytes[i - 2]));
+            let mut j = i + 1;
+            while j < bytes.len() {
+                match bytes[j] {
+                    b'\\' if escapes => j += 2,
+                    b'\'' if bytes.get(j + 1) == Some(&b'\'') => j += 2,
+                    b'\'' => return Some(j + 1),
+                    _ => j += 1,
+                }
+            }
+            Some(bytes.len())
+        }
+        b'"' => Some(
+            bytes[i + 1..]
+                .iter()
+                .position(|b| *b == b'"')
+                .map_or(bytes.len(), |p| i + p + 2),
+        ),
+        b'-' if bytes.get(i + 1) == Some(&b'-') => Some(
+            bytes[i..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(bytes.len(), |p| i + p),
+        ),
+        b'/' if bytes.get(i + 1) == Some(&b'*') => {
+            let mut depth = 0;
+            let mut j = i;
+            while j < bytes.len() {
+                if bytes[j..].starts_with(b"/*") {
+                    depth += 1;
+                    j += 2;
+                } else if bytes[j..].starts_with(b"*/") {
+                    depth -= 1;
+                    j += 2;
+                    if depth == 0 {
+                        return Some(j);
+                    }
+                } else {
+                    j += 1;
+                }
+            }
+            Some(bytes.len())
+        }
+        b'$' if prev.is_none_or(|p| !is_ident_char(p)) => {
+            // $$...$$ or $tag$...$tag$, but not $1
+            let mut end = i + 1;
+            while end < bytes.len() && is_ident_char(bytes[end]) {
+                end += 1;
+            }
+            let tag_ok = bytes.get(end) == Some(&b'$')
+                && bytes.get(i + 1).is_none_or(|b| !b.is_ascii_digit());
+            if !tag_ok {
+                return None;
+            }
+            let tag = &bytes[i..=end];
+            Some(
+                bytes[end + 1..]
+                    .windows(tag.len())
+                    .position(|w| w == tag)
+                    .map_or(bytes.len(), |at| end + 1 + at + tag.len()),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// psql's reading of a boolean variable; an empty value counts as on
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.to_ascii_lowercase().as_str(),
+        "" | "on" | "true" | "yes" | "1" | "t" | "y"
+    )
+}
+
+/// Value for a `:name`, `:'name'` or `:"name"` reference at `i`, and where it ends
+fn variable_at(bytes: &[u8], i: usize, vars: &HashMap<String, String>) -> Option<(String, usize)> {
+    match bytes.get(i + 1) {
+        Some(&quote @ (b'\'' | b'"')) => {
+            let close = i + 2 + bytes[i + 2..].iter().position(|b| *b == quote)?;
+            let name = std::str::from_utf8(&bytes[i + 2..close]).ok()?;
+            let value = vars.get(name)?;
+            let quoted = if quote == b'\'' {
+                db::quote_literal(value)
+            } else {
+                db::quote_ident(value)
+            };
+            Some((quoted, close + 1))
+        }
+        Some(b) if is_ident_start(*b) => {
+            let mut end = i + 1;
+            while end < bytes.len() && is_ident_char(bytes[end]) {
+                end += 1;
+            }
+            let name = std::str::from_utf8(&bytes[i + 1..end]).ok()?;
+            Some((vars.get(name)?.clone(), end))
+        }
+        _ => None,
+    }
+}
+
+/// One script file being read, statement by statement
+struct Source {
+    path: PathBuf,
+    text: String,
+    pos: usize,
+    line: usize,
+    /// Statement text read so far; it survives meta-commands in the middle of it
+    buffer: Vec<u8>,
+    buffer_line: usize,
+    /// The buffer holds something besides whitespace and comments
+    has_sql: bool,
+    paren_depth: usize,
+}
+
+impl Source {
+    fn open(path: PathBuf) -> Result<Self> {
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self {
+            path,
+            text,
+            pos: 0,
+            line: 1,
+            buffer: Vec::new(),
+            buffer_line: 1,
+            has_sql: false,
+            paren_depth: 0,
+        })
+    }
+
+    fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    fn copy_to(&mut self, end: usize) {
+        let span = &self.text.as_bytes()[self.pos..end];
+        self.line += span.iter().filter(|b| **b == b'\n').count();
+        self.buffer.extend_from_slice(span);
+        self.pos = end;
+    }
+
+    fn take_statement(&mut self) -> Option<Item> {
+        let has_sql = std::mem::take(&mut self.has_sql);
+        let sql = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+        self.paren_depth = 0;
+        has_sql.then_some(Item::Statement {
+            sql,
+            line: self.buffer_line,
+        })
+    }
+
+    /// Read up to the next complete statement or meta-command, substituting variables
+    fn next_item(&mut self, vars: &HashMap<String, String>) -> Option<Item> {
+        while self.pos < self.text.len() {
+            let bytes = self.text.as_bytes();
+            let b = bytes[self.pos];
+            let comment = matches!(
+                (b, bytes.get(self.pos + 1)),
+                (b'-', Some(b'-')) | (b'/', Some(b'*'))
+            );
+            if !self.has_sql && !b.is_ascii_whitespace() && !comment && b != b'\\' {
+                self.has_sql = true;
+                self.buffer_line = self.line;
+            }
+            if let Some(end) = quoted_end(bytes, self.pos) {
+                self.copy_to(end);
+                continue;
+            }
+            match b {
+                b'\\' => {
+                    let end = self.text[self.pos..]
+                        .find('\n')
+                        .map_or(self.text.len(), |p| self.pos + p);
+                    let command = self.text[self.pos + 1..end].trim();
+                    let (name, rest) = command
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((command, ""));
+                    let item = Item::Meta {
+                        name: name.to_string(),
+                        rest: rest.trim().to_string(),
+                        line: self.line,
+                    };
+                    self.pos = end;
+                    return Some(item);
+                }
+                b';' if self.paren_depth == 0 => {
+                    self.copy_to(self.pos + 1);
+                    if let Some(item) = self.take_statement() {
+                        return Some(item);
+                    }
+                }
+                b':' if bytes.get(self.pos + 1) == Some(&b':') => self.copy_to(self.pos + 2),
+                b':' => match variable_at(bytes, self.pos, vars) {
+                    Some((value, end)) => {
+                        self.buffer.extend_from_slice(value.as_bytes());
+                        self.pos = end;
+                    }
+                    None => self.copy_to(self.pos + 1),
+                },
+                b'(' => {
+                    self.paren_depth += 1;
+                    self.copy_to(self.pos + 1);
+                }
+                b')' => {
+                    self.paren_depth = self.paren_depth.saturating_sub(1);
+                    self.copy_to(self.pos + 1);
+                }
+                _ => self.copy_to(self.pos + 1),
+            }
+        }
+        self.take_statement()
+    }
+
+    /// Inline data after `COPY ... FROM STDIN`: the lines up to a `\.` terminator
+    fn take_copy_data(&mut self) -> Vec<u8> {
+        let mut data = Vec::new();
+        // The data starts on the line after the COPY statement
+        match self.text[self.pos..].find('\n') {
+            Some(p) => {
+                self.pos += p + 1;
+                self.line += 1;
+            }
+            None => self.pos = self.text.len(),
+        }
+        while self.pos < self.text.len() {
+            let end = self.text[self.pos..]
+                .find('\n')
+                .map_or(self.text.len(), |p| self.pos + p);
+            let line = &self.text[self.pos..end];
+            self.pos = (end + 1).min(self.text.len());
+            self.line += 1;
+            if line.trim_end_matches('\r') == "\\." {
+                break;
+            }
+            data.extend_from_slice(line.as_bytes());
+            data.push(b'\n');
+        }
+        data
+    }
+}
+
+/// Split meta-command arguments on whitespace. Single-quoted parts are unquoted,
+/// double-quoted parts are kept as written and `:name` references are substituted.
+fn meta_args(rest: &str, vars: &HashMap<String, String>) -> Vec<String> {
+    let bytes = rest.as_bytes();
+    let mut args = Vec::new();
+    let mut current: Vec<u8> = Vec::new();
+    let mut started = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                if started {
+                    args.push(String::from_utf8_lossy(&std::mem::take(&mut current)).into_owned());
+                    started = false;
+                }
+                i += 1;
+                continue;
+            }
+            b'\'' => {
+                let mut j = i + 1;
+                while j < bytes.len() {
+                    if bytes[j] == b'\'' {
+                        if bytes.get(j + 1) == Some(&b'\'') {
+                            current.push(b'\'');
+                            j += 2;
+                            continue;
+                        }
+                        break;
+                    }
+                    current.push(bytes[j]);
+                    j += 1;
+                }
+                i = j + 1;
+            }
+            b'"' => {
+                let end = bytes[i + 1..]
+                    .iter()
+                    .position(|b| *b == b'"')
+                    .map_or(bytes.len(), |p| i + p + 2);
+                current.extend_from_slice(&bytes[i..end]);
+                i = end;
+            }
+            b':' => match variable_at(bytes, i, vars) {
+                Some((value, end)) => {
+                    current.extend_from_slice(value.as_bytes());
+                    i = end;
+                }
+                None => {
+                    current.push(b':');
+                    i += 1;
+                }
+            },
+            b => {
+                current.push(b);
+                i += 1;
+            }
+        }
+        started = true;
+    }
+    if started {
+        args.push(String::from_utf8_lossy(&current).into_owned());
+    }
+    args
+}
+
+enum CopyFile {
+    /// Data inline in the script, or output to the script's output
+    Inline,
+    Path(String),
+}
+
+struct CopyCommand {
+    sql: String,
+    from: bool,
+    file: CopyFile,
+}
+
+/// Turn `\copy target from|to file [options]` into a server-side
+/// `COPY target FROM STDIN|TO STDOUT [options]` plus the local file.
+fn parse_copy(rest: &str) -> Result<CopyCommand> {
+    let bytes = rest.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+    let mut split = None;
+    while i < bytes.len() {
+        if let Some(end) = quoted_end(bytes, i) {
+            i = end;
+            continue;
+        }
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => depth = depth.saturating_sub(1),
+            b if depth == 0 && b.is_ascii_alphabetic() && (i == 0 || !is_ident_char(bytes[i - 1])) => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| !is_ident_char(*b))
+                    .map_or(bytes.len(), |p| i + p);
+                let word = rest[i..end].to_ascii_lowercase();
+                if i > 0 && (word == "from" || word == "to") {
+                    split = Some((i, end, word == "from"));
+                    break;
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let (at, end, from) = split.ok_or_else(|| anyhow::anyhow!("\\copy: expected FROM or TO"))?;
+    let target = rest[..at].trim();
+    let after = rest[end..].trim_start();
+
+    let (file, options) = if let Some(quoted) = after.strip_prefix('\'') {
+        let mut name = String::new();
+        let mut chars = quoted.char_indices().peekable();
+        let mut close = quoted.len();
+        while let Some((idx, c)) = chars.next() {
+            if c == '\'' {
+                if chars.peek().map(|(_, n)| *n) == Some('\'') {
+                    chars.next();
+                    name.push('\'');
+                    continue;
+                }
+                close = idx + 1;
+                break;
+            }
+            name.push(c);
+        }
+        (CopyFile::Path(name), &quoted[close..])
+    } else {
+        let (word, options) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+        let file = match word.to_ascii_lowercase().as_str() {
+            "" => anyhow::bail!("\\copy: missing file name"),
+            "stdin" | "stdout" => CopyFile::Inline,
+            "pstdin" | "pstdout" | "program" => {
+                anyhow::bail!("\\copy {} is not supported in scripts", word)
+            }
+            _ => CopyFile::Path(word.to_string()),
+        };
+        (file, options)
+    };
+
+    let sql = format!(
+        "COPY {} {} {}",
+        target,
+        if from { "FROM STDIN" } else { "TO STDOUT" },
+        options.trim()
+    );
+    Ok(CopyCommand {
+        sql: sql.trim_end().to_string(),
+        from,
+        file,
+    })
+}
+
+/// Whether a statement is `COPY ... FROM STDIN` / `COPY ... TO STDOUT`
+fn copy_direction(sql: &str) -> Option<bool> {
+    // pg_dump puts a comment block in front of every COPY
+    let mut sql = sql.trim_start();
+    while sql.starts_with("--") || sql.starts_with("/*") {
+        let end = quoted_end(sql.as_bytes(), 0).unwrap_or(sql.len());
+        sql = sql[end..].trim_start();
+    }
+    let words: Vec<String> = sql
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_lowercase())
+        .collect();
+    if words.first().map(String::as_str) != Some("copy") {
+        return None;
+    }
+    words.windows(2).find_map(|pair| match (pair[0].as_str(), pair[1].as_str()) {
+        ("from", "stdin") => Some(true),
+        ("to", "stdout") => Some(false),
+        _ => None,
+    })
+}
+
+struct Script<'a> {
+    client: &'a Arc<Client>,
+    stack: Vec<Source>,
+    vars: HashMap<String, String>,
+    /// Directory `\i` and `\copy` resolve relative paths against
+    base_dir: PathBuf,
+    /// Key from `\restrict`; other meta-commands are refused until it is lifted
+    restricted: Option<String>,
+    /// Refuse statements the guard flags, as the connection is protected
+    protected: bool,
+    quit: bool,
+    result: ScriptResult,
+}
+
+impl Script<'_> {
+    fn resolve(&self, file: &str, relative_to_current: bool) -> PathBuf {
+        let path = Path::new(file);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+        match self.stack.last() {
+            Some(source) if relative_to_current => source.dir().join(path),
+            _ => self.base_dir.join(path),
+        }
+    }
+
+    async fn copy_in(&mut self, sql: &str, mut input: impl Read) -> Result<()> {
+        let sink = self.client.copy_in(sql).await.map_err(|e| anyhow::anyhow!(db::db_error_message(&e)))?;
+        pin_mut!(sink);
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = input.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            sink.send(Bytes::copy_from_slice(&buf[..n]))
+                .await
+                .map_err(|e| anyhow::anyhow!(db::db_error_message(&e)))?;
+        }
+        let rows = sink
+            .finish()
+            .await
+            .map_err(|e| anyhow::anyhow!(db::db_error_message(&e)))?;
+        self.result.rows_copied += rows;
+        Ok(())
+    }
+
+    async fn copy_out(&mut self, sql: &str, mut output: Option<&mut (dyn Write + Send)>) -> Result<()> {
+        let stream = self.client.copy_out(sql).await.map_err(|e| anyhow::anyhow!(db::db_error_message(&e)))?;
+        pin_mut!(stream);
+        let mut text = Vec::new();
+        while let Some(chunk) = stream
+            .try_next()
+            .await
+            .map_err(|e| anyhow::anyhow!(db::db_error_message(&e)))?
+        {
+            match output {
+                Some(ref mut out) => out.write_all(&chunk)?,
+                None => text.extend_from_slice(&chunk),
+            }
+        }
+        self.result
+            .output
+            .extend(String::from_utf8_lossy(&text).lines().map(|l| l.to_string()));
+        Ok(())
+    }
+
+    async fn statement(&mut self, sql: &str) -> Result<()> {
+        if self.protected && !db::classify_statements(sql).is_empty() {
+            anyhow::bail!(
+                "This statement needs confirmation on a protected connection and cannot run from a script"
+            );
+        }
+        match copy_direction(sql) {
+            Some(true) => {
+                let data = self.stack.last_mut().map(Source::take_copy_data).unwrap_or_default();
+                self.copy_in(sql, data.as_slice()).await?;
+            }
+            Some(false) => self.copy_out(sql, None).await?,
+            None => self
+                .client
+                .batch_execute(sql)
+                .await
+                .map_err(|e| anyhow::anyhow!(db::db_error_message(&e)))?,
+        }
+        self.result.statements_executed += 1;
+        Ok(())
+    }
+
+    async fn meta(&mut self, name: &str, rest: &str) -> Result<()> {
+        if let Some(key) = &self.restricted {
+            if name == "unrestrict" && rest == key {
+                self.restricted = None;
+                return Ok(());
+            }
+            anyhow::bail!("Meta-command \\{} is not allowed while restricted", name);
+        }
+        match name {
+            "set" => {
+                let args = meta_args(rest, &self.vars);
+                match args.split_first() {
+                    Some((var, values)) => {
+                        if !var.bytes().all(is_ident_char) {
+                            anyhow::bail!("Invalid variable name: \"{}\"", var);
+                        }
+                        self.vars.insert(var.clone(), values.concat());
+                    }
+                    None => {
+                        let mut vars: Vec<_> = self.vars.iter().collect();
+                        vars.sort();
+                        self.result
+                            .output
+                            .extend(vars.into_iter().map(|(k, v)| format!("{} = '{}'", k, v)));
+                    }
+                }
+            }
+            "unset" => {
+                for var in meta_args(rest, &self.vars) {
+                    self.vars.remove(&var);
+                }
+            }
+            "echo" | "qecho" | "warn" => {
+                let mut args = meta_args(rest, &self.vars);
+                if args.first().map(String::as_str) == Some("-n") {
+                    args.remove(0);
+                }
+                self.result.output.push(args.join(" "));
+            }
+            "i" | "include" | "ir" | "include_relative" => {
+                let file = meta_args(rest, &self.vars)
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("\\{}: missing required argument", name))?;
+                if self.stack.len() >= MAX_INCLUDE_DEPTH {
+                    anyhow::bail!("\\{}: includes nested too deeply", name);
+                }
+                let path = self.resolve(&file, matches!(name, "ir" | "include_relative"));
+                self.stack.push(Source::open(path)?);
+            }
+            "copy" => {
+                let copy = parse_copy(rest)?;
+                match (copy.from, copy.file) {
+                    (true, CopyFile::Inline) => {
+                        let data = self.stack.last_mut().map(Source::take_copy_data).unwrap_or_default();
+                        self.copy_in(&copy.sql, data.as_slice()).await?;
+                    }
+                    (true, CopyFile::Path(file)) => {
+                        let path = self.resolve(&file, true);
+                        let input = File::open(&path)
+                            .with_context(|| format!("Failed to open {}", path.display()))?;
+                        self.copy_in(&copy.sql, input).await?;
+                    }
+                    (false, CopyFile::Inline) => self.copy_out(&copy.sql, None).await?,
+                    (false, CopyFile::Path(file)) => {
+                        let path = self.resolve(&file, true);
+                        let mut out = BufWriter::new(
+                            File::create(&path)
+                                .with_context(|| format!("Failed to create {}", path.display()))?,
+                        );
+                        self.copy_out(&copy.sql, Some(&mut out)).await?;
+                        out.flush()?;
+                    }
+                }
+            }
+            "restrict" => {
+                let key = rest.trim();
+                if key.is_empty() {
+                    anyhow::bail!("\\restrict: missing required argument");
+                }
+                self.restricted = Some(key.to_string());
+            }
+            "q" | "quit" => self.quit = true,
+            // Display settings of interactive psql; script output is plain lines
+            "x" | "timing" | "pset" => {}
+            other => {
+                let command = db::parse_meta_command(&format!("\\{} {}", other, rest));
+                let result = match command {
+                    Some(command) => db::run_catalog_command(self.client, &command).await?,
+                    None => None,
+                };
+                match result {
+                    Some(result) => self.result.output.extend(result_lines(&result)),
+                    None => anyhow::bail!("Unsupported meta-command in script: \\{}", other),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A meta-command result as text: title, header, rows and footer
+fn result_lines(result: &db::QueryResult) -> Vec<String> {
+    let mut lines = vec![result.command_tag.clone()];
+    if !result.columns.is_empty() {
+        let names: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        lines.push(names.join(" | "));
+        for row in &result.rows {
+            let values: Vec<String> = row
+                .iter()
+                .map(|v| match v {
+                    serde_json::Value::Null => String::new(),
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect();
+            lines.push(values.join(" | "));
+        }
+    }
+    lines.extend(result.footer.iter().cloned());
+    lines
+}
+
+/// Run a plain SQL file the way `psql -f` would: statements are sent one at a
+/// time, and `\set`, `\i`/`\ir`, `\copy`, `\echo` and inline COPY data are
+/// handled locally. `\d` and the other catalog meta-commands write their result
+/// to the output. Relative paths resolve against the script's directory.
+/// Scripts have no confirmation step, so on a `protected` connection the
+/// statements the guard flags fail instead of running.
+pub async fn run_script(
+    client: &Arc<Client>,
+    path: &Path,
+    options: &ScriptOptions,
+    protected: bool,
+) -> Result<ScriptResult> {
+    let started = Instant::now();
+    let source = Source::open(path.to_path_buf())?;
+    let mut vars = options.variables.clone();
+    if options.on_error_stop {
+        vars.insert("ON_ERROR_STOP".into(), "on".into());
+    }
+    let mut script = Script {
+        client,
+        base_dir: source.dir().to_path_buf(),
+        stack: vec![source],
+        vars,
+        restricted: None,
+        protected,
+        quit: false,
+        result: ScriptResult {
+            success: false,
+            statements_executed: 0,
+            rows_copied: 0,
+            output: Vec::new(),
+            errors: Vec::new(),
+            elapsed_ms: 0,
+        },
+    };
+
+    if options.single_transaction {
+        client.batch_execute("BEGIN").await?;
+    }
+
+    while !script.quit {
+        let vars = &script.vars;
+        let Some(source) = script.stack.last_mut() else { break };
+        let Some(item) = source.next_item(vars) else {
+            script.stack.pop();
+            continue;
+        };
+        let file = source.path.display().to_string();
+        let (outcome, line) = match item {
+            Item::Statement { sql, line } => (script.statement(&sql).await, line),
+            Item::Meta { name, rest, line } => (script.meta(&name, &rest).await, line),
+        };
+        if let Err(e) = outcome {
+            script.result.errors.push(ScriptError {
+                file,
+                line,
+                message: e.to_string(),
+            });
+            let stop = script.vars.get("ON_ERROR_STOP").is_some_and(|v| is_truthy(v));
+            // Past an error everything in the transaction fails anyway
+            if stop || options.single_transaction {
+                break;
+            }
+        }
+    }
+
+    if options.single_transaction {
+        let end = if script.result.errors.is_empty() { "COMMIT" } else { "ROLLBACK" };
+        if let Err(e) = client.batch_execute(end).await {
+            script.result.errors.push(ScriptError {
+                file: path.display().to_string(),
+                line: 0,
+                message: db::db_error_message(&e),
+            });
+        }
+    }
+
+    let mut result = script.result;
+    result.success = result.errors.is_empty();
+    result.elapsed_ms = started.elapsed().as_millis();
+    Ok(result)
+}
+
+/// Run a .sql script on its own connection, so session settings and open
+/// transactions in the script do not leak into the editor's connection.
+#[tauri::command]
+pub async fn run_sql_script(
+    connection_id: String,
+    file_path: String,
+    options: Option<ScriptOptions>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<ScriptResult, String> {
+    let config = manager
+        .get_config(&connection_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let client = Arc::new(
+        manager
+            .open_dedicated(&connection_id)
+            .await
+            .map_err(|e| e.to_string())?,
+    );
+    run_script(
+        &client,
+        Path::new(&file_path),
+        &options.unwrap_or_default(),
+        config.protected,
+    )
+    .await
+        .map_err(|e| e.to_string())
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::{AppHandle, Manager};
@@ -20,6 +21,25 @@ pub struct QueryHistoryEntry {
     pub success: bool,
     pub error_message: Option<String>,
     pub created_at: String,
+    /// Saved query this run came from, with the parameter values it was given
+    pub saved_query_id: Option<i64>,
+    pub parameters: Option<serde_json::Value>,
+}
+
+/// A named placeholder (`:name` or `${name}`) declared on a saved query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryParameter {
+    pub name: String,
+    #[serde(default = "default_parameter_type")]
+    pub data_type: String,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+fn default_parameter_type() -> String {
+    "text".to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +49,8 @@ pub struct SavedQuery {
     pub sql: String,
     pub connection_id: Option<String>,
     pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: Vec<QueryParameter>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -43,6 +65,25 @@ pub struct ConnectionRecord {
     pub user: String,
     pub ssl_mode: String,
     pub color: Option<String>,
+    pub protected: bool,
+    pub row_limit: u32,
+    pub cell_byte_budget: u32,
+    pub execution_mode: String,
+    pub created_at: String,
+}
+
+/// A stored catalog snapshot, without its contents
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaSnapshotRecord {
+    pub id: i64,
+    pub connection_id: String,
+    pub database: String,
+    /// Counts up per connection and database, one per distinct catalog state
+    pub version: i64,
+    pub hash: String,
+    pub object_count: i64,
+    /// What took it: `connect` or `manual`
+    pub source: String,
     pub created_at: String,
 }
 
@@ -121,6 +162,29 @@ pub async fn init_local_db(app_handle: &AppHandle) -> Result<()> {
             last_used TEXT NOT NULL DEFAULT (datetime('now'))
         );
 
+        CREATE TABLE IF NOT EXISTS matview_refreshes (
+            connection_id TEXT NOT NULL,
+            database TEXT NOT NULL,
+            schema_name TEXT NOT NULL,
+            view_name TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            refreshed_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (connection_id, database, schema_name, view_name)
+        );
+
+        CREATE TABLE IF NOT EXISTS schema_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            connection_id TEXT NOT NULL,
+            database TEXT NOT NULL,
+            version INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            object_count INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            snapshot TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(connection_id, database, version)
+        );
+
         CREATE INDEX IF NOT EXISTS idx_history_connection ON query_history(connection_id);
         CREATE INDEX IF NOT EXISTS idx_history_created ON query_history(created_at DESC);
         CREATE INDEX IF NOT EXISTS idx_analytics_count ON usage_analytics(access_count DESC);
@@ -140,6 +204,36 @@ pub async fn init_local_db(app_handle: &AppHandle) -> Result<()> {
         [],
     );
 
+    // Migration: add protected flag to connections
+    let _ = conn.execute(
+        "ALTER TABLE connections ADD COLUMN protected BOOLEAN NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Migration: add result size limits to connections
+    let _ = conn.execute(
+        "ALTER TABLE connections ADD COLUMN row_limit INTEGER NOT NULL DEFAULT 1000",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE connections ADD COLUMN cell_byte_budget INTEGER NOT NULL DEFAULT 16384",
+        [],
+    );
+
+    // Migration: per-connection wire protocol for editor queries
+    let _ = conn.execute(
+        "ALTER TABLE connections ADD COLUMN execution_mode TEXT NOT NULL DEFAULT 'extended'",
+        [],
+    );
+
+    // Migration: parameter declarations on saved queries, and their use in history
+    let _ = conn.execute(
+        "ALTER TABLE saved_queries ADD COLUMN parameters TEXT NOT NULL DEFAULT '[]'",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE query_history ADD COLUMN saved_query_id INTEGER", []);
+    let _ = conn.execute("ALTER TABLE query_history ADD COLUMN parameters TEXT", []);
+
     // Store in app state
     let local_db = LocalDb {
         conn: Arc::new(Mutex::new(conn)),
@@ -149,13 +243,43 @@ pub async fn init_local_db(app_handle: &AppHandle) -> Result<()> {
     Ok(())
 }
 
+const SCHEMA_SNAPSHOT_COLUMNS: &str =
+    "id, connection_id, database, version, hash, object_count, source, created_at";
+
+fn schema_snapshot_from_row(row: &rusqlite::Row) -> rusqlite::Result<SchemaSnapshotRecord> {
+    Ok(SchemaSnapshotRecord {
+        id: row.get(0)?,
+        connection_id: row.get(1)?,
+        database: row.get(2)?,
+        version: row.get(3)?,
+        hash: row.get(4)?,
+        object_count: row.get(5)?,
+        source: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+fn saved_query_from_row(row: &rusqlite::Row) -> rusqlite::Result<SavedQuery> {
+    let parameters: String = row.get(7)?;
+    Ok(SavedQuery {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        sql: row.get(2)?,
+        connection_id: row.get(3)?,
+        description: row.get(4)?,
+        parameters: serde_json::from_str(&parameters).unwrap_or_default(),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
 impl LocalDb {
     pub async fn save_connection(&self, conn: &ConnectionRecord, password: &str) -> Result<()> {
         let db = self.conn.lock().await;
         db.execute(
-            "INSERT OR REPLACE INTO connections (id, name, host, port, database, user, ssl_mode, color, password, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, COALESCE((SELECT created_at FROM connections WHERE id = ?1), datetime('now')))",
-            rusqlite::params![conn.id, conn.name, conn.host, conn.port, conn.database, conn.user, conn.ssl_mode, conn.color, password],
+            "INSERT OR REPLACE INTO connections (id, name, host, port, database, user, ssl_mode, color, password, protected, row_limit, cell_byte_budget, execution_mode, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, COALESCE((SELECT created_at FROM connections WHERE id = ?1), datetime('now')))",
+            rusqlite::params![conn.id, conn.name, conn.host, conn.port, conn.database, conn.user, conn.ssl_mode, conn.color, password, conn.protected, conn.row_limit, conn.cell_byte_budget, conn.execution_mode],
         )?;
         Ok(())
     }
@@ -163,7 +287,7 @@ impl LocalDb {
     pub async fn list_connections(&self) -> Result<Vec<ConnectionRecord>> {
         let db = self.conn.lock().await;
         let mut stmt = db.prepare(
-            "SELECT id, name, host, port, database, user, ssl_mode, color, created_at FROM connections ORDER BY name",
+            "SELECT id, name, host, port, database, user, ssl_mode, color, protected, row_limit, cell_byte_budget, execution_mode, created_at FROM connections ORDER BY name",
         )?;
         let rows = stmt.query_map([], |row| {
             Ok(ConnectionRecord {
@@ -175,7 +299,11 @@ impl LocalDb {
                 user: row.get(5)?,
                 ssl_mode: row.get(6)?,
                 color: row.get(7)?,
-                created_at: row.get(8)?,
+                protected: row.get(8)?,
+                row_limit: row.get(9)?,
+                cell_byte_budget: row.get(10)?,
+                execution_mode: row.get(11)?,
+                created_at: row.get(12)?,
             })
         })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
@@ -205,10 +333,41 @@ impl LocalDb {
         Ok(())
     }
 
+    /// Record a run of a saved query along with the parameter values used.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_saved_query_history(
+        &self,
+        connection_id: &str,
+        sql: &str,
+        execution_time_ms: i64,
+        row_count: i64,
+        success: bool,
+        error_message: Option<&str>,
+        saved_query_id: i64,
+        parameters: &serde_json::Value,
+    ) -> Result<()> {
+        let db = self.conn.lock().await;
+        db.execute(
+            "INSERT INTO query_history (connection_id, sql, execution_time_ms, row_count, success, error_message, saved_query_id, parameters)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                connection_id,
+                sql,
+                execution_time_ms,
+                row_count,
+                success,
+                error_message,
+                saved_query_id,
+                parameters.to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
     pub async fn get_history(&self, connection_id: &str, limit: i64) -> Result<Vec<QueryHistoryEntry>> {
         let db = self.conn.lock().await;
         let mut stmt = db.prepare(
-            "SELECT id, connection_id, sql, execution_time_ms, row_count, success, error_message, created_at
+            "SELECT id, connection_id, sql, execution_time_ms, row_count, success, error_message, created_at, saved_query_id, parameters
              FROM query_history
              WHERE connection_id = ?1
              ORDER BY created_at DESC
@@ -224,6 +383,10 @@ impl LocalDb {
                 success: row.get(5)?,
                 error_message: row.get(6)?,
                 created_at: row.get(7)?,
+                saved_query_id: row.get(8)?,
+                parameters: row
+                    .get::<_, Option<String>>(9)?
+                    .and_then(|p| serde_json::from_str(&p).ok()),
             })
         })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
@@ -232,7 +395,7 @@ impl LocalDb {
     pub async fn get_all_history(&self, limit: i64) -> Result<Vec<QueryHistoryEntry>> {
         let db = self.conn.lock().await;
         let mut stmt = db.prepare(
-            "SELECT id, connection_id, sql, execution_time_ms, row_count, success, error_message, created_at
+            "SELECT id, connection_id, sql, execution_time_ms, row_count, success, error_message, created_at, saved_query_id, parameters
              FROM query_history
              ORDER BY created_at DESC
              LIMIT ?1",
@@ -247,6 +410,10 @@ impl LocalDb {
                 success: row.get(5)?,
                 error_message: row.get(6)?,
                 created_at: row.get(7)?,
+                saved_query_id: row.get(8)?,
+                parameters: row
+                    .get::<_, Option<String>>(9)?
+                    .and_then(|p| serde_json::from_str(&p).ok()),
             })
         })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
@@ -268,7 +435,7 @@ impl LocalDb {
         let db = self.conn.lock().await;
         let pattern = format!("%{}%", table_name);
         let mut stmt = db.prepare(
-            "SELECT id, connection_id, sql, execution_time_ms, row_count, success, error_message, created_at
+            "SELECT id, connection_id, sql, execution_time_ms, row_count, success, error_message, created_at, saved_query_id, parameters
              FROM query_history
              WHERE connection_id = ?1 AND sql LIKE ?2 AND success = 1
              ORDER BY created_at DESC
@@ -284,6 +451,10 @@ impl LocalDb {
                 success: row.get(5)?,
                 error_message: row.get(6)?,
                 created_at: row.get(7)?,
+                saved_query_id: row.get(8)?,
+                parameters: row
+                    .get::<_, Option<String>>(9)?
+                    .and_then(|p| serde_json::from_str(&p).ok()),
             })
         })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
@@ -292,8 +463,14 @@ impl LocalDb {
     pub async fn save_query(&self, query: &SavedQuery) -> Result<i64> {
         let db = self.conn.lock().await;
         db.execute(
-            "INSERT INTO saved_queries (name, sql, connection_id, description) VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![query.name, query.sql, query.connection_id, query.description],
+            "INSERT INTO saved_queries (name, sql, connection_id, description, parameters) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                query.name,
+                query.sql,
+                query.connection_id,
+                query.description,
+                serde_json::to_string(&query.parameters)?
+            ],
         )?;
         Ok(db.last_insert_rowid())
     }
@@ -301,28 +478,132 @@ impl LocalDb {
     pub async fn get_saved_queries(&self) -> Result<Vec<SavedQuery>> {
         let db = self.conn.lock().await;
         let mut stmt = db.prepare(
-            "SELECT id, name, sql, connection_id, description, created_at, updated_at FROM saved_queries ORDER BY updated_at DESC",
+            "SELECT id, name, sql, connection_id, description, created_at, updated_at, parameters FROM saved_queries ORDER BY updated_at DESC",
         )?;
-        let rows = stmt.query_map([], |row| {
-            Ok(SavedQuery {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                sql: row.get(2)?,
-                connection_id: row.get(3)?,
-                description: row.get(4)?,
-                created_at: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
-        })?;
+        let rows = stmt.query_map([], saved_query_from_row)?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
+    pub async fn get_saved_query(&self, id: i64) -> Result<SavedQuery> {
+        let db = self.conn.lock().await;
+        let query = db.query_row(
+            "SELECT id, name, sql, connection_id, description, created_at, updated_at, parameters FROM saved_queries WHERE id = ?1",
+            [id],
+            saved_query_from_row,
+        )?;
+        Ok(query)
+    }
+
     pub async fn delete_saved_query(&self, id: i64) -> Result<()> {
         let db = self.conn.lock().await;
         db.execute("DELETE FROM saved_queries WHERE id = ?1", [id])?;
         Ok(())
     }
 
+    pub async fn record_matview_refresh(
+        &self,
+        connection_id: &str,
+        database: &str,
+        schema: &str,
+        name: &str,
+        duration_ms: i64,
+    ) -> Result<()> {
+        let db = self.conn.lock().await;
+        db.execute(
+            "INSERT OR REPLACE INTO matview_refreshes (connection_id, database, schema_name, view_name, duration_ms, refreshed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+            rusqlite::params![connection_id, database, schema, name, duration_ms],
+        )?;
+        Ok(())
+    }
+
+    /// Last refresh time of each matview in `schema`, keyed by view name
+    pub async fn get_matview_refreshes(
+        &self,
+        connection_id: &str,
+        database: &str,
+        schema: &str,
+    ) -> Result<HashMap<String, String>> {
+        let db = self.conn.lock().await;
+        let mut stmt = db.prepare(
+            "SELECT view_name, refreshed_at FROM matview_refreshes
+             WHERE connection_id = ?1 AND database = ?2 AND schema_name = ?3",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![connection_id, database, schema], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Store a snapshot as the next version, unless it matches the latest one
+    /// for the connection and database, which is returned instead
+    pub async fn save_schema_snapshot(
+        &self,
+        connection_id: &str,
+        database: &str,
+        hash: &str,
+        object_count: i64,
+        source: &str,
+        snapshot: &str,
+    ) -> Result<SchemaSnapshotRecord> {
+        let db = self.conn.lock().await;
+        let latest = db.query_row(
+            &format!(
+                "SELECT {} FROM schema_snapshots
+                 WHERE connection_id = ?1 AND database = ?2
+                 ORDER BY version DESC LIMIT 1",
+                SCHEMA_SNAPSHOT_COLUMNS
+            ),
+            rusqlite::params![connection_id, database],
+            schema_snapshot_from_row,
+        );
+        let version = match latest {
+            Ok(latest) if latest.hash == hash => return Ok(latest),
+            Ok(latest) => latest.version + 1,
+            Err(rusqlite::Error::QueryReturnedNoRows) => 1,
+            Err(e) => return Err(e.into()),
+        };
+        db.execute(
+            "INSERT INTO schema_snapshots (connection_id, database, version, hash, object_count, source, snapshot)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![connection_id, database, version, hash, object_count, source, snapshot],
+        )?;
+        let record = db.query_row(
+            &format!("SELECT {} FROM schema_snapshots WHERE id = ?1", SCHEMA_SNAPSHOT_COLUMNS),
+            [db.last_insert_rowid()],
+            schema_snapshot_from_row,
+        )?;
+        Ok(record)
+    }
+
+    /// Newest first
+    pub async fn list_schema_snapshots(
+        &self,
+        connection_id: &str,
+        limit: i64,
+    ) -> Result<Vec<SchemaSnapshotRecord>> {
+        let db = self.conn.lock().await;
+        let mut stmt = db.prepare(&format!(
+            "SELECT {} FROM schema_snapshots
+             WHERE connection_id = ?1
+             ORDER BY created_at DESC, id DESC LIMIT ?2",
+            SCHEMA_SNAPSHOT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![connection_id, limit], schema_snapshot_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// The record and its serialized snapshot
+    pub async fn get_schema_snapshot(&self, id: i64) -> Result<(SchemaSnapshotRecord, String)> {
+        let db = self.conn.lock().await;
+        let result = db.query_row(
+            &format!("SELECT {}, snapshot FROM schema_snapshots WHERE id = ?1", SCHEMA_SNAPSHOT_COLUMNS),
+            [id],
+            |row| Ok((schema_snapshot_from_row(row)?, row.get(8)?)),
+        )?;
+        Ok(result)
+    }
+
     pub async fn get_connection_password(&self, id: &str) -> Result<String> {
         let db = self.conn.lock().await;
         let pw: String = db.query_row(